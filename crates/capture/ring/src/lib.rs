@@ -0,0 +1,289 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+#![no_std]
+
+use core::ptr;
+use core::sync::atomic::{AtomicU32, Ordering};
+
+#[cfg(test)]
+mod tests;
+
+// A pcapng stream in a single producer, single consumer byte ring in shared memory.
+//
+// The producer lays out a `Header` at the start of the region and appends whole pcapng blocks
+// after it, starting with a section header and an interface description. The consumer copies
+// out whatever has been written since it last looked, so everything it reads, concatenated, is
+// a valid pcapng file. Packets that don't fit are dropped and counted rather than overwriting
+// anything the consumer hasn't read yet.
+
+/// Bytes at the start of the region taken by the ring's bookkeeping
+pub const HEADER_SIZE: usize = 64;
+
+// Offsets run up to twice the ring size and wrap there, so a full ring can be told apart from
+// an empty one. Wrapping at the end of the `u32` range instead would only land back at the
+// start of the ring for power of two sizes.
+const MAX_RING_SIZE: usize = 1 << 30;
+
+const MAGIC: u32 = u32::from_le_bytes(*b"pcap");
+
+#[repr(C)]
+struct Header {
+    // Set last by the producer once the rest of the header is valid
+    magic: AtomicU32,
+    size: AtomicU32,
+    // Offsets of the next byte to write and read, see `advance`
+    write: AtomicU32,
+    read: AtomicU32,
+    dropped: AtomicU32,
+}
+
+const SHB_TYPE: u32 = 0x0A0D_0D0A;
+const IDB_TYPE: u32 = 0x1;
+const EPB_TYPE: u32 = 0x6;
+const BYTE_ORDER_MAGIC: u32 = 0x1A2B_3C4D;
+const LINKTYPE_ETHERNET: u16 = 1;
+const OPT_ENDOFOPT: u16 = 0;
+const OPT_EPB_FLAGS: u16 = 2;
+
+const SHB_LEN: usize = 28;
+const IDB_LEN: usize = 20;
+const EPB_HEADER_LEN: usize = 28;
+// epb_flags, opt_endofopt and the trailing block length
+const EPB_TRAILER_LEN: usize = 16;
+
+fn advance(size: u32, offset: u32, len: u32) -> u32 {
+    (offset + len) % (2 * size)
+}
+
+// Bytes from `read` to `write`
+fn used(size: u32, write: u32, read: u32) -> u32 {
+    (write + 2 * size - read) % (2 * size)
+}
+
+/// Values are the inbound/outbound bits of the pcapng `epb_flags` option
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Inbound = 0b01,
+    Outbound = 0b10,
+}
+
+// Little endian, as told to readers by the section header's byte order magic
+struct BlockWriter<const N: usize> {
+    buf: [u8; N],
+    len: usize,
+}
+
+impl<const N: usize> BlockWriter<N> {
+    fn new() -> Self {
+        Self {
+            buf: [0; N],
+            len: 0,
+        }
+    }
+
+    fn put(mut self, bytes: &[u8]) -> Self {
+        self.buf[self.len..self.len + bytes.len()].copy_from_slice(bytes);
+        self.len += bytes.len();
+        self
+    }
+
+    fn u16(self, val: u16) -> Self {
+        self.put(&val.to_le_bytes())
+    }
+
+    fn u32(self, val: u32) -> Self {
+        self.put(&val.to_le_bytes())
+    }
+
+    fn finish(self) -> [u8; N] {
+        assert_eq!(self.len, N);
+        self.buf
+    }
+}
+
+pub struct Producer {
+    header: *const Header,
+    data: *mut u8,
+    size: u32,
+    snap_len: u32,
+}
+
+impl Producer {
+    /// Initialize the ring and write the pcapng section header and interface description.
+    ///
+    /// Frames are truncated to `snap_len` bytes.
+    ///
+    /// # Safety
+    ///
+    /// `region` must be valid for `region_size` bytes, suitably aligned for atomics and shared
+    /// with nothing but a `Consumer` for as long as the `Producer` is used.
+    pub unsafe fn new(region: *mut u8, region_size: usize, snap_len: u32) -> Self {
+        assert!(region_size > HEADER_SIZE);
+        let size = (region_size - HEADER_SIZE).min(MAX_RING_SIZE) as u32;
+        let producer = Self {
+            header: region.cast(),
+            data: region.add(HEADER_SIZE),
+            size,
+            snap_len,
+        };
+
+        let header = producer.header();
+        header.magic.store(0, Ordering::Relaxed);
+        header.size.store(size, Ordering::Relaxed);
+        header.write.store(0, Ordering::Relaxed);
+        header.read.store(0, Ordering::Relaxed);
+        header.dropped.store(0, Ordering::Relaxed);
+        header.magic.store(MAGIC, Ordering::Release);
+
+        let shb = BlockWriter::<SHB_LEN>::new()
+            .u32(SHB_TYPE)
+            .u32(SHB_LEN as u32)
+            .u32(BYTE_ORDER_MAGIC)
+            // Version 1.0
+            .u16(1)
+            .u16(0)
+            // Section length not given
+            .put(&(-1i64).to_le_bytes())
+            .u32(SHB_LEN as u32)
+            .finish();
+        let idb = BlockWriter::<IDB_LEN>::new()
+            .u32(IDB_TYPE)
+            .u32(IDB_LEN as u32)
+            .u16(LINKTYPE_ETHERNET)
+            .u16(0)
+            .u32(snap_len)
+            .u32(IDB_LEN as u32)
+            .finish();
+        producer.push(&[&shb, &idb]);
+        producer
+    }
+
+    fn header(&self) -> &Header {
+        unsafe { &*self.header }
+    }
+
+    /// Append an enhanced packet block for `frame`, with the timestamp in microseconds.
+    ///
+    /// Returns false if the ring is too full, in which case the frame is counted as dropped.
+    pub fn write_packet(&self, timestamp_us: u64, direction: Direction, frame: &[u8]) -> bool {
+        let captured = &frame[..frame.len().min(self.snap_len as usize)];
+        let padding = captured.len().next_multiple_of(4) - captured.len();
+        let block_len = (EPB_HEADER_LEN + captured.len() + padding + EPB_TRAILER_LEN) as u32;
+
+        let header = BlockWriter::<EPB_HEADER_LEN>::new()
+            .u32(EPB_TYPE)
+            .u32(block_len)
+            // Interface ID
+            .u32(0)
+            .u32((timestamp_us >> 32) as u32)
+            .u32(timestamp_us as u32)
+            .u32(captured.len() as u32)
+            .u32(frame.len() as u32)
+            .finish();
+        let trailer = BlockWriter::<EPB_TRAILER_LEN>::new()
+            .u16(OPT_EPB_FLAGS)
+            .u16(4)
+            .u32(direction as u32)
+            .u16(OPT_ENDOFOPT)
+            .u16(0)
+            .u32(block_len)
+            .finish();
+
+        let pushed = self.push(&[&header, captured, &[0; 3][..padding], &trailer]);
+        if !pushed {
+            self.header().dropped.fetch_add(1, Ordering::Relaxed);
+        }
+        pushed
+    }
+
+    // Write the pieces as one record, or not at all
+    fn push(&self, pieces: &[&[u8]]) -> bool {
+        let header = self.header();
+        let len: usize = pieces.iter().map(|piece| piece.len()).sum();
+        let write = header.write.load(Ordering::Relaxed);
+        let used = used(self.size, write, header.read.load(Ordering::Acquire));
+        if len > (self.size - used) as usize {
+            return false;
+        }
+
+        let mut offset = write;
+        for piece in pieces {
+            self.copy_in(offset, piece);
+            offset = advance(self.size, offset, piece.len() as u32);
+        }
+        header.write.store(offset, Ordering::Release);
+        true
+    }
+
+    fn copy_in(&self, offset: u32, bytes: &[u8]) {
+        let start = (offset % self.size) as usize;
+        let first = bytes.len().min(self.size as usize - start);
+        unsafe {
+            ptr::copy_nonoverlapping(bytes.as_ptr(), self.data.add(start), first);
+            ptr::copy_nonoverlapping(bytes[first..].as_ptr(), self.data, bytes.len() - first);
+        }
+    }
+
+    pub fn dropped(&self) -> u32 {
+        self.header().dropped.load(Ordering::Relaxed)
+    }
+}
+
+pub struct Consumer {
+    header: *const Header,
+    data: *const u8,
+}
+
+impl Consumer {
+    /// # Safety
+    ///
+    /// `region` must be valid for as long as the `Consumer` is used and shared with nothing but
+    /// a `Producer`, which may set it up later.
+    pub unsafe fn new(region: *mut u8) -> Self {
+        Self {
+            header: region.cast(),
+            data: region.add(HEADER_SIZE),
+        }
+    }
+
+    fn header(&self) -> &Header {
+        unsafe { &*self.header }
+    }
+
+    /// Hand everything written since the last call to `f`, in at most two pieces, and return
+    /// how many bytes that was.
+    pub fn drain(&mut self, mut f: impl FnMut(&[u8])) -> usize {
+        let header = self.header();
+        if header.magic.load(Ordering::Acquire) != MAGIC {
+            return 0;
+        }
+        let size = header.size.load(Ordering::Relaxed);
+        let read = header.read.load(Ordering::Relaxed);
+        let len = used(size, header.write.load(Ordering::Acquire), read) as usize;
+        if len == 0 {
+            return 0;
+        }
+
+        let start = (read % size) as usize;
+        let first = len.min(size as usize - start);
+        unsafe {
+            f(&*ptr::slice_from_raw_parts(self.data.add(start), first));
+            if first < len {
+                f(&*ptr::slice_from_raw_parts(self.data, len - first));
+            }
+        }
+        header
+            .read
+            .store(advance(size, read, len as u32), Ordering::Release);
+        len
+    }
+
+    /// Packets the producer had to drop because the ring was full
+    pub fn dropped(&self) -> u32 {
+        self.header().dropped.load(Ordering::Relaxed)
+    }
+}
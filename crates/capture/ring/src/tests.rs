@@ -0,0 +1,187 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+extern crate std;
+
+use super::*;
+use std::vec;
+use std::vec::Vec;
+
+const SNAP_LEN: u32 = 1514;
+
+// u64 keeps the header aligned for its atomics
+fn region(ring_size: usize) -> Vec<u64> {
+    vec![0; (HEADER_SIZE + ring_size).div_ceil(8)]
+}
+
+// With the section header and interface description already drained
+fn ring(region: &mut [u64], ring_size: usize, snap_len: u32) -> (Producer, Consumer) {
+    let region = region.as_mut_ptr().cast();
+    let (producer, mut consumer) = unsafe {
+        (
+            Producer::new(region, HEADER_SIZE + ring_size, snap_len),
+            Consumer::new(region),
+        )
+    };
+    assert_eq!(drain(&mut consumer).len(), SHB_LEN + IDB_LEN);
+    (producer, consumer)
+}
+
+fn drain(consumer: &mut Consumer) -> Vec<u8> {
+    let mut out = Vec::new();
+    consumer.drain(|bytes| out.extend_from_slice(bytes));
+    out
+}
+
+fn u16_at(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes(bytes[offset..offset + 2].try_into().unwrap())
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+// Split a pcapng stream into its blocks, checking the two copies of each block's length agree
+fn blocks(mut stream: &[u8]) -> Vec<&[u8]> {
+    let mut blocks = Vec::new();
+    while !stream.is_empty() {
+        let len = u32_at(stream, 4) as usize;
+        assert_eq!(len % 4, 0);
+        assert_eq!(u32_at(stream, len - 4) as usize, len);
+        let (block, rest) = stream.split_at(len);
+        blocks.push(block);
+        stream = rest;
+    }
+    blocks
+}
+
+// The captured bytes of an enhanced packet block
+fn epb_data(block: &[u8]) -> &[u8] {
+    assert_eq!(u32_at(block, 0), EPB_TYPE);
+    let captured = u32_at(block, 20) as usize;
+    &block[EPB_HEADER_LEN..EPB_HEADER_LEN + captured]
+}
+
+fn frame(len: usize, seed: u8) -> Vec<u8> {
+    (0..len).map(|i| (i as u8).wrapping_add(seed)).collect()
+}
+
+#[test]
+fn block_layout() {
+    let mut region = region(0x1000);
+    let region = region.as_mut_ptr().cast();
+    let producer = unsafe { Producer::new(region, HEADER_SIZE + 0x1000, 8) };
+    let mut consumer = unsafe { Consumer::new(region) };
+
+    let sent = frame(13, 0);
+    assert!(producer.write_packet(0x1_2345_6789, Direction::Outbound, &sent));
+    let stream = drain(&mut consumer);
+    let blocks = blocks(&stream);
+    assert_eq!(blocks.len(), 3);
+
+    let shb = blocks[0];
+    assert_eq!(shb.len(), SHB_LEN);
+    assert_eq!(u32_at(shb, 0), SHB_TYPE);
+    assert_eq!(u32_at(shb, 8), BYTE_ORDER_MAGIC);
+    assert_eq!((u16_at(shb, 12), u16_at(shb, 14)), (1, 0));
+    assert_eq!(&shb[16..24], &[0xFF; 8]);
+
+    let idb = blocks[1];
+    assert_eq!(idb.len(), IDB_LEN);
+    assert_eq!(u32_at(idb, 0), IDB_TYPE);
+    assert_eq!(u16_at(idb, 8), LINKTYPE_ETHERNET);
+    assert_eq!(u32_at(idb, 12), 8);
+
+    // Truncated to the snap length, which is already a multiple of 4
+    let epb = blocks[2];
+    assert_eq!(epb.len(), EPB_HEADER_LEN + 8 + EPB_TRAILER_LEN);
+    assert_eq!(u32_at(epb, 8), 0);
+    assert_eq!((u32_at(epb, 12), u32_at(epb, 16)), (0x1, 0x2345_6789));
+    assert_eq!((u32_at(epb, 20), u32_at(epb, 24)), (8, 13));
+    assert_eq!(epb_data(epb), &sent[..8]);
+    let options = &epb[EPB_HEADER_LEN + 8..];
+    assert_eq!((u16_at(options, 0), u16_at(options, 2)), (OPT_EPB_FLAGS, 4));
+    assert_eq!(u32_at(options, 4), Direction::Outbound as u32);
+    assert_eq!((u16_at(options, 8), u16_at(options, 10)), (OPT_ENDOFOPT, 0));
+}
+
+#[test]
+fn epb_padding() {
+    let mut region = region(0x1000);
+    let (producer, mut consumer) = ring(&mut region, 0x1000, SNAP_LEN);
+
+    let sent = frame(61, 0);
+    assert!(producer.write_packet(0, Direction::Inbound, &sent));
+    let stream = drain(&mut consumer);
+    let epb = blocks(&stream)[0];
+    assert_eq!(epb.len(), EPB_HEADER_LEN + 64 + EPB_TRAILER_LEN);
+    assert_eq!(epb_data(epb), &sent[..]);
+    assert_eq!(&epb[EPB_HEADER_LEN + 61..EPB_HEADER_LEN + 64], &[0; 3]);
+    assert_eq!(
+        u32_at(epb, EPB_HEADER_LEN + 64 + 4),
+        Direction::Inbound as u32
+    );
+}
+
+#[test]
+fn wraps_around() {
+    // Not a power of two, and not a multiple of any block length
+    const RING_SIZE: usize = 1001;
+    let mut region = region(RING_SIZE);
+    let (producer, mut consumer) = ring(&mut region, RING_SIZE, SNAP_LEN);
+
+    // Enough to go round the ring, and its offsets, many times over
+    for i in 0..2000 {
+        let sent = frame(60 + i % 200, i as u8);
+        assert!(producer.write_packet(i as u64, Direction::Inbound, &sent));
+        let stream = drain(&mut consumer);
+        let blocks = blocks(&stream);
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(u32_at(blocks[0], 16), i as u32);
+        assert_eq!(epb_data(blocks[0]), &sent[..]);
+    }
+    assert_eq!(consumer.dropped(), 0);
+}
+
+#[test]
+fn drops_when_full() {
+    const RING_SIZE: usize = 1000;
+    let mut region = region(RING_SIZE);
+    let (producer, mut consumer) = ring(&mut region, RING_SIZE, SNAP_LEN);
+
+    // 100 byte blocks, so 10 fill the ring exactly
+    let sent = frame(56, 0);
+    for _ in 0..10 {
+        assert!(producer.write_packet(0, Direction::Inbound, &sent));
+    }
+    assert!(!producer.write_packet(0, Direction::Inbound, &sent));
+    assert!(!producer.write_packet(0, Direction::Inbound, &frame(1, 0)));
+    assert_eq!(producer.dropped(), 2);
+    assert_eq!(consumer.dropped(), 2);
+
+    // Nothing already written was overwritten
+    let stream = drain(&mut consumer);
+    assert_eq!(stream.len(), RING_SIZE);
+    let written = blocks(&stream);
+    assert_eq!(written.len(), 10);
+    assert!(written.iter().all(|block| epb_data(block) == &sent[..]));
+
+    // And there's room again once the consumer has caught up
+    assert!(producer.write_packet(0, Direction::Inbound, &sent));
+    assert_eq!(blocks(&drain(&mut consumer)).len(), 1);
+}
+
+#[test]
+fn consumer_waits_for_producer() {
+    let mut region = region(0x1000);
+    let region = region.as_mut_ptr().cast();
+    let mut consumer = unsafe { Consumer::new(region) };
+    assert!(drain(&mut consumer).is_empty());
+
+    let _producer = unsafe { Producer::new(region, HEADER_SIZE + 0x1000, SNAP_LEN) };
+    assert_eq!(drain(&mut consumer).len(), SHB_LEN + IDB_LEN);
+    assert!(drain(&mut consumer).is_empty());
+}
@@ -0,0 +1,78 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+#![no_std]
+#![no_main]
+
+use capture_ring::Consumer;
+use log::{info, warn};
+use sel4_microkit::{
+    debug_print, memory_region_symbol, protection_domain, Channel, Handler, Infallible, MessageInfo,
+};
+
+mod config;
+
+// Drains the driver's packet capture region to the console as hex, one line per `LINE_LEN`
+// bytes prefixed with `PREFIX`. Turn the log back into a pcapng file with:
+//
+//   grep '^pcapng: ' console.log | cut -c9- | xxd -r -p > capture.pcapng
+
+const PREFIX: &str = "pcapng: ";
+const LINE_LEN: usize = 32;
+
+#[protection_domain]
+fn init() -> HandlerImpl {
+    config::log::LOGGER.set().unwrap();
+    let consumer =
+        unsafe { Consumer::new(memory_region_symbol!(net_capture_vaddr: *mut u8).as_ptr()) };
+    info!("Initialized Capture");
+    HandlerImpl {
+        consumer,
+        dropped: 0,
+    }
+}
+
+struct HandlerImpl {
+    consumer: Consumer,
+    dropped: u32,
+}
+
+impl HandlerImpl {
+    fn print(bytes: &[u8]) {
+        for line in bytes.chunks(LINE_LEN) {
+            debug_print!("{PREFIX}");
+            for byte in line {
+                debug_print!("{byte:02x}");
+            }
+            debug_print!("\n");
+        }
+    }
+}
+
+impl Handler for HandlerImpl {
+    type Error = Infallible;
+
+    fn notified(&mut self, channel: Channel) -> Result<(), Self::Error> {
+        if channel == config::channels::NET_DRIVER {
+            self.consumer.drain(Self::print);
+
+            let dropped = self.consumer.dropped();
+            if dropped != self.dropped {
+                warn!("Capture ring full, {dropped} packets dropped so far");
+                self.dropped = dropped;
+            }
+        }
+        Ok(())
+    }
+
+    fn protected(
+        &mut self,
+        _channel: Channel,
+        _msg_info: MessageInfo,
+    ) -> Result<MessageInfo, Self::Error> {
+        unreachable!()
+    }
+}
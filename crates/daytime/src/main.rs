@@ -0,0 +1,196 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+#![no_std]
+#![no_main]
+
+use core::fmt::{self, Write};
+use log::{error, info, warn};
+use ping_interface::{Client, Error, Protocol, Socket};
+use sel4_microkit::{
+    memory_region_symbol, protection_domain, Channel, Handler, Infallible, MessageInfo,
+};
+use timer_interface::{Timer, Utc};
+
+mod config;
+
+// A daytime server (RFC 867) on TCP and UDP, as an example of using `ping`'s sockets from
+// another PD. Try it with:
+//
+//   nc <address> 13
+//   nc -u <address> 13
+//
+// Each TCP connection gets the time and is closed. `ping` notifies us when a socket needs
+// looking at, so everything happens in `notified`.
+
+// Room for the time line, and for whatever a UDP client sends
+const BUF_LEN: usize = 64;
+
+#[protection_domain]
+fn init() -> Daytime {
+    config::log::LOGGER.set().unwrap();
+    let client = unsafe {
+        Client::new(
+            config::channels::NET,
+            memory_region_symbol!(net_data_vaddr: *mut u8).as_ptr(),
+            config::sizes::NET_DATA,
+        )
+    };
+
+    let sockets = client.open(Protocol::Udp).and_then(|udp| {
+        client.bind(udp, config::PORT)?;
+        Ok((udp, listen(&client)?))
+    });
+    let (udp, tcp) = match sockets {
+        Ok(sockets) => sockets,
+        Err(err) => {
+            error!("Failed to open the daytime sockets, there is no service: {err:?}");
+            return Daytime::Idle;
+        }
+    };
+
+    info!("Initialized Daytime on port {}", config::PORT);
+    Daytime::Running(HandlerImpl {
+        client,
+        timer: Timer::new(config::channels::TIMER),
+        udp,
+        tcp: Some(tcp),
+    })
+}
+
+enum Daytime {
+    Running(HandlerImpl),
+    /// Opening the sockets failed, as when `ping` has no network and answers `NetDown`
+    Idle,
+}
+
+struct HandlerImpl {
+    client: Client,
+    timer: Timer,
+    udp: Socket,
+    // None if opening the next listening socket failed, which is tried again on the next
+    // notification
+    tcp: Option<Socket>,
+}
+
+fn listen(client: &Client) -> Result<Socket, Error> {
+    let socket = client.open(Protocol::Tcp)?;
+    client.listen(socket, config::PORT)?;
+    Ok(socket)
+}
+
+// A fixed size line, so nothing allocates
+struct Line {
+    buf: [u8; BUF_LEN],
+    len: usize,
+}
+
+impl Write for Line {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let end = self.len + s.len();
+        self.buf
+            .get_mut(self.len..end)
+            .ok_or(fmt::Error)?
+            .copy_from_slice(s.as_bytes());
+        self.len = end;
+        Ok(())
+    }
+}
+
+impl HandlerImpl {
+    fn daytime(&self) -> Line {
+        let mut line = Line {
+            buf: [0; BUF_LEN],
+            len: 0,
+        };
+        match self.timer.utc_us() {
+            Ok(utc_us) => write!(line, "{}\r\n", Utc(utc_us)),
+            Err(_) => write!(line, "time not synchronized\r\n"),
+        }
+        .unwrap();
+        line
+    }
+
+    fn serve_tcp(&mut self) -> Result<(), Error> {
+        let Some(tcp) = self.tcp else {
+            self.tcp = Some(listen(&self.client)?);
+            return Ok(());
+        };
+        let status = self.client.status(tcp)?;
+        if status.active && status.can_send {
+            let line = self.daytime();
+            self.client.send(tcp, &line.buf[..line.len])?;
+        } else if !status.remote_closed {
+            // Still listening, or not ready to take the line yet
+            return Ok(());
+        }
+        // The connection is closed gracefully and the socket goes back to `ping`
+        self.tcp = None;
+        self.client.close(tcp)?;
+        self.tcp = Some(listen(&self.client)?);
+        Ok(())
+    }
+
+    fn serve_udp(&mut self) -> Result<(), Error> {
+        let mut request = [0; BUF_LEN];
+        loop {
+            let remote = match self.client.recv_from(self.udp, &mut request) {
+                Ok((_, remote)) => remote,
+                Err(Error::WouldBlock) => return Ok(()),
+                Err(err) => return Err(err),
+            };
+            let line = self.daytime();
+            match self.client.send_to(self.udp, &line.buf[..line.len], remote) {
+                // Like any UDP reply, it may be lost
+                Ok(()) | Err(Error::WouldBlock) => {}
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+impl Handler for HandlerImpl {
+    type Error = Infallible;
+
+    fn notified(&mut self, channel: Channel) -> Result<(), Self::Error> {
+        if channel == config::channels::NET {
+            if let Err(err) = self.serve_tcp() {
+                warn!("TCP: {err:?}");
+            }
+            if let Err(err) = self.serve_udp() {
+                warn!("UDP: {err:?}");
+            }
+        }
+        Ok(())
+    }
+
+    fn protected(
+        &mut self,
+        _channel: Channel,
+        _msg_info: MessageInfo,
+    ) -> Result<MessageInfo, Self::Error> {
+        unreachable!()
+    }
+}
+
+impl Handler for Daytime {
+    type Error = Infallible;
+
+    fn notified(&mut self, channel: Channel) -> Result<(), Self::Error> {
+        match self {
+            Self::Running(handler) => handler.notified(channel),
+            Self::Idle => Ok(()),
+        }
+    }
+
+    fn protected(
+        &mut self,
+        _channel: Channel,
+        _msg_info: MessageInfo,
+    ) -> Result<MessageInfo, Self::Error> {
+        unreachable!()
+    }
+}
@@ -0,0 +1,67 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+// Cache maintenance for packet buffers mapped cacheable (the `cached-dma` feature).
+//
+// Without `cached-dma` the buffers are mapped uncached and these do nothing. They also do
+// nothing when not building for aarch64, so the rings can be exercised on the host.
+//
+// Descriptors are never mapped cacheable: several share a cache line, so cleaning one could
+// overwrite a status the GEM just wrote to its neighbour.
+
+// Cortex-A53
+const CACHE_LINE: usize = 64;
+
+#[cfg(all(feature = "cached-dma", target_arch = "aarch64"))]
+mod ops {
+    use core::arch::asm;
+
+    pub fn clean_line(addr: usize) {
+        unsafe { asm!("dc cvac, {}", in(reg) addr, options(nostack, preserves_flags)) }
+    }
+
+    // `dc ivac` is not available at EL0
+    pub fn clean_invalidate_line(addr: usize) {
+        unsafe { asm!("dc civac, {}", in(reg) addr, options(nostack, preserves_flags)) }
+    }
+
+    pub fn barrier() {
+        unsafe { asm!("dsb sy", options(nostack, preserves_flags)) }
+    }
+}
+
+#[cfg(not(all(feature = "cached-dma", target_arch = "aarch64")))]
+mod ops {
+    pub fn clean_line(_addr: usize) {}
+
+    pub fn clean_invalidate_line(_addr: usize) {}
+
+    pub fn barrier() {}
+}
+
+fn for_each_line(buf: &[u8], op: fn(usize)) {
+    let start = buf.as_ptr() as usize & !(CACHE_LINE - 1);
+    let end = buf.as_ptr() as usize + buf.len();
+    for addr in (start..end).step_by(CACHE_LINE) {
+        op(addr);
+    }
+    // Complete the maintenance before ownership of the buffer changes
+    ops::barrier();
+}
+
+/// Make the CPU's writes to `buf` visible to the GEM. Call before handing a buffer to the GEM.
+pub fn clean(buf: &[u8]) {
+    for_each_line(buf, ops::clean_line);
+}
+
+/// Drop `buf` from the cache. Call before handing a buffer to the GEM for it to write, and
+/// again before reading what it wrote, since lines may have been fetched speculatively.
+///
+/// Lines are cleaned as well, so `buf` must not share a cache line with anything the CPU
+/// writes while the GEM owns it.
+pub fn invalidate(buf: &[u8]) {
+    for_each_line(buf, ops::clean_invalidate_line);
+}
@@ -18,26 +18,45 @@ mod rx;
 mod tx;
 
 pub use alloc::{alloc_dma, DmaDef, DmaPtr, DmaPtrs, GemDmaPtrs};
-pub use rx::RxRing;
-pub use tx::{TxDummy, TxRing};
+pub use rx::{RxRing, DESC_SIZE as RX_DESC_SIZE};
+pub use tx::{TxDummy, TxRing, DESC_SIZE as TX_DESC_SIZE};
 
-const NUM_BUFS: usize = 128;
-pub const MTU: usize = 1600;
+pub use eth_driver_interface::MTU;
 
-impl Device for Driver {
-    type RxToken<'token> = GemRxToken<'token> where Self: 'token;
-    type TxToken<'token> = GemTxToken<'token> where Self: 'token;
+pub(crate) const NUM_BUFS: usize = 128;
 
-    // Required methods
-    fn receive(&mut self, _timestamp: Instant) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
-        if self.tx_available() && self.rx_available() {
-            let rx = GemRxToken {
-                rx_ring: &mut self.rx_ring,
+/// Shown each frame the GEM has sent, when its descriptor is reclaimed. The frame isn't shown
+/// if the GEM reported an error sending it.
+pub trait TxObserver {
+    fn sent(&self, frame: &[u8]);
+}
+
+impl TxObserver for () {
+    fn sent(&self, _frame: &[u8]) {}
+}
+
+impl Driver {
+    /// `Device::receive`, showing `observer` the frames sent since it was last called
+    //
+    // Reception doesn't wait for TX space. If the ring is still full when the TX token is
+    // consumed, the reply is dropped instead, see `GemTxToken::consume`.
+    pub fn receive_observed<'a>(
+        &'a mut self,
+        observer: &'a dyn TxObserver,
+    ) -> Option<(GemRxToken<'a>, GemTxToken<'a>)> {
+        self.tx_ring.reclaim(observer);
+        if self.rx_available() {
+            let rx_ring = if self.rx_priority_ring.next_entry_available() {
+                &mut self.rx_priority_ring
+            } else {
+                &mut self.rx_ring
             };
+            let rx = GemRxToken { rx_ring };
 
             let tx = GemTxToken {
                 tx_ring: &mut self.tx_ring,
                 dev: &self.dev,
+                observer,
             };
             Some((rx, tx))
         } else {
@@ -45,17 +64,42 @@ impl Device for Driver {
         }
     }
 
-    fn transmit(&mut self, _timestamp: Instant) -> Option<Self::TxToken<'_>> {
+    /// `Device::transmit`, showing `observer` the frames sent since it was last called
+    pub fn transmit_observed<'a>(
+        &'a mut self,
+        observer: &'a dyn TxObserver,
+    ) -> Option<GemTxToken<'a>> {
+        self.tx_ring.reclaim(observer);
         if self.tx_available() {
             Some(GemTxToken {
                 tx_ring: &mut self.tx_ring,
                 dev: &self.dev,
+                observer,
             })
         } else {
             None
         }
     }
 
+    /// Show `observer` the frames sent since it was last called, without waiting for the next
+    /// frame to be received or sent
+    pub fn reclaim_tx(&mut self, observer: &dyn TxObserver) {
+        self.tx_ring.reclaim(observer);
+    }
+}
+
+impl Device for Driver {
+    type RxToken<'token> = GemRxToken<'token> where Self: 'token;
+    type TxToken<'token> = GemTxToken<'token> where Self: 'token;
+
+    fn receive(&mut self, _timestamp: Instant) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
+        self.receive_observed(&())
+    }
+
+    fn transmit(&mut self, _timestamp: Instant) -> Option<Self::TxToken<'_>> {
+        self.transmit_observed(&())
+    }
+
     fn capabilities(&self) -> DeviceCapabilities {
         let mut dev_caps = DeviceCapabilities::default();
         dev_caps.medium = Medium::Ethernet;
@@ -85,6 +129,7 @@ impl<'a> RxToken for GemRxToken<'a> {
 pub struct GemTxToken<'a> {
     tx_ring: &'a mut TxRing,
     dev: &'a zynqmp_hal::gem::Device<Running>,
+    observer: &'a dyn TxObserver,
 }
 
 impl<'a> TxToken for GemTxToken<'a> {
@@ -93,13 +138,32 @@ impl<'a> TxToken for GemTxToken<'a> {
         F: FnOnce(&mut [u8]) -> R,
     {
         // TODO: This sends a malformed packet if len > MTU. Should we panic instead?
-        let send_len = if len > MTU { MTU } else { len };
-        let tx_packet = self.tx_ring.get_next_buffer(send_len);
-        let result = f(&mut tx_packet[..send_len]);
-        let _desc_paddr = self.tx_ring.send_complete();
+        let len = if len > MTU { MTU } else { len };
+        // Tokens handed out with an RX token aren't checked for space up front
+        self.tx_ring.reclaim(self.observer);
+        if !self.tx_ring.next_entry_available() {
+            return self.drop_frame(len, f);
+        }
+
+        let tx_packet = self.tx_ring.get_next_buffer();
+        let result = f(&mut tx_packet[..len]);
+        let _desc_paddr = self.tx_ring.send_complete(len);
         // TODO: Should we set tx_desc every time?
         self.dev.transmit();
-        debug!("tx_desc: 0x{:0X}", self.dev.get_tx_desc());
+        result
+    }
+}
+
+impl<'a> GemTxToken<'a> {
+    // smoltcp still has to be given a buffer to build the frame in
+    fn drop_frame<R, F>(self, len: usize, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        debug!("TX ring full, dropping {len} byte frame");
+        let tx_packet = self.tx_ring.get_scratch_buffer();
+        let result = f(&mut tx_packet[..len]);
+        self.tx_ring.frame_dropped();
         result
     }
 }
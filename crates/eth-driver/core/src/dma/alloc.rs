@@ -4,13 +4,16 @@
 // SPDX-License-Identifier: BSD-2-Clause
 //
 
+use crate::InitError;
 use core::ptr::NonNull;
 use log::debug;
 
 pub struct GemDmaPtrs {
     pub rx: DmaPtrs,
+    pub rx_priority: DmaPtrs,
     pub tx: DmaPtrs,
     pub tx_dummy: DmaPtr,
+    pub tx_scratch: DmaPtr,
 }
 
 pub struct DmaPtrs {
@@ -31,59 +34,76 @@ pub struct DmaDef {
 
 use super::{MTU, NUM_BUFS};
 
-use super::rx::DESC_SIZE as RX_DESC_SIZE;
-use super::tx::DESC_SIZE as TX_DESC_SIZE;
+use super::{RX_DESC_SIZE, TX_DESC_SIZE};
 
-pub fn alloc_dma(dma: DmaDef) -> GemDmaPtrs {
-    // TODO: Still need to handle alignment
-    // TODO: Implement a more sophisticated allocator?
-    let rx_desc_size = (RX_DESC_SIZE * NUM_BUFS) as isize;
-    let tx_desc_size = (TX_DESC_SIZE * NUM_BUFS) as isize;
-    let buf_size = (MTU * NUM_BUFS) as isize;
-
-    let rx_desc_vaddr = dma.vaddr;
-    let rx_desc_paddr = dma.paddr;
-    unsafe {
-        let rx_buf_vaddr = rx_desc_vaddr.byte_offset(rx_desc_size);
-        let tx_desc_vaddr = rx_buf_vaddr.byte_offset(buf_size);
-        let tx_buf_vaddr = tx_desc_vaddr.byte_offset(tx_desc_size);
-        let tx_dummy_vaddr = tx_buf_vaddr.byte_offset(buf_size);
+// Hands out consecutive chunks of the DMA region
+struct Bump {
+    dma: DmaDef,
+    offset: usize,
+}
 
-        let rx_buf_paddr = rx_desc_paddr.byte_offset(rx_desc_size);
-        let tx_desc_paddr = rx_buf_paddr.byte_offset(buf_size);
-        let tx_buf_paddr = tx_desc_paddr.byte_offset(tx_desc_size);
-        let tx_dummy_paddr = tx_buf_paddr.byte_offset(buf_size);
+impl Bump {
+    fn new(dma: DmaDef) -> Self {
+        Self { dma, offset: 0 }
+    }
 
-        let end = tx_dummy_vaddr.byte_offset(TX_DESC_SIZE as isize);
-        let size = end.byte_offset_from(rx_desc_vaddr);
-        debug!("dma.size: {}, needed_size: {size}", dma.size);
-        assert!(dma.size >= size as usize);
+    // Pointers past the end of the region are caught by `check` before they are used
+    fn take(&mut self, size: usize) -> DmaPtr {
+        let offset = self.offset;
+        self.offset += size;
+        DmaPtr {
+            vaddr: self.dma.vaddr.as_ptr().wrapping_byte_add(offset),
+            paddr: self.dma.paddr.as_ptr().wrapping_byte_add(offset),
+        }
+    }
 
-        GemDmaPtrs {
-            rx: DmaPtrs {
-                desc: DmaPtr {
-                    vaddr: rx_desc_vaddr.as_ptr(),
-                    paddr: rx_desc_paddr.as_ptr(),
-                },
-                buf: DmaPtr {
-                    vaddr: rx_buf_vaddr.as_ptr(),
-                    paddr: rx_buf_paddr.as_ptr(),
-                },
-            },
-            tx: DmaPtrs {
-                desc: DmaPtr {
-                    vaddr: tx_desc_vaddr.as_ptr(),
-                    paddr: tx_desc_paddr.as_ptr(),
-                },
-                buf: DmaPtr {
-                    vaddr: tx_buf_vaddr.as_ptr(),
-                    paddr: tx_buf_paddr.as_ptr(),
-                },
-            },
-            tx_dummy: DmaPtr {
-                vaddr: tx_dummy_vaddr.as_ptr(),
-                paddr: tx_dummy_paddr.as_ptr(),
-            },
+    fn check(&self, region: &'static str) -> Result<(), InitError> {
+        debug!(
+            "{region}.size: {}, needed_size: {}",
+            self.dma.size, self.offset
+        );
+        if self.dma.size < self.offset {
+            return Err(InitError::DmaRegionTooSmall {
+                region,
+                size: self.dma.size,
+                needed: self.offset,
+            });
         }
+        // The GEM only takes 32-bit addresses
+        let paddr = self.dma.paddr.as_ptr() as usize;
+        if u32::try_from(paddr + self.offset.saturating_sub(1)).is_err() {
+            return Err(InitError::AddressNotDmaReachable { paddr });
+        }
+        Ok(())
     }
 }
+
+fn take_ring(descs: &mut Bump, bufs: &mut Bump, desc_size: usize) -> DmaPtrs {
+    DmaPtrs {
+        desc: descs.take(desc_size * NUM_BUFS),
+        buf: bufs.take(MTU * NUM_BUFS),
+    }
+}
+
+/// Carve the rings out of the descriptor and buffer regions.
+///
+/// Descriptors and buffers live in separate regions so that the buffers can be mapped
+/// cacheable (see `cache`). All buffer sizes are multiples of the cache line size, so no two
+/// buffers share a line.
+pub fn alloc_dma(desc_dma: DmaDef, buf_dma: DmaDef) -> Result<GemDmaPtrs, InitError> {
+    // TODO: Still need to handle alignment
+    let mut descs = Bump::new(desc_dma);
+    let mut bufs = Bump::new(buf_dma);
+
+    let ptrs = GemDmaPtrs {
+        rx: take_ring(&mut descs, &mut bufs, RX_DESC_SIZE),
+        tx: take_ring(&mut descs, &mut bufs, TX_DESC_SIZE),
+        tx_dummy: descs.take(TX_DESC_SIZE),
+        rx_priority: take_ring(&mut descs, &mut bufs, RX_DESC_SIZE),
+        tx_scratch: bufs.take(MTU),
+    };
+
+    descs.check("desc_dma")?;
+    bufs.check("buf_dma")?;
+    Ok(ptrs)
+}
@@ -4,15 +4,17 @@
 // SPDX-License-Identifier: BSD-2-Clause
 //
 
+use crate::snapshot::RxDescSnapshot;
 use tock_registers::interfaces::{ReadWriteable, Readable};
 use tock_registers::register_bitfields;
-use tock_registers::registers::ReadWrite;
+use tock_registers::registers::{ReadOnly, ReadWrite};
 
 // Only support 32-bit addressing for now
 #[repr(C)]
 pub struct Descriptor {
     addr: ReadWrite<u32, Addr::Register>,
-    status: u32,
+    // Only written by the GEM
+    status: ReadOnly<u32, Status::Register>,
 }
 
 register_bitfields![u32,
@@ -20,6 +22,13 @@ register_bitfields![u32,
         ADDRESS OFFSET(2) NUMBITS(30) [],
         WRAP OFFSET(1) NUMBITS(1) [],
         AVAIL OFFSET(0) NUMBITS(1) [],
+    ],
+    Status [
+        END_OF_FRAME OFFSET(15) NUMBITS(1) [],
+        START_OF_FRAME OFFSET(14) NUMBITS(1) [],
+        // Bit 13 is the top bit of the length in jumbo frame mode
+        LEN_JUMBO OFFSET(0) NUMBITS(14) [],
+        LEN OFFSET(0) NUMBITS(13) [],
     ]
 ];
 
@@ -46,4 +55,27 @@ impl Descriptor {
     pub fn mark_last(&mut self) {
         self.addr.modify(Addr::WRAP::SET);
     }
+
+    /// Length of the frame the GEM wrote, once it is available
+    pub fn frame_len(&self) -> usize {
+        let len = if cfg!(feature = "jumbo") {
+            self.status.read(Status::LEN_JUMBO)
+        } else {
+            self.status.read(Status::LEN)
+        };
+        len as usize
+    }
+
+    pub fn snapshot(&self) -> RxDescSnapshot {
+        let addr = self.addr.extract();
+        let status = self.status.extract();
+        RxDescSnapshot {
+            addr: addr.read(Addr::ADDRESS) << Addr::ADDRESS.shift,
+            avail: addr.is_set(Addr::AVAIL),
+            wrap: addr.is_set(Addr::WRAP),
+            start_of_frame: status.is_set(Status::START_OF_FRAME),
+            end_of_frame: status.is_set(Status::END_OF_FRAME),
+            len: self.frame_len() as u16,
+        }
+    }
 }
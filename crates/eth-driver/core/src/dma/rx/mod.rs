@@ -5,6 +5,8 @@
 //
 
 use super::{DataBuf, MTU, NUM_BUFS};
+use crate::cache;
+use crate::snapshot::RxRingSnapshot;
 use core::ops::{Deref, DerefMut};
 
 mod descriptor;
@@ -17,6 +19,7 @@ pub const DESC_SIZE: usize = core::mem::size_of::<Descriptor>();
 pub struct RxRing {
     curr_entry: usize,
     buffer: DataBuf,
+    base_paddr: usize,
     entries: *mut [Descriptor; NUM_BUFS],
 }
 
@@ -27,6 +30,7 @@ impl RxRing {
         let mut ring = Self {
             curr_entry: 0,
             buffer: DataBuf::new(buf_ptr),
+            base_paddr: dma_ptrs.desc.paddr as usize,
             entries,
         };
         ring.setup(dma_ptrs.buf.paddr as usize);
@@ -38,6 +42,9 @@ impl RxRing {
     }
 
     fn setup(&mut self, buffers_paddr: usize) {
+        for i in 0..NUM_BUFS {
+            cache::invalidate(self.buffer.get(i));
+        }
         for (i, entry) in self.iter_mut().enumerate() {
             entry.set_addr(buffers_paddr + (i * MTU));
             entry.mark_done();
@@ -45,16 +52,44 @@ impl RxRing {
         self.last_mut().unwrap().mark_last();
     }
 
+    pub fn base_paddr(&self) -> u32 {
+        self.base_paddr as u32
+    }
+
+    /// Give every buffer back to the GEM, dropping frames that haven't been received, and
+    /// start again from the first entry. Only call while reception is disabled.
+    pub fn reset(&mut self) {
+        for i in 0..NUM_BUFS {
+            cache::invalidate(self.buffer.get(i));
+            self.get_mut(i).unwrap().mark_done();
+        }
+        self.curr_entry = 0;
+    }
+
+    pub fn snapshot(&self) -> RxRingSnapshot {
+        RxRingSnapshot {
+            base_paddr: self.base_paddr(),
+            curr_entry: self.curr_entry,
+            descs: core::array::from_fn(|i| self[i].snapshot()),
+        }
+    }
+
     pub fn next_entry_available(&self) -> bool {
         self.get(self.curr_entry).unwrap().is_available()
     }
 
+    /// The frame in the next entry, as long as the GEM says it is
     pub fn recv_next(&mut self) -> &mut [u8] {
-        self.buffer.get(self.curr_entry)
+        let len = self[self.curr_entry].frame_len().min(MTU);
+        let packet = self.buffer.get(self.curr_entry);
+        cache::invalidate(packet);
+        &mut packet[..len]
     }
 
     pub fn mark_done(&mut self) {
         let curr_entry = self.curr_entry;
+        // The client may have written to the buffer
+        cache::invalidate(self.buffer.get(curr_entry));
         self.get_mut(curr_entry).unwrap().mark_done();
         let entries_len = self.len();
         self.curr_entry = (self.curr_entry + 1) % entries_len;
@@ -4,6 +4,7 @@
 // SPDX-License-Identifier: BSD-2-Clause
 //
 
+use crate::snapshot::TxDescSnapshot;
 use tock_registers::interfaces::{ReadWriteable, Readable, Writeable};
 use tock_registers::register_bitfields;
 use tock_registers::registers::ReadWrite;
@@ -49,6 +50,15 @@ impl Descriptor {
         self.status.is_set(Status::USED)
     }
 
+    /// Once the GEM has handed the descriptor back, whether the frame failed to go out
+    pub fn failed(&self) -> bool {
+        self.status.matches_any(&[
+            Status::RETRY_LIMIT::SET,
+            Status::AXI_ERR::SET,
+            Status::LATE_COLLISION::SET,
+        ])
+    }
+
     pub fn mark_gem_owned(&mut self) {
         self.status.modify(Status::USED::CLEAR);
     }
@@ -80,4 +90,15 @@ impl Descriptor {
     pub fn mark_last(&mut self) {
         self.status.modify(Status::WRAP::SET);
     }
+
+    pub fn snapshot(&self) -> TxDescSnapshot {
+        let status = self.status.extract();
+        TxDescSnapshot {
+            addr: self.addr,
+            used: status.is_set(Status::USED),
+            wrap: status.is_set(Status::WRAP),
+            frame_end: status.is_set(Status::FRAME_END),
+            len: status.read(Status::LEN) as u16,
+        }
+    }
 }
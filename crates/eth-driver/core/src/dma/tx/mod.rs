@@ -4,7 +4,9 @@
 // SPDX-License-Identifier: BSD-2-Clause
 //
 
-use super::{DataBuf, DmaPtr, DmaPtrs, MTU, NUM_BUFS};
+use super::{DataBuf, DmaPtr, DmaPtrs, TxObserver, MTU, NUM_BUFS};
+use crate::cache;
+use crate::snapshot::TxRingSnapshot;
 use core::ops::{Deref, DerefMut};
 
 mod descriptor;
@@ -48,22 +50,37 @@ impl DerefMut for TxDummy {
 
 pub struct TxRing {
     curr_entry: usize,
+    // Oldest entry still owned by the GEM
+    head: usize,
+    in_flight: usize,
+    // Of the frame in each entry's buffer
+    lens: [usize; NUM_BUFS],
     buffer: DataBuf,
+    buffers_paddr: usize,
     base_paddr: usize,
     entries: *mut [Descriptor; NUM_BUFS],
+    // Frames are built here and discarded when the ring is full
+    scratch: *mut [u8; MTU],
+    dropped: usize,
 }
 
 impl TxRing {
-    pub fn new(dma_ptrs: &DmaPtrs) -> Self {
+    pub fn new(dma_ptrs: &DmaPtrs, scratch_ptr: &DmaPtr) -> Self {
         let entries = dma_ptrs.desc.vaddr.cast();
         let buf_ptr = dma_ptrs.buf.vaddr.cast();
         let mut ring = Self {
             curr_entry: 0,
+            head: 0,
+            in_flight: 0,
+            lens: [0; NUM_BUFS],
             buffer: DataBuf::new(buf_ptr),
+            buffers_paddr: dma_ptrs.buf.paddr as usize,
             base_paddr: dma_ptrs.desc.paddr as usize,
             entries,
+            scratch: scratch_ptr.vaddr.cast(),
+            dropped: 0,
         };
-        ring.setup(dma_ptrs.buf.paddr as usize);
+        ring.setup();
         ring
     }
 
@@ -71,42 +88,115 @@ impl TxRing {
         self.entries
     }
 
-    fn setup(&mut self, buffers_paddr: usize) {
-        for (i, entry) in self.iter_mut().enumerate() {
-            entry.set_addr(buffers_paddr + (i * MTU));
+    fn setup(&mut self) {
+        for i in 0..self.len() {
+            let buf_paddr = self.buf_paddr(i);
+            let entry = self.get_mut(i).unwrap();
+            entry.set_addr(buf_paddr);
             entry.mark_sw_owned();
         }
         self.last_mut().unwrap().mark_last();
     }
 
+    fn buf_paddr(&self, idx: usize) -> usize {
+        self.buffers_paddr + (idx * MTU)
+    }
+
     pub fn next_entry_available(&self) -> bool {
-        self.get(self.curr_entry).unwrap().is_available()
+        self.free_entries() > 0
     }
 
-    pub fn get_next_buffer(&mut self, len: usize) -> &mut [u8] {
-        let curr_entry = self.curr_entry;
-        let desc = self.get_mut(curr_entry).unwrap();
-        desc.clear_status();
-        desc.set_len(len);
-        // Assume only single buffer sized frames
-        desc.mark_frame_end();
-        desc.mark_gem_owned();
+    pub fn base_paddr(&self) -> u32 {
+        self.desc_paddr(0)
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.in_flight
+    }
+
+    /// Take back every descriptor, abandoning frames still in flight, and start again from the
+    /// first entry. Only call while transmission is disabled.
+    pub fn reset(&mut self) {
+        for entry in self.iter_mut() {
+            entry.mark_sw_owned();
+        }
+        self.curr_entry = 0;
+        self.head = 0;
+        self.in_flight = 0;
+    }
+
+    pub fn snapshot(&self) -> TxRingSnapshot {
+        TxRingSnapshot {
+            base_paddr: self.base_paddr(),
+            curr_entry: self.curr_entry,
+            head: self.head,
+            in_flight: self.in_flight,
+            descs: core::array::from_fn(|i| self[i].snapshot()),
+        }
+    }
+
+    pub fn free_entries(&self) -> usize {
+        self.len() - self.in_flight
+    }
 
+    /// Return descriptors of completed frames to software, showing `observer` the frames that
+    /// went out.
+    pub fn reclaim(&mut self, observer: &dyn TxObserver) {
+        while self.in_flight > 0 {
+            let head = self.head;
+            let desc = self.get(head).unwrap();
+            if !desc.is_available() {
+                break;
+            }
+            if !desc.failed() {
+                observer.sent(&self.buffer[head][..self.lens[head]]);
+            }
+            self.head = self.idx_after(head, 1);
+            self.in_flight -= 1;
+        }
+    }
+
+    pub fn get_next_buffer(&mut self) -> &mut [u8] {
         self.buffer.get(self.curr_entry)
     }
 
+    pub fn get_scratch_buffer(&mut self) -> &mut [u8] {
+        unsafe { &mut *self.scratch }
+    }
+
+    pub fn frame_dropped(&mut self) {
+        self.dropped = self.dropped.wrapping_add(1);
+    }
+
+    /// Number of frames dropped because the ring was full
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+
+    // `alloc_dma` checked the whole ring is below 4 GiB
     fn desc_paddr(&self, idx: usize) -> u32 {
-        (self.base_paddr + idx * 8).try_into().unwrap()
+        (self.base_paddr + idx * DESC_SIZE) as u32
     }
 
-    fn increment_idx(&mut self) {
-        let entries_len = self.len();
-        self.curr_entry = (self.curr_entry + 1) % entries_len;
+    fn idx_after(&self, idx: usize, n: usize) -> usize {
+        (idx + n) % self.len()
     }
 
-    pub fn send_complete(&mut self) -> u32 {
+    /// Hand the frame written to the buffer from `get_next_buffer` to the GEM.
+    pub fn send_complete(&mut self, len: usize) -> u32 {
         let curr_entry = self.curr_entry;
-        self.increment_idx();
+        cache::clean(&self.buffer.get(curr_entry)[..len]);
+        let buf_paddr = self.buf_paddr(curr_entry);
+        let desc = self.get_mut(curr_entry).unwrap();
+        desc.clear_status();
+        desc.set_addr(buf_paddr);
+        desc.set_len(len);
+        desc.mark_frame_end();
+        desc.mark_gem_owned();
+
+        self.lens[curr_entry] = len;
+        self.in_flight += 1;
+        self.curr_entry = self.idx_after(curr_entry, 1);
         self.desc_paddr(curr_entry)
     }
 }
@@ -6,21 +6,58 @@
 
 #![no_std]
 
+#[cfg(feature = "mock")]
+extern crate alloc;
+
 use eth_phy::dp83867::{DP83867Conf, Phy, PortMirroring};
 use eth_phy::{configure_phy, GenPhy, PhyInterface, Supported};
 use log::info;
+use tock_registers::interfaces::{ReadWriteable, Readable, Writeable};
 use zynqmp_hal::gem::{Device, MacAddress, Running};
 
+mod cache;
 mod dma;
+mod lifecycle;
+mod multicast;
+mod phy;
+pub mod platform;
+mod regs;
+mod screener;
+#[cfg(feature = "sel4")]
 mod sel4_interfaces;
+mod snapshot;
+mod status;
+#[cfg(all(test, feature = "mock"))]
+mod tests;
+mod unicast;
 
-pub use dma::DmaDef;
 use dma::{alloc_dma, GemDmaPtrs, RxRing, TxDummy, TxRing};
+pub use dma::{DmaDef, GemRxToken, GemTxToken, TxObserver, MTU};
+pub use platform::Platform;
+use regs::{
+    DmaConfig, DmaRxBufSize, JumboMaxLength, MdioTransfer, NetworkConfig, QueueInterrupt, Regs,
+    RX_BUF_SIZE_UNIT,
+};
+pub use screener::{
+    Compare, CompareBase, RxQueue, ScreenerAction, ScreenerError, Type1Screener, Type2Screener,
+};
+pub use snapshot::{
+    RegsSnapshot, RxDescSnapshot, RxRingSnapshot, Snapshot, TxDescSnapshot, TxRingSnapshot,
+};
+pub use unicast::TooManyAddresses;
 
 pub struct Driver {
     dev: Device<Running>,
+    regs: Regs,
     rx_ring: RxRing,
+    // Fed by the screeners, see `screener`
+    rx_priority_ring: RxRing,
     tx_ring: TxRing,
+    // See `lifecycle`
+    running: bool,
+    phy_powered_down: bool,
+    // See `status`
+    counters: status::Counters,
 }
 
 #[derive(Debug)]
@@ -30,28 +67,72 @@ pub enum IrqType {
     Unknown,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InitError {
+    /// `region` is `size` bytes but the rings need `needed`
+    DmaRegionTooSmall {
+        region: &'static str,
+        size: usize,
+        needed: usize,
+    },
+    /// Nothing answered on MDIO at the PHY's address
+    PhyNotDetected,
+    /// The PHY didn't complete autonegotiation, as when no cable is plugged in
+    AutonegTimeout,
+    /// The GEM's PHY management interface didn't finish a transfer
+    MdioTimeout,
+    /// The DMA region at `paddr` extends above 4 GiB, which the GEM can't address
+    AddressNotDmaReachable { paddr: usize },
+}
+
 const MAC: [u8; 6] = [0x00, 0x0A, 0x35, 0x03, 0x78, 0xA1];
 
+// ZCU102 PHY
+const PHY_ADDR: u8 = 0xc;
+
 impl Driver {
-    pub fn new(ptr: *mut (), dma: DmaDef) -> Self {
-        let dma_ptrs = alloc_dma(dma);
+    /// `desc_dma` holds the descriptor rings and must be mapped uncached. `buf_dma` holds the
+    /// packet buffers, which may be mapped cacheable with the `cached-dma` feature.
+    pub fn new(ptr: *mut (), desc_dma: DmaDef, buf_dma: DmaDef) -> Result<Self, InitError> {
+        Self::with_mdio_transfer(ptr, desc_dma, buf_dma, |_| {})
+    }
+
+    fn with_mdio_transfer(
+        ptr: *mut (),
+        desc_dma: DmaDef,
+        buf_dma: DmaDef,
+        mdio_transfer: MdioTransfer,
+    ) -> Result<Self, InitError> {
+        let dma_ptrs = alloc_dma(desc_dma, buf_dma)?;
         let rx_ring = RxRing::new(&dma_ptrs.rx);
-        let tx_ring = TxRing::new(&dma_ptrs.tx);
+        let rx_priority_ring = RxRing::new(&dma_ptrs.rx_priority);
+        let tx_ring = TxRing::new(&dma_ptrs.tx, &dma_ptrs.tx_scratch);
         let _tx_dummy = TxDummy::new(&dma_ptrs.tx_dummy);
-        let dev = Self::init(ptr, &dma_ptrs);
+        let regs = Regs::new(ptr, mdio_transfer);
+        let dev = Self::init(ptr, &regs, &dma_ptrs)?;
 
-        Self {
+        Ok(Self {
             dev,
+            regs,
             rx_ring,
+            rx_priority_ring,
             tx_ring,
-        }
+            running: true,
+            phy_powered_down: false,
+            counters: Default::default(),
+        })
     }
 
-    fn init(ptr: *mut (), dma_ptrs: &GemDmaPtrs) -> Device<Running> {
+    fn init(
+        ptr: *mut (),
+        regs: &Regs,
+        dma_ptrs: &GemDmaPtrs,
+    ) -> Result<Device<Running>, InitError> {
         info!("Initializing Driver");
         let dev = Device::new(ptr.cast());
         let dev = dev.init();
         info!("Initialized GEM device");
+        phy::detect(regs, PHY_ADDR)?;
 
         let (speed, duplex) = {
             let supported = Supported {
@@ -79,27 +160,60 @@ impl Driver {
                 sgmii_ref_clk_en: false,
                 interface: PhyInterface::RgmiiId,
             };
-            let genphy = GenPhy::new(0xc, &dev, supported);
+            let genphy = GenPhy::new(PHY_ADDR, &dev, supported);
             let phy = Phy::new(&genphy, conf);
             configure_phy(&genphy, &phy)
         };
+        if !phy::wait_for_autoneg(regs, PHY_ADDR)? {
+            return Err(InitError::AutonegTimeout);
+        }
         let dev = dev.phy_complete();
 
         dev.set_rx_desc(dma_ptrs.rx.desc.paddr as u32);
         // TODO: Should this be done each time a packet is sent?
         dev.set_tx_desc(dma_ptrs.tx.desc.paddr as u32);
         dev.set_tx_q1_desc(dma_ptrs.tx_dummy.paddr as u32);
+        Self::init_priority_queue(regs, dma_ptrs);
         dev.set_mac_address(MacAddress::new(MAC));
 
         info!("PHY: Speed: {speed:?}, Duplex: {duplex:?}");
         dev.set_speed(speed);
         dev.set_duplex(duplex);
 
-        dev.run()
+        Self::configure_frame_size(regs);
+
+        Ok(dev.run())
+    }
+
+    fn configure_frame_size(regs: &Regs) {
+        let rx_buf_size = (MTU / RX_BUF_SIZE_UNIT) as u32;
+        regs.dma_config
+            .modify(DmaConfig::RX_BUF_SIZE.val(rx_buf_size));
+
+        if cfg!(feature = "jumbo") {
+            info!("Enabling jumbo frames: {MTU} bytes");
+            regs.jumbo_max_length
+                .write(JumboMaxLength::MAX_LENGTH.val(MTU as u32));
+            regs.network_config.modify(NetworkConfig::JUMBO_FRAMES::SET);
+        }
+    }
+
+    fn init_priority_queue(regs: &Regs, dma_ptrs: &GemDmaPtrs) {
+        regs.receive_q1_ptr
+            .set(dma_ptrs.rx_priority.desc.paddr as u32);
+        regs.dma_rxbuf_size_q1
+            .write(DmaRxBufSize::RX_BUF_SIZE.val((MTU / RX_BUF_SIZE_UNIT) as u32));
+        regs.int_q1_enable
+            .write(QueueInterrupt::RECEIVE_COMPLETE::SET);
     }
 
     pub fn get_irq_type(&self) -> IrqType {
-        if self.dev.rx_is_complete() {
+        if self.dev.rx_is_complete()
+            || self
+                .regs
+                .int_q1_status
+                .is_set(QueueInterrupt::RECEIVE_COMPLETE)
+        {
             IrqType::RxComplete
         } else if self.dev.tx_is_complete() {
             IrqType::TxComplete
@@ -108,11 +222,38 @@ impl Driver {
         }
     }
 
+    /// Clear the GEM's interrupt status. Call once the platform has delivered its interrupt.
+    pub fn ack_interrupts(&mut self) {
+        if self.dev.rx_is_complete() {
+            let _sta = self.dev.get_receive_status();
+        }
+        if self.dev.tx_is_complete() {
+            let _val = self.dev.get_transmit_status();
+        }
+
+        self.clear_queue_interrupts();
+        self.dev.clear_all_interrupts();
+    }
+
+    pub fn mac_address(&self) -> [u8; 6] {
+        self.dev.mac_address().inner()
+    }
+
     pub fn rx_available(&self) -> bool {
-        self.rx_ring.next_entry_available()
+        self.rx_priority_ring.next_entry_available() || self.rx_ring.next_entry_available()
+    }
+
+    // Queue interrupt status for queues other than 0 is cleared on read
+    fn clear_queue_interrupts(&self) {
+        let _sta = self.regs.int_q1_status.get();
     }
 
     pub fn tx_available(&self) -> bool {
-        self.tx_ring.next_entry_available()
+        self.running && self.tx_ring.next_entry_available()
+    }
+
+    /// Number of frames dropped because the TX ring was full when they were sent
+    pub fn tx_dropped(&self) -> usize {
+        self.tx_ring.dropped()
     }
 }
@@ -0,0 +1,84 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+use super::{phy, Driver, TxObserver, PHY_ADDR};
+use crate::regs::NetworkControl;
+use log::{info, warn};
+use tock_registers::interfaces::{ReadWriteable, Writeable};
+
+// Stopping and restarting the GEM. Only the GEM's side of the rings is reset, so clients keep
+// their own ring state across a stop.
+
+// Reclaiming is a few uncached reads, this allows for well over a full ring to go out
+const TX_DRAIN_POLL_LIMIT: usize = 1_000_000;
+
+impl Driver {
+    pub fn is_running(&self) -> bool {
+        self.running
+    }
+
+    /// Let in-flight frames go out, then disable reception and transmission.
+    ///
+    /// Frames still in flight after a while are abandoned, as are received frames that
+    /// haven't been handed to the client yet. `observer` is shown the frames that do go out.
+    pub fn stop(&mut self, power_down_phy: bool, observer: &dyn TxObserver) {
+        if !self.running {
+            return;
+        }
+
+        let drained = (0..TX_DRAIN_POLL_LIMIT).any(|_| {
+            self.tx_ring.reclaim(observer);
+            self.tx_ring.in_flight() == 0
+        });
+        if !drained {
+            warn!(
+                "Abandoning {} TX descriptors still in flight",
+                self.tx_ring.in_flight()
+            );
+        }
+        self.regs
+            .network_control
+            .modify(NetworkControl::RX_ENABLE::CLEAR + NetworkControl::TX_ENABLE::CLEAR);
+
+        // The GEM goes back to the start of each ring once it is disabled
+        self.tx_ring.reset();
+        self.rx_ring.reset();
+        self.rx_priority_ring.reset();
+
+        if power_down_phy {
+            match phy::set_power_down(&self.regs, PHY_ADDR, true) {
+                Ok(()) => self.phy_powered_down = true,
+                Err(err) => warn!("Failed to power down PHY: {err:?}"),
+            }
+        }
+        self.running = false;
+        info!("Stopped driver");
+    }
+
+    pub fn start(&mut self) {
+        if self.running {
+            return;
+        }
+
+        if self.phy_powered_down {
+            match phy::set_power_down(&self.regs, PHY_ADDR, false) {
+                Ok(()) => self.phy_powered_down = false,
+                Err(err) => warn!("Failed to power up PHY: {err:?}"),
+            }
+        }
+
+        self.regs.receive_q_ptr.set(self.rx_ring.base_paddr());
+        self.regs.transmit_q_ptr.set(self.tx_ring.base_paddr());
+        self.regs
+            .receive_q1_ptr
+            .set(self.rx_priority_ring.base_paddr());
+        self.regs
+            .network_control
+            .modify(NetworkControl::RX_ENABLE::SET + NetworkControl::TX_ENABLE::SET);
+        self.running = true;
+        info!("Started driver");
+    }
+}
@@ -0,0 +1,39 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+use super::Driver;
+use crate::regs::NetworkConfig;
+use tock_registers::interfaces::{ReadWriteable, Writeable};
+
+// Multicast reception through the GEM's 64 bin hash filter. A group hashing to the same bin as
+// one that was asked for is let through too, so the stack still has to check the destination.
+
+// Bit `n` of the index is the XOR of every 6th bit of the address starting from bit `n`, where
+// bit 0 is the least significant bit of the first byte
+fn hash_index(addr: &[u8; 6]) -> u32 {
+    let bit = |i: usize| (addr[i / 8] >> (i % 8)) as u32 & 1;
+    (0..6).fold(0, |index, n| {
+        let parity = (n..48).step_by(6).fold(0, |parity, i| parity ^ bit(i));
+        index | (parity << n)
+    })
+}
+
+impl Driver {
+    /// Receive frames sent to the multicast MAC addresses in `groups`, replacing any set
+    /// before. An empty list turns multicast reception off.
+    pub fn set_multicast_filter(&mut self, groups: &[[u8; 6]]) {
+        let hash = groups
+            .iter()
+            .fold(0u64, |hash, group| hash | (1 << hash_index(group)));
+        self.regs.hash_bottom.set(hash as u32);
+        self.regs.hash_top.set((hash >> 32) as u32);
+        self.regs.network_config.modify(if hash != 0 {
+            NetworkConfig::MULTICAST_HASH_ENABLE::SET
+        } else {
+            NetworkConfig::MULTICAST_HASH_ENABLE::CLEAR
+        });
+    }
+}
@@ -0,0 +1,107 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+use crate::regs::{NetworkStatus, PhyManagement, Regs};
+use crate::InitError;
+use tock_registers::fields::FieldValue;
+use tock_registers::interfaces::{Readable, Writeable};
+
+// Just enough MDIO to check on the PHY before and after eth_phy configures it, since
+// `configure_phy` doesn't report failures, and to power it down while the driver is stopped.
+
+// MDIO transfers take ~30us, this is far longer
+const MDIO_POLL_LIMIT: usize = 1_000_000;
+// Autonegotiation takes a few seconds. Each BMSR read is an MDIO transfer, so this is ~6s.
+const AUTONEG_POLL_LIMIT: usize = 200_000;
+
+const MII_BMCR: u8 = 0x0;
+const MII_BMSR: u8 = 0x1;
+const MII_PHYSID1: u8 = 0x2;
+const MII_PHYSID2: u8 = 0x3;
+
+const BMCR_ANEG_RESTART: u16 = 1 << 9;
+const BMCR_POWER_DOWN: u16 = 1 << 11;
+const BMSR_ANEG_COMPLETE: u16 = 1 << 5;
+
+/// The PHY management interface didn't go idle
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MdioTimeout;
+
+impl From<MdioTimeout> for InitError {
+    fn from(_: MdioTimeout) -> Self {
+        InitError::MdioTimeout
+    }
+}
+
+fn mdio_wait_idle(regs: &Regs) -> Result<(), MdioTimeout> {
+    (0..MDIO_POLL_LIMIT)
+        .any(|_| regs.network_status.is_set(NetworkStatus::PHY_MGMT_IDLE))
+        .then_some(())
+        .ok_or(MdioTimeout)
+}
+
+fn mdio_op(
+    regs: &Regs,
+    op: FieldValue<u32, PhyManagement::Register>,
+    phy_addr: u8,
+    reg: u8,
+    data: u16,
+) -> Result<u16, MdioTimeout> {
+    mdio_wait_idle(regs)?;
+    regs.phy_management.write(
+        PhyManagement::CLAUSE_22::SET
+            + op
+            + PhyManagement::PHY_ADDR.val(phy_addr as u32)
+            + PhyManagement::REG_ADDR.val(reg as u32)
+            + PhyManagement::MUST_BE_10.val(0b10)
+            + PhyManagement::DATA.val(data as u32),
+    );
+    regs.mdio_started();
+    mdio_wait_idle(regs)?;
+    Ok(regs.phy_management.read(PhyManagement::DATA) as u16)
+}
+
+fn mdio_read(regs: &Regs, phy_addr: u8, reg: u8) -> Result<u16, MdioTimeout> {
+    mdio_op(regs, PhyManagement::OPERATION::Read, phy_addr, reg, 0)
+}
+
+fn mdio_write(regs: &Regs, phy_addr: u8, reg: u8, data: u16) -> Result<(), MdioTimeout> {
+    mdio_op(regs, PhyManagement::OPERATION::Write, phy_addr, reg, data).map(|_| ())
+}
+
+/// Check a PHY answers at `phy_addr`. Nothing driving MDIO reads back as all ones.
+pub fn detect(regs: &Regs, phy_addr: u8) -> Result<(), InitError> {
+    let id = [
+        mdio_read(regs, phy_addr, MII_PHYSID1)?,
+        mdio_read(regs, phy_addr, MII_PHYSID2)?,
+    ];
+    if id == [0xFFFF; 2] || id == [0; 2] {
+        return Err(InitError::PhyNotDetected);
+    }
+    Ok(())
+}
+
+/// Wait for autonegotiation to complete, returning whether it did. Without a link partner, such
+/// as when no cable is plugged in, it never does.
+pub fn wait_for_autoneg(regs: &Regs, phy_addr: u8) -> Result<bool, MdioTimeout> {
+    for _ in 0..AUTONEG_POLL_LIMIT {
+        if mdio_read(regs, phy_addr, MII_BMSR)? & BMSR_ANEG_COMPLETE != 0 {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Powering the PHY back up restarts autonegotiation, the link comes up asynchronously.
+pub fn set_power_down(regs: &Regs, phy_addr: u8, power_down: bool) -> Result<(), MdioTimeout> {
+    let bmcr = mdio_read(regs, phy_addr, MII_BMCR)?;
+    let bmcr = if power_down {
+        bmcr | BMCR_POWER_DOWN
+    } else {
+        (bmcr & !BMCR_POWER_DOWN) | BMCR_ANEG_RESTART
+    };
+    mdio_write(regs, phy_addr, MII_BMCR, bmcr)
+}
@@ -0,0 +1,35 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+use super::{DmaDef, Driver, InitError};
+
+#[cfg(feature = "mock")]
+pub mod mock;
+
+/// Where the environment the driver runs in has mapped the GEM and the DMA regions.
+///
+/// Waiting for and acknowledging the GEM's interrupt is left to the environment, which calls
+/// `Driver::ack_interrupts` once one has arrived.
+pub trait Platform {
+    /// Virtual address of the GEM register block, mapped uncached
+    fn register_block(&self) -> *mut ();
+
+    /// Region for the descriptor rings, mapped uncached
+    fn desc_dma(&self) -> DmaDef;
+
+    /// Region for the packet buffers
+    fn buf_dma(&self) -> DmaDef;
+}
+
+impl Driver {
+    pub fn from_platform<P: Platform>(platform: &P) -> Result<Self, InitError> {
+        Self::new(
+            platform.register_block(),
+            platform.desc_dma(),
+            platform.buf_dma(),
+        )
+    }
+}
@@ -0,0 +1,264 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+use super::Platform;
+use crate::{DmaDef, Driver, InitError, PHY_ADDR};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ptr::NonNull;
+
+// Plain memory standing in for the GEM and the DMA regions, for running the driver on the
+// host. Nothing happens on its own: tests play the part of the GEM by setting registers and
+// descriptor bits themselves, or with `gem_receive` and `gem_transmitted`.
+//
+// The one exception is MDIO, which the driver waits on: `mdio_transfer` answers each transfer
+// like a DP83867 with the link up would. The PHY's registers are kept after the GEM's in the
+// register region, where `mdio_transfer` can find them. Only drivers built with `driver` have it
+// hooked in, on real platforms the GEM does the transfers itself.
+
+const REGISTER_BLOCK_SIZE: usize = 0x1000;
+
+// Offset of network_status and its PHY management idle bit. Set from the start so MDIO
+// transfers complete immediately.
+pub const NETWORK_STATUS: usize = 0x008;
+const PHY_MGMT_IDLE: u32 = 1 << 2;
+
+const PHY_MANAGEMENT: usize = 0x034;
+const PHY_MANAGEMENT_OP_WRITE: u32 = 0b01;
+const PHY_MANAGEMENT_OP_READ: u32 = 0b10;
+
+const NUM_PHY_REGS: usize = 32;
+const PHY_REGS: usize = REGISTER_BLOCK_SIZE;
+
+pub const MII_BMCR: u8 = 0x0;
+pub const MII_BMSR: u8 = 0x1;
+pub const MII_PHYSID1: u8 = 0x2;
+pub const MII_PHYSID2: u8 = 0x3;
+
+// Reset and autonegotiation restart clear themselves
+const BMCR_SELF_CLEARING: u16 = (1 << 15) | (1 << 9);
+/// Set in BMSR once autonegotiation has completed
+pub const BMSR_ANEG_COMPLETE: u16 = 1 << 5;
+
+// 1000BASE-T full duplex, autonegotiation enabled
+const BMCR_RESET_VALUE: u16 = 0x1140;
+// Autonegotiation complete and link up
+const BMSR_LINK_UP: u16 = 0x796D;
+// TI DP83867
+const PHYSID: [u16; 2] = [0x2000, 0xA231];
+
+// Where the driver points the GEM at its queue 0 rings
+const RECEIVE_Q_PTR: usize = 0x018;
+const TRANSMIT_Q_PTR: usize = 0x01C;
+
+// RX descriptor bits the GEM sets
+const RX_AVAIL: u32 = 1 << 0;
+const RX_ADDR_MASK: u32 = !0b11;
+const RX_START_OF_FRAME: u32 = 1 << 14;
+const RX_END_OF_FRAME: u32 = 1 << 15;
+// TX descriptor bits
+const TX_USED: u32 = 1 << 31;
+const TX_LEN_MASK: u32 = (1 << 14) - 1;
+
+// Physical addresses handed to the driver. The GEM only takes 32-bit addresses, so these
+// can't be the host addresses of the regions.
+const DESC_PADDR: usize = 0x1000_0000;
+const BUF_PADDR: usize = 0x2000_0000;
+
+struct Region {
+    // u64 keeps descriptors and registers aligned
+    mem: Vec<u64>,
+    paddr: usize,
+}
+
+impl Region {
+    fn new(size: usize, paddr: usize) -> Self {
+        Self {
+            mem: vec![0; size.div_ceil(8)],
+            paddr,
+        }
+    }
+
+    fn vaddr(&self) -> *mut u8 {
+        self.mem.as_ptr().cast_mut().cast()
+    }
+
+    fn size(&self) -> usize {
+        self.mem.len() * 8
+    }
+
+    fn dma_def(&self) -> DmaDef {
+        DmaDef {
+            vaddr: NonNull::new(self.vaddr().cast()).unwrap(),
+            paddr: NonNull::new(self.paddr as *mut ()).unwrap(),
+            size: self.size(),
+        }
+    }
+}
+
+pub struct MockPlatform {
+    regs: Region,
+    desc: Region,
+    bufs: Region,
+}
+
+impl MockPlatform {
+    pub fn new(desc_size: usize, buf_size: usize) -> Self {
+        let mock = Self {
+            regs: Region::new(REGISTER_BLOCK_SIZE + NUM_PHY_REGS * 4, 0),
+            desc: Region::new(desc_size, DESC_PADDR),
+            bufs: Region::new(buf_size, BUF_PADDR),
+        };
+        mock.set_reg(NETWORK_STATUS, PHY_MGMT_IDLE);
+        mock.set_phy_reg(MII_BMCR, BMCR_RESET_VALUE);
+        mock.set_phy_reg(MII_BMSR, BMSR_LINK_UP);
+        mock.set_phy_reg(MII_PHYSID1, PHYSID[0]);
+        mock.set_phy_reg(MII_PHYSID2, PHYSID[1]);
+        mock
+    }
+
+    fn reg_ptr(&self, offset: usize) -> *mut u32 {
+        assert!(offset < REGISTER_BLOCK_SIZE && offset % 4 == 0);
+        unsafe { self.regs.vaddr().add(offset).cast() }
+    }
+
+    /// Read the register at `offset`, as last written by the driver or `set_reg`
+    pub fn reg(&self, offset: usize) -> u32 {
+        unsafe { self.reg_ptr(offset).read_volatile() }
+    }
+
+    pub fn set_reg(&self, offset: usize, val: u32) {
+        unsafe { self.reg_ptr(offset).write_volatile(val) }
+    }
+
+    /// The PHY's register `reg`, as last written over MDIO or with `set_phy_reg`
+    pub fn phy_reg(&self, reg: u8) -> u16 {
+        unsafe { phy_reg_ptr(self.regs.vaddr(), reg).read_volatile() as u16 }
+    }
+
+    pub fn set_phy_reg(&self, reg: u8, val: u16) {
+        unsafe { phy_reg_ptr(self.regs.vaddr(), reg).write_volatile(val as u32) }
+    }
+
+    /// A driver on the mock, with its MDIO transfers answered by the mock's PHY
+    pub fn driver(&self) -> Result<Driver, InitError> {
+        Driver::with_mdio_transfer(
+            self.register_block(),
+            self.desc_dma(),
+            self.buf_dma(),
+            mdio_transfer,
+        )
+    }
+
+    /// Host address of something the driver gave the GEM a physical address for
+    pub fn translate(&self, paddr: u32) -> Option<*mut u8> {
+        let paddr = paddr as usize;
+        [&self.desc, &self.bufs].into_iter().find_map(|region| {
+            let offset = paddr.checked_sub(region.paddr)?;
+            (offset < region.size()).then(|| unsafe { region.vaddr().add(offset) })
+        })
+    }
+
+    /// Physical address of the first descriptor in the driver's queue 0 RX ring
+    pub fn rx_ring(&self) -> u32 {
+        self.reg(RECEIVE_Q_PTR)
+    }
+
+    /// Physical address of the first descriptor in the driver's queue 0 TX ring
+    pub fn tx_ring(&self) -> u32 {
+        self.reg(TRANSMIT_Q_PTR)
+    }
+
+    fn desc_words(&self, desc_paddr: u32) -> *mut [u32; 2] {
+        self.translate(desc_paddr)
+            .expect("descriptor outside the DMA regions")
+            .cast()
+    }
+
+    /// Receive `frame` into the RX descriptor at `desc_paddr` like the GEM would, and hand the
+    /// descriptor to the driver. Returns false if the driver hasn't given it back yet.
+    pub fn gem_receive(&self, desc_paddr: u32, frame: &[u8]) -> bool {
+        let desc = self.desc_words(desc_paddr);
+        let [addr, _] = unsafe { desc.read_volatile() };
+        if addr & RX_AVAIL != 0 {
+            return false;
+        }
+        let buf = self
+            .translate(addr & RX_ADDR_MASK)
+            .expect("buffer outside the DMA regions");
+        unsafe {
+            buf.copy_from_nonoverlapping(frame.as_ptr(), frame.len());
+            let status = frame.len() as u32 | RX_START_OF_FRAME | RX_END_OF_FRAME;
+            desc.write_volatile([addr | RX_AVAIL, status]);
+        }
+        true
+    }
+
+    /// Send the frame in the TX descriptor at `desc_paddr` like the GEM would, and hand the
+    /// descriptor back to the driver. Returns `None` if the driver hasn't given it a frame.
+    pub fn gem_transmitted(&self, desc_paddr: u32) -> Option<Vec<u8>> {
+        let desc = self.desc_words(desc_paddr);
+        let [addr, status] = unsafe { desc.read_volatile() };
+        if status & TX_USED != 0 {
+            return None;
+        }
+        let buf = self
+            .translate(addr)
+            .expect("buffer outside the DMA regions");
+        let len = (status & TX_LEN_MASK) as usize;
+        let frame = unsafe { core::slice::from_raw_parts(buf, len) }.to_vec();
+        unsafe { desc.write_volatile([addr, status | TX_USED]) };
+        Some(frame)
+    }
+}
+
+unsafe fn phy_reg_ptr(regs: *mut u8, reg: u8) -> *mut u32 {
+    assert!((reg as usize) < NUM_PHY_REGS);
+    regs.add(PHY_REGS + reg as usize * 4).cast()
+}
+
+impl Platform for MockPlatform {
+    fn register_block(&self) -> *mut () {
+        self.regs.vaddr().cast()
+    }
+
+    fn desc_dma(&self) -> DmaDef {
+        self.desc.dma_def()
+    }
+
+    fn buf_dma(&self) -> DmaDef {
+        self.bufs.dma_def()
+    }
+}
+
+// Answers as the PHY at `PHY_ADDR`. Nothing answers at other addresses, so reads from them
+// give all ones.
+fn mdio_transfer(regs: *mut ()) {
+    let base = regs.cast::<u8>();
+    let phy_management = unsafe { base.add(PHY_MANAGEMENT).cast::<u32>() };
+    let command = unsafe { phy_management.read_volatile() };
+    let op = (command >> 28) & 0b11;
+    let phy_addr = ((command >> 23) & 0x1F) as u8;
+    let reg = ((command >> 18) & 0x1F) as u8;
+    let data = command as u16;
+
+    let reg_ptr = unsafe { phy_reg_ptr(base, reg) };
+    let answer = match op {
+        _ if phy_addr != PHY_ADDR => 0xFFFF,
+        PHY_MANAGEMENT_OP_READ => unsafe { reg_ptr.read_volatile() as u16 },
+        PHY_MANAGEMENT_OP_WRITE => {
+            let data = if reg == MII_BMCR {
+                data & !BMCR_SELF_CLEARING
+            } else {
+                data
+            };
+            unsafe { reg_ptr.write_volatile(data as u32) };
+            data
+        }
+        _ => data,
+    };
+    unsafe { phy_management.write_volatile((command & !0xFFFF) | answer as u32) };
+}
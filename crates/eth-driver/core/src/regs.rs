@@ -0,0 +1,215 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+use core::ops::Deref;
+use tock_registers::register_bitfields;
+use tock_registers::register_structs;
+use tock_registers::registers::{ReadOnly, ReadWrite, WriteOnly};
+
+// GEM registers the HAL does not (yet) expose. This overlays the same register block the HAL
+// device was created from, so only touch registers the HAL leaves alone.
+
+register_bitfields![u32,
+    pub NetworkControl [
+        RX_ENABLE OFFSET(2) NUMBITS(1) [],
+        TX_ENABLE OFFSET(3) NUMBITS(1) [],
+    ],
+    pub NetworkConfig [
+        // 100 Mbit/s when set, 10 Mbit/s when clear, unless GIGABIT is set
+        SPEED OFFSET(0) NUMBITS(1) [],
+        FULL_DUPLEX OFFSET(1) NUMBITS(1) [],
+        JUMBO_FRAMES OFFSET(3) NUMBITS(1) [],
+        COPY_ALL_FRAMES OFFSET(4) NUMBITS(1) [],
+        NO_BROADCAST OFFSET(5) NUMBITS(1) [],
+        MULTICAST_HASH_ENABLE OFFSET(6) NUMBITS(1) [],
+        GIGABIT OFFSET(10) NUMBITS(1) [],
+    ],
+    pub NetworkStatus [
+        PHY_MGMT_IDLE OFFSET(2) NUMBITS(1) [],
+    ],
+    pub DmaConfig [
+        // In units of 64 bytes
+        RX_BUF_SIZE OFFSET(16) NUMBITS(8) [],
+    ],
+    pub PhyManagement [
+        DATA OFFSET(0) NUMBITS(16) [],
+        // Must be written as 0b10
+        MUST_BE_10 OFFSET(16) NUMBITS(2) [],
+        REG_ADDR OFFSET(18) NUMBITS(5) [],
+        PHY_ADDR OFFSET(23) NUMBITS(5) [],
+        OPERATION OFFSET(28) NUMBITS(2) [
+            Write = 0b01,
+            Read = 0b10,
+        ],
+        CLAUSE_22 OFFSET(30) NUMBITS(1) [],
+    ],
+    pub JumboMaxLength [
+        MAX_LENGTH OFFSET(0) NUMBITS(14) [],
+    ],
+    pub QueueInterrupt [
+        RECEIVE_COMPLETE OFFSET(1) NUMBITS(1) [],
+        RX_USED_BIT_READ OFFSET(2) NUMBITS(1) [],
+        AMBA_ERROR OFFSET(6) NUMBITS(1) [],
+        TRANSMIT_COMPLETE OFFSET(7) NUMBITS(1) [],
+        RECEIVE_OVERRUN OFFSET(10) NUMBITS(1) [],
+        RESP_NOT_OK OFFSET(11) NUMBITS(1) [],
+    ],
+    pub DmaRxBufSize [
+        // In units of 64 bytes
+        RX_BUF_SIZE OFFSET(0) NUMBITS(8) [],
+    ],
+    pub ScreeningType1 [
+        QUEUE_NUMBER OFFSET(0) NUMBITS(4) [],
+        DSTC_MATCH OFFSET(4) NUMBITS(8) [],
+        UDP_PORT_MATCH OFFSET(12) NUMBITS(16) [],
+        DSTC_ENABLE OFFSET(28) NUMBITS(1) [],
+        UDP_PORT_MATCH_ENABLE OFFSET(29) NUMBITS(1) [],
+        DROP_ON_MATCH OFFSET(30) NUMBITS(1) [],
+    ],
+    pub ScreeningType2 [
+        QUEUE_NUMBER OFFSET(0) NUMBITS(4) [],
+        VLAN_PRIORITY OFFSET(4) NUMBITS(3) [],
+        VLAN_ENABLE OFFSET(8) NUMBITS(1) [],
+        ETHERTYPE_REG_INDEX OFFSET(9) NUMBITS(3) [],
+        ETHERTYPE_ENABLE OFFSET(12) NUMBITS(1) [],
+        COMPARE_A OFFSET(13) NUMBITS(5) [],
+        COMPARE_A_ENABLE OFFSET(18) NUMBITS(1) [],
+        COMPARE_B OFFSET(19) NUMBITS(5) [],
+        COMPARE_B_ENABLE OFFSET(24) NUMBITS(1) [],
+        COMPARE_C OFFSET(25) NUMBITS(5) [],
+        COMPARE_C_ENABLE OFFSET(30) NUMBITS(1) [],
+    ],
+    pub ScreeningEthertype [
+        COMPARE_VALUE OFFSET(0) NUMBITS(16) [],
+    ],
+    pub Type2CompareWord0 [
+        MASK_VALUE OFFSET(0) NUMBITS(16) [],
+        COMPARE_VALUE OFFSET(16) NUMBITS(16) [],
+    ],
+    pub Type2CompareWord1 [
+        OFFSET_VALUE OFFSET(0) NUMBITS(7) [],
+        COMPARE_OFFSET OFFSET(7) NUMBITS(2) [
+            StartOfFrame = 0,
+            AfterEthertype = 1,
+            AfterIpHeader = 2,
+            AfterTcpUdpHeader = 3,
+        ],
+    ]
+];
+
+pub const NUM_TYPE1_SCREENERS: usize = 4;
+pub const NUM_TYPE2_SCREENERS: usize = 4;
+pub const NUM_ETHERTYPE_COMPARES: usize = 4;
+pub const NUM_TYPE2_COMPARES: usize = 4;
+pub const NUM_EXTRA_SPECIFIC_ADDRESSES: usize = 3;
+
+register_structs! {
+    pub GemRegisters {
+        (0x000 => pub network_control: ReadWrite<u32, NetworkControl::Register>),
+        (0x004 => pub network_config: ReadWrite<u32, NetworkConfig::Register>),
+        (0x008 => pub network_status: ReadOnly<u32, NetworkStatus::Register>),
+        (0x00C => _reserved1),
+        (0x010 => pub dma_config: ReadWrite<u32, DmaConfig::Register>),
+        // Owned by the HAL, only ever read here
+        (0x014 => pub transmit_status: ReadOnly<u32>),
+        // Reading these gives the GEM's current position in each ring
+        (0x018 => pub receive_q_ptr: ReadWrite<u32>),
+        (0x01C => pub transmit_q_ptr: ReadWrite<u32>),
+        (0x020 => pub receive_status: ReadOnly<u32>),
+        (0x024 => _reserved0),
+        (0x034 => pub phy_management: ReadWrite<u32, PhyManagement::Register>),
+        (0x038 => _reserved11),
+        (0x048 => pub jumbo_max_length: ReadWrite<u32, JumboMaxLength::Register>),
+        (0x04C => _reserved3),
+        // Bits 0-31 and 32-63 of the 64 bit hash filter
+        (0x080 => pub hash_bottom: ReadWrite<u32>),
+        (0x084 => pub hash_top: ReadWrite<u32>),
+        // Specific address filter 1 holds the GEM's own address and belongs to the HAL
+        (0x088 => _reserved16),
+        (0x090 => pub specific_address: [SpecificAddress; NUM_EXTRA_SPECIFIC_ADDRESSES]),
+        (0x0A8 => _reserved12),
+        // Statistics, all cleared on read. Octet counts are 48 bits split over two registers.
+        (0x100 => pub octets_tx_bottom: ReadOnly<u32>),
+        (0x104 => pub octets_tx_top: ReadOnly<u32>),
+        (0x108 => pub frames_tx: ReadOnly<u32>),
+        (0x10C => _reserved13),
+        (0x150 => pub octets_rx_bottom: ReadOnly<u32>),
+        (0x154 => pub octets_rx_top: ReadOnly<u32>),
+        (0x158 => pub frames_rx: ReadOnly<u32>),
+        (0x15C => _reserved14),
+        (0x190 => pub fcs_errors: ReadOnly<u32>),
+        (0x194 => pub length_field_errors: ReadOnly<u32>),
+        (0x198 => pub receive_symbol_errors: ReadOnly<u32>),
+        (0x19C => pub alignment_errors: ReadOnly<u32>),
+        (0x1A0 => pub receive_resource_errors: ReadOnly<u32>),
+        (0x1A4 => pub receive_overruns: ReadOnly<u32>),
+        (0x1A8 => _reserved15),
+        (0x400 => pub int_q1_status: ReadWrite<u32, QueueInterrupt::Register>),
+        (0x404 => _reserved4),
+        (0x480 => pub receive_q1_ptr: ReadWrite<u32>),
+        (0x484 => _reserved5),
+        (0x4A0 => pub dma_rxbuf_size_q1: ReadWrite<u32, DmaRxBufSize::Register>),
+        (0x4A4 => _reserved6),
+        (0x500 => pub screening_type_1: [ReadWrite<u32, ScreeningType1::Register>; NUM_TYPE1_SCREENERS]),
+        (0x510 => _reserved7),
+        (0x540 => pub screening_type_2: [ReadWrite<u32, ScreeningType2::Register>; NUM_TYPE2_SCREENERS]),
+        (0x550 => _reserved8),
+        (0x600 => pub int_q1_enable: WriteOnly<u32, QueueInterrupt::Register>),
+        (0x604 => _reserved9),
+        (0x6E0 => pub screening_type_2_ethertype: [ReadWrite<u32, ScreeningEthertype::Register>; NUM_ETHERTYPE_COMPARES]),
+        (0x6F0 => _reserved10),
+        (0x700 => pub type2_compare: [Type2Compare; NUM_TYPE2_COMPARES]),
+        (0x720 => @END),
+    },
+
+    pub SpecificAddress {
+        // The first four bytes of the address, the first in the low byte. Writing this turns
+        // the filter off until `top` is written.
+        (0x0 => pub bottom: ReadWrite<u32>),
+        // The last two bytes
+        (0x4 => pub top: ReadWrite<u32>),
+        (0x8 => @END),
+    },
+
+    pub Type2Compare {
+        (0x0 => pub word_0: ReadWrite<u32, Type2CompareWord0::Register>),
+        (0x4 => pub word_1: ReadWrite<u32, Type2CompareWord1::Register>),
+        (0x8 => @END),
+    }
+}
+
+pub const RX_BUF_SIZE_UNIT: usize = 64;
+
+/// Carries out the MDIO transfer just started in `phy_management`, given the register block.
+/// The GEM does this itself, so only `MockPlatform` has anything to do here.
+pub type MdioTransfer = fn(*mut ());
+
+pub struct Regs {
+    ptr: *const GemRegisters,
+    mdio_transfer: MdioTransfer,
+}
+
+impl Regs {
+    pub fn new(ptr: *mut (), mdio_transfer: MdioTransfer) -> Self {
+        Self {
+            ptr: ptr.cast(),
+            mdio_transfer,
+        }
+    }
+
+    /// Call once a transfer has been written to `phy_management`, before waiting for it
+    pub fn mdio_started(&self) {
+        (self.mdio_transfer)(self.ptr.cast_mut().cast())
+    }
+}
+
+impl Deref for Regs {
+    type Target = GemRegisters;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.ptr }
+    }
+}
@@ -0,0 +1,208 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+use super::Driver;
+use crate::regs::{
+    ScreeningEthertype, ScreeningType1, ScreeningType2, Type2CompareWord0, Type2CompareWord1,
+    NUM_TYPE1_SCREENERS, NUM_TYPE2_SCREENERS,
+};
+use tock_registers::fields::FieldValue;
+use tock_registers::interfaces::Writeable;
+
+// Receive flow steering with the GEM's type 1 and type 2 screeners. Frames matching a screener
+// go to the given RX queue (or are dropped), everything else lands in the normal queue.
+//
+// Type 2 screener `n` owns EtherType compare register `n` and type 2 compare register `n`.
+
+/// RX queues frames can be steered to. The priority queue is always serviced first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RxQueue {
+    Normal = 0,
+    Priority = 1,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScreenerAction {
+    Queue(RxQueue),
+    Drop,
+}
+
+/// Matches on the IP DS/TC byte and/or the UDP destination port.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Type1Screener {
+    /// The GEM compares the whole DS/TC byte, so this only matches frames with ECN clear
+    pub dscp: Option<u8>,
+    pub udp_port: Option<u16>,
+    pub action: ScreenerAction,
+}
+
+/// Matches on EtherType, VLAN priority and/or a 16-bit compare somewhere in the frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Type2Screener {
+    pub ethertype: Option<u16>,
+    pub vlan_priority: Option<u8>,
+    pub compare: Option<Compare>,
+    /// The GEM can only drop frames matched by type 1 screeners
+    pub queue: RxQueue,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Compare {
+    pub base: CompareBase,
+    /// Offset in bytes from `base`
+    pub offset: u8,
+    pub value: u16,
+    pub mask: u16,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareBase {
+    StartOfFrame,
+    AfterEthertype,
+    AfterIpHeader,
+    AfterTcpUdpHeader,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScreenerError {
+    IndexOutOfRange,
+    InvalidDscp,
+    InvalidVlanPriority,
+    InvalidCompareOffset,
+    NoMatch,
+}
+
+const MAX_DSCP: u8 = 0x3F;
+const MAX_VLAN_PRIORITY: u8 = 0x7;
+const MAX_COMPARE_OFFSET: u8 = 0x7F;
+
+impl Driver {
+    pub fn set_type1_screener(
+        &mut self,
+        idx: usize,
+        screener: &Type1Screener,
+    ) -> Result<(), ScreenerError> {
+        let reg = self
+            .regs
+            .screening_type_1
+            .get(idx)
+            .ok_or(ScreenerError::IndexOutOfRange)?;
+
+        if screener.dscp.is_none() && screener.udp_port.is_none() {
+            return Err(ScreenerError::NoMatch);
+        }
+
+        let mut val = match screener.action {
+            ScreenerAction::Queue(queue) => ScreeningType1::QUEUE_NUMBER.val(queue as u32),
+            ScreenerAction::Drop => ScreeningType1::DROP_ON_MATCH::SET,
+        };
+        if let Some(dscp) = screener.dscp {
+            if dscp > MAX_DSCP {
+                return Err(ScreenerError::InvalidDscp);
+            }
+            val += ScreeningType1::DSTC_MATCH.val((dscp as u32) << 2)
+                + ScreeningType1::DSTC_ENABLE::SET;
+        }
+        if let Some(port) = screener.udp_port {
+            val += ScreeningType1::UDP_PORT_MATCH.val(port as u32)
+                + ScreeningType1::UDP_PORT_MATCH_ENABLE::SET;
+        }
+        reg.write(val);
+        Ok(())
+    }
+
+    pub fn clear_type1_screener(&mut self, idx: usize) -> Result<(), ScreenerError> {
+        let reg = self
+            .regs
+            .screening_type_1
+            .get(idx)
+            .ok_or(ScreenerError::IndexOutOfRange)?;
+        reg.set(0);
+        Ok(())
+    }
+
+    pub fn set_type2_screener(
+        &mut self,
+        idx: usize,
+        screener: &Type2Screener,
+    ) -> Result<(), ScreenerError> {
+        if idx >= NUM_TYPE2_SCREENERS {
+            return Err(ScreenerError::IndexOutOfRange);
+        }
+        if screener.ethertype.is_none()
+            && screener.vlan_priority.is_none()
+            && screener.compare.is_none()
+        {
+            return Err(ScreenerError::NoMatch);
+        }
+        // Before anything is written, so a bad screener leaves the old one in place
+        if screener
+            .vlan_priority
+            .is_some_and(|priority| priority > MAX_VLAN_PRIORITY)
+        {
+            return Err(ScreenerError::InvalidVlanPriority);
+        }
+        if screener
+            .compare
+            .is_some_and(|compare| compare.offset > MAX_COMPARE_OFFSET)
+        {
+            return Err(ScreenerError::InvalidCompareOffset);
+        }
+
+        let mut val: FieldValue<u32, ScreeningType2::Register> =
+            ScreeningType2::QUEUE_NUMBER.val(screener.queue as u32);
+        if let Some(ethertype) = screener.ethertype {
+            self.regs.screening_type_2_ethertype[idx]
+                .write(ScreeningEthertype::COMPARE_VALUE.val(ethertype as u32));
+            val += ScreeningType2::ETHERTYPE_REG_INDEX.val(idx as u32)
+                + ScreeningType2::ETHERTYPE_ENABLE::SET;
+        }
+        if let Some(priority) = screener.vlan_priority {
+            val += ScreeningType2::VLAN_PRIORITY.val(priority as u32)
+                + ScreeningType2::VLAN_ENABLE::SET;
+        }
+        if let Some(compare) = &screener.compare {
+            let base = match compare.base {
+                CompareBase::StartOfFrame => Type2CompareWord1::COMPARE_OFFSET::StartOfFrame,
+                CompareBase::AfterEthertype => Type2CompareWord1::COMPARE_OFFSET::AfterEthertype,
+                CompareBase::AfterIpHeader => Type2CompareWord1::COMPARE_OFFSET::AfterIpHeader,
+                CompareBase::AfterTcpUdpHeader => {
+                    Type2CompareWord1::COMPARE_OFFSET::AfterTcpUdpHeader
+                }
+            };
+            let regs = &self.regs.type2_compare[idx];
+            regs.word_0.write(
+                Type2CompareWord0::COMPARE_VALUE.val(compare.value as u32)
+                    + Type2CompareWord0::MASK_VALUE.val(compare.mask as u32),
+            );
+            regs.word_1
+                .write(base + Type2CompareWord1::OFFSET_VALUE.val(compare.offset as u32));
+            val +=
+                ScreeningType2::COMPARE_A.val(idx as u32) + ScreeningType2::COMPARE_A_ENABLE::SET;
+        }
+        self.regs.screening_type_2[idx].write(val);
+        Ok(())
+    }
+
+    pub fn clear_type2_screener(&mut self, idx: usize) -> Result<(), ScreenerError> {
+        let reg = self
+            .regs
+            .screening_type_2
+            .get(idx)
+            .ok_or(ScreenerError::IndexOutOfRange)?;
+        reg.set(0);
+        Ok(())
+    }
+
+    pub fn clear_screeners(&mut self) {
+        for idx in 0..NUM_TYPE1_SCREENERS {
+            self.regs.screening_type_1[idx].set(0);
+        }
+        for idx in 0..NUM_TYPE2_SCREENERS {
+            self.regs.screening_type_2[idx].set(0);
+        }
+    }
+}
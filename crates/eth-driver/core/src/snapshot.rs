@@ -0,0 +1,187 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+use super::Driver;
+use crate::dma::NUM_BUFS;
+use core::fmt;
+use tock_registers::interfaces::Readable;
+
+// A copy of the driver's rings and the GEM registers that say where it is in them, for working
+// out why traffic stopped. Only registers without side effects on read are included.
+//
+// `Display` gives a summary with a map of who owns each descriptor, `d` for the driver and `G`
+// for the GEM. `{:#}` lists every descriptor as well.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    pub running: bool,
+    pub phy_powered_down: bool,
+    pub tx_dropped: usize,
+    pub regs: RegsSnapshot,
+    pub rx: RxRingSnapshot,
+    pub rx_priority: RxRingSnapshot,
+    pub tx: TxRingSnapshot,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RegsSnapshot {
+    pub network_control: u32,
+    pub network_config: u32,
+    pub network_status: u32,
+    pub dma_config: u32,
+    pub transmit_status: u32,
+    pub receive_status: u32,
+    pub receive_q_ptr: u32,
+    pub transmit_q_ptr: u32,
+    pub receive_q1_ptr: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RxRingSnapshot {
+    pub base_paddr: u32,
+    /// Next entry the driver will receive from
+    pub curr_entry: usize,
+    pub descs: [RxDescSnapshot; NUM_BUFS],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RxDescSnapshot {
+    pub addr: u32,
+    /// Set by the GEM once it has written a frame, the driver owns the descriptor
+    pub avail: bool,
+    pub wrap: bool,
+    pub start_of_frame: bool,
+    pub end_of_frame: bool,
+    pub len: u16,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TxRingSnapshot {
+    pub base_paddr: u32,
+    /// Next entry the driver will send from
+    pub curr_entry: usize,
+    /// Oldest entry still owned by the GEM
+    pub head: usize,
+    pub in_flight: usize,
+    pub descs: [TxDescSnapshot; NUM_BUFS],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TxDescSnapshot {
+    pub addr: u32,
+    /// Set when the driver owns the descriptor
+    pub used: bool,
+    pub wrap: bool,
+    pub frame_end: bool,
+    pub len: u16,
+}
+
+impl Driver {
+    pub fn snapshot(&self) -> Snapshot {
+        let regs = &self.regs;
+        Snapshot {
+            running: self.running,
+            phy_powered_down: self.phy_powered_down,
+            tx_dropped: self.tx_ring.dropped(),
+            regs: RegsSnapshot {
+                network_control: regs.network_control.get(),
+                network_config: regs.network_config.get(),
+                network_status: regs.network_status.get(),
+                dma_config: regs.dma_config.get(),
+                transmit_status: regs.transmit_status.get(),
+                receive_status: regs.receive_status.get(),
+                receive_q_ptr: regs.receive_q_ptr.get(),
+                transmit_q_ptr: regs.transmit_q_ptr.get(),
+                receive_q1_ptr: regs.receive_q1_ptr.get(),
+            },
+            rx: self.rx_ring.snapshot(),
+            rx_priority: self.rx_priority_ring.snapshot(),
+            tx: self.tx_ring.snapshot(),
+        }
+    }
+}
+
+// Index of the descriptor `q_ptr` points at, if it is in the ring at `base_paddr`
+fn q_ptr_entry(q_ptr: u32, base_paddr: u32, desc_size: usize) -> Option<usize> {
+    let offset = q_ptr.checked_sub(base_paddr)? as usize;
+    (offset / desc_size < NUM_BUFS).then_some(offset / desc_size)
+}
+
+// One character per descriptor, 64 to a line
+fn fmt_ownership(
+    f: &mut fmt::Formatter<'_>,
+    owned_by_driver: impl Iterator<Item = bool>,
+) -> fmt::Result {
+    for (i, driver) in owned_by_driver.enumerate() {
+        if i % 64 == 0 {
+            write!(f, "\n    ")?;
+        }
+        f.write_str(if driver { "d" } else { "G" })?;
+    }
+    writeln!(f)
+}
+
+impl fmt::Display for Snapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "GEM snapshot: running: {}, PHY powered down: {}, TX dropped: {}",
+            self.running, self.phy_powered_down, self.tx_dropped
+        )?;
+        let regs = &self.regs;
+        writeln!(
+            f,
+            "  net_ctrl: {:#010x} net_cfg: {:#010x} net_status: {:#010x} dma_cfg: {:#010x}",
+            regs.network_control, regs.network_config, regs.network_status, regs.dma_config
+        )?;
+        writeln!(
+            f,
+            "  tx_status: {:#010x} rx_status: {:#010x}",
+            regs.transmit_status, regs.receive_status
+        )?;
+
+        let rx_desc_size = crate::dma::RX_DESC_SIZE;
+        for (name, ring, q_ptr) in [
+            ("RX", &self.rx, regs.receive_q_ptr),
+            ("RX priority", &self.rx_priority, regs.receive_q1_ptr),
+        ] {
+            write!(
+                f,
+                "  {name} ring @ {:#010x}: curr_entry: {}, GEM at: {:#010x} ({:?}):",
+                ring.base_paddr,
+                ring.curr_entry,
+                q_ptr,
+                q_ptr_entry(q_ptr, ring.base_paddr, rx_desc_size),
+            )?;
+            fmt_ownership(f, ring.descs.iter().map(|desc| desc.avail))?;
+            if f.alternate() {
+                for (i, desc) in ring.descs.iter().enumerate() {
+                    writeln!(f, "    [{i:3}] {desc:?}")?;
+                }
+            }
+        }
+
+        let tx = &self.tx;
+        write!(
+            f,
+            "  TX ring @ {:#010x}: curr_entry: {}, head: {}, in_flight: {}",
+            tx.base_paddr, tx.curr_entry, tx.head, tx.in_flight,
+        )?;
+        write!(
+            f,
+            ", GEM at: {:#010x} ({:?}):",
+            regs.transmit_q_ptr,
+            q_ptr_entry(regs.transmit_q_ptr, tx.base_paddr, crate::dma::TX_DESC_SIZE),
+        )?;
+        fmt_ownership(f, tx.descs.iter().map(|desc| desc.used))?;
+        if f.alternate() {
+            for (i, desc) in tx.descs.iter().enumerate() {
+                writeln!(f, "    [{i:3}] {desc:?}")?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,73 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+use super::Driver;
+use crate::regs::{NetworkConfig, Regs};
+use eth_driver_interface::Status;
+use tock_registers::interfaces::Readable;
+
+// The GEM's statistics registers clear when read, so the driver adds them up here. They are
+// only read when a status is asked for, which is often enough for the 48 bit octet counts, but
+// the 32 bit frame counts stop at their maximum if left for long enough at line rate.
+
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct Counters {
+    rx_frames: u64,
+    rx_bytes: u64,
+    rx_errors: u64,
+    tx_frames: u64,
+    tx_bytes: u64,
+}
+
+impl Counters {
+    fn accumulate(&mut self, regs: &Regs) {
+        let octets = |bottom: u32, top: u32| (top as u64) << 32 | bottom as u64;
+        // The bottom half has to be read first
+        let rx_bottom = regs.octets_rx_bottom.get();
+        self.rx_bytes += octets(rx_bottom, regs.octets_rx_top.get());
+        self.rx_frames += regs.frames_rx.get() as u64;
+        self.rx_errors += [
+            regs.fcs_errors.get(),
+            regs.length_field_errors.get(),
+            regs.receive_symbol_errors.get(),
+            regs.alignment_errors.get(),
+            regs.receive_resource_errors.get(),
+            regs.receive_overruns.get(),
+        ]
+        .iter()
+        .map(|&count| count as u64)
+        .sum::<u64>();
+        let tx_bottom = regs.octets_tx_bottom.get();
+        self.tx_bytes += octets(tx_bottom, regs.octets_tx_top.get());
+        self.tx_frames += regs.frames_tx.get() as u64;
+    }
+}
+
+impl Driver {
+    /// The link speed and duplex the GEM was configured for, and the counters since boot
+    pub fn status(&mut self) -> Status {
+        self.counters.accumulate(&self.regs);
+        let config = self.regs.network_config.extract();
+        let speed_mbps = if config.is_set(NetworkConfig::GIGABIT) {
+            1000
+        } else if config.is_set(NetworkConfig::SPEED) {
+            100
+        } else {
+            10
+        };
+        Status {
+            speed_mbps,
+            full_duplex: config.is_set(NetworkConfig::FULL_DUPLEX),
+            running: self.running,
+            rx_frames: self.counters.rx_frames,
+            rx_bytes: self.counters.rx_bytes,
+            rx_errors: self.counters.rx_errors,
+            tx_frames: self.counters.tx_frames,
+            tx_bytes: self.counters.tx_bytes,
+            tx_dropped: self.tx_dropped() as u64,
+        }
+    }
+}
@@ -0,0 +1,369 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+// The driver against `MockPlatform`, with the tests playing the part of the GEM.
+
+use crate::dma::NUM_BUFS;
+use crate::platform::mock::{
+    MockPlatform, BMSR_ANEG_COMPLETE, MII_BMSR, MII_PHYSID1, MII_PHYSID2, NETWORK_STATUS,
+};
+use crate::unicast::{filter_writes, FilterWrite};
+use crate::{Driver, InitError, TooManyAddresses, TxObserver};
+use alloc::vec::Vec;
+use core::cell::RefCell;
+use smoltcp::phy::{Device, RxToken, TxToken};
+use smoltcp::time::Instant;
+
+const DESC_SIZE: usize = 0x1000;
+// Enough for jumbo frames too
+const BUF_SIZE: usize = 0x40_0000;
+// RX and TX descriptors are both two words
+const DESC_STRIDE: u32 = 8;
+
+const NETWORK_CONFIG: usize = 0x004;
+const MULTICAST_HASH_ENABLE: u32 = 1 << 6;
+const HASH_BOTTOM: usize = 0x080;
+const HASH_TOP: usize = 0x084;
+// Bottom and top of specific address filter 2, the first `set_unicast_filter` uses
+const SPECIFIC_ADDRESS: usize = 0x090;
+const SPECIFIC_ADDRESS_STRIDE: usize = 8;
+
+fn frame(len: usize) -> Vec<u8> {
+    (0..len).map(|i| i as u8).collect()
+}
+
+fn driver(mock: &MockPlatform) -> Driver {
+    mock.driver().unwrap()
+}
+
+fn send(dev: &mut Driver, frame: &[u8]) {
+    dev.transmit(Instant::ZERO)
+        .unwrap()
+        .consume(frame.len(), |buf| buf.copy_from_slice(frame));
+}
+
+#[test]
+fn init_hands_rx_ring_to_gem() {
+    let mock = MockPlatform::new(DESC_SIZE, BUF_SIZE);
+    let dev = driver(&mock);
+    let snapshot = dev.snapshot();
+
+    assert!(snapshot.running);
+    for ring in [&snapshot.rx, &snapshot.rx_priority] {
+        assert!(ring.descs.iter().all(|desc| !desc.avail));
+        assert!(ring.descs[NUM_BUFS - 1].wrap);
+        assert!(ring.descs[..NUM_BUFS - 1].iter().all(|desc| !desc.wrap));
+    }
+    assert_eq!(snapshot.regs.receive_q_ptr, snapshot.rx.base_paddr);
+    assert_eq!(
+        snapshot.regs.receive_q1_ptr,
+        snapshot.rx_priority.base_paddr
+    );
+    assert!(snapshot.tx.descs.iter().all(|desc| desc.used));
+    assert_eq!(snapshot.tx.in_flight, 0);
+}
+
+#[test]
+fn init_fails_without_phy() {
+    let mock = MockPlatform::new(DESC_SIZE, BUF_SIZE);
+    mock.set_phy_reg(MII_PHYSID1, 0xFFFF);
+    mock.set_phy_reg(MII_PHYSID2, 0xFFFF);
+    assert_eq!(mock.driver().err(), Some(InitError::PhyNotDetected));
+}
+
+#[test]
+fn init_fails_without_link() {
+    let mock = MockPlatform::new(DESC_SIZE, BUF_SIZE);
+    mock.set_phy_reg(MII_BMSR, mock.phy_reg(MII_BMSR) & !BMSR_ANEG_COMPLETE);
+    assert_eq!(mock.driver().err(), Some(InitError::AutonegTimeout));
+}
+
+#[test]
+fn init_fails_when_mdio_hangs() {
+    let mock = MockPlatform::new(DESC_SIZE, BUF_SIZE);
+    mock.set_reg(NETWORK_STATUS, 0);
+    assert_eq!(mock.driver().err(), Some(InitError::MdioTimeout));
+}
+
+#[test]
+fn init_fails_with_small_dma_region() {
+    let mock = MockPlatform::new(DESC_SIZE, 0x1000);
+    assert!(matches!(
+        mock.driver().err(),
+        Some(InitError::DmaRegionTooSmall {
+            region: "buf_dma",
+            size: 0x1000,
+            ..
+        })
+    ));
+}
+
+#[test]
+fn transmit() {
+    let mock = MockPlatform::new(DESC_SIZE, BUF_SIZE);
+    let mut dev = driver(&mock);
+    let tx_base = mock.tx_ring();
+
+    let sent = frame(100);
+    send(&mut dev, &sent);
+
+    assert_eq!(mock.gem_transmitted(tx_base), Some(sent));
+    // Nothing more was queued
+    assert_eq!(mock.gem_transmitted(tx_base + DESC_STRIDE), None);
+    assert!(dev.transmit(Instant::ZERO).is_some());
+}
+
+#[derive(Default)]
+struct Sent(RefCell<Vec<Vec<u8>>>);
+
+impl TxObserver for Sent {
+    fn sent(&self, frame: &[u8]) {
+        self.0.borrow_mut().push(frame.into());
+    }
+}
+
+#[test]
+fn transmit_observed() {
+    let mock = MockPlatform::new(DESC_SIZE, BUF_SIZE);
+    let mut dev = driver(&mock);
+    let tx_base = mock.tx_ring();
+    let sent = Sent::default();
+
+    let frames = [frame(60), frame(1500)];
+    for frame in &frames {
+        dev.transmit_observed(&sent)
+            .unwrap()
+            .consume(frame.len(), |buf| buf.copy_from_slice(frame));
+    }
+    // Not until the GEM has sent them
+    dev.reclaim_tx(&sent);
+    assert!(sent.0.borrow().is_empty());
+
+    assert!(mock.gem_transmitted(tx_base).is_some());
+    dev.reclaim_tx(&sent);
+    assert_eq!(*sent.0.borrow(), frames[..1]);
+    assert!(mock.gem_transmitted(tx_base + DESC_STRIDE).is_some());
+    dev.stop(false, &sent);
+    assert_eq!(*sent.0.borrow(), frames);
+}
+
+#[test]
+fn transmit_stops_when_ring_is_full() {
+    let mock = MockPlatform::new(DESC_SIZE, BUF_SIZE);
+    let mut dev = driver(&mock);
+    let tx_base = mock.tx_ring();
+
+    for _ in 0..NUM_BUFS {
+        send(&mut dev, &frame(60));
+    }
+    assert!(!dev.tx_available());
+    assert!(dev.transmit(Instant::ZERO).is_none());
+
+    // The descriptor is reclaimed the next time the driver looks for space
+    assert!(mock.gem_transmitted(tx_base).is_some());
+    assert!(dev.transmit(Instant::ZERO).is_some());
+}
+
+#[test]
+fn receive_while_tx_ring_is_full() {
+    let mock = MockPlatform::new(DESC_SIZE, BUF_SIZE);
+    let mut dev = driver(&mock);
+
+    for _ in 0..NUM_BUFS {
+        send(&mut dev, &frame(60));
+    }
+    let received = frame(200);
+    assert!(mock.gem_receive(mock.rx_ring(), &received));
+
+    // The frame is still received, and the reply is dropped rather than queued
+    let (rx, tx) = dev.receive(Instant::ZERO).unwrap();
+    rx.consume(|buf| assert_eq!(buf, &received[..]));
+    tx.consume(60, |buf| buf.fill(0));
+    assert_eq!(dev.tx_dropped(), 1);
+    assert!(!dev.rx_available());
+}
+
+#[test]
+fn receive() {
+    let mock = MockPlatform::new(DESC_SIZE, BUF_SIZE);
+    let mut dev = driver(&mock);
+    let rx_base = mock.rx_ring();
+
+    let received = frame(200);
+    assert!(mock.gem_receive(rx_base, &received));
+    assert!(dev.rx_available());
+
+    // Just the frame the descriptor says was received, not the rest of its buffer
+    let (rx, _tx) = dev.receive(Instant::ZERO).unwrap();
+    rx.consume(|buf| assert_eq!(buf, &received[..]));
+
+    // The descriptor went back to the GEM, and nothing else has arrived
+    assert!(!dev.rx_available());
+    assert!(dev.receive(Instant::ZERO).is_none());
+    assert!(mock.gem_receive(rx_base, &received));
+}
+
+#[test]
+fn receive_in_ring_order() {
+    let mock = MockPlatform::new(DESC_SIZE, BUF_SIZE);
+    let mut dev = driver(&mock);
+    let rx_base = mock.rx_ring();
+
+    let frames = [frame(60), frame(1000)];
+    for (i, frame) in frames.iter().enumerate() {
+        assert!(mock.gem_receive(rx_base + DESC_STRIDE * i as u32, frame));
+    }
+    for frame in &frames {
+        let (rx, _tx) = dev.receive(Instant::ZERO).unwrap();
+        rx.consume(|buf| assert_eq!(buf, &frame[..]));
+    }
+    assert!(dev.receive(Instant::ZERO).is_none());
+}
+
+#[test]
+fn stop_and_start() {
+    // RX_ENABLE and TX_ENABLE in network_control
+    const RX_TX_ENABLE: u32 = 0b1100;
+    let mock = MockPlatform::new(DESC_SIZE, BUF_SIZE);
+    let mut dev = driver(&mock);
+    let (rx_base, tx_base) = (mock.rx_ring(), mock.tx_ring());
+
+    // Move both rings on from their first entry, and leave a frame the client hasn't taken
+    send(&mut dev, &frame(60));
+    assert!(mock.gem_transmitted(tx_base).is_some());
+    assert!(mock.gem_receive(rx_base, &frame(100)));
+
+    dev.stop(false, &());
+    assert!(!dev.is_running());
+    assert!(!dev.tx_available());
+    let snapshot = dev.snapshot();
+    assert!(!snapshot.running);
+    assert_eq!(snapshot.regs.network_control & RX_TX_ENABLE, 0);
+    assert_eq!((snapshot.tx.curr_entry, snapshot.tx.in_flight), (0, 0));
+    assert!(snapshot.tx.descs.iter().all(|desc| desc.used));
+    for ring in [&snapshot.rx, &snapshot.rx_priority] {
+        assert_eq!(ring.curr_entry, 0);
+        assert!(ring.descs.iter().all(|desc| !desc.avail));
+    }
+    assert!(!dev.rx_available());
+
+    dev.start();
+    assert!(dev.is_running());
+    let snapshot = dev.snapshot();
+    assert!(snapshot.running);
+    assert_eq!(snapshot.regs.network_control & RX_TX_ENABLE, RX_TX_ENABLE);
+    assert_eq!(snapshot.regs.receive_q_ptr, snapshot.rx.base_paddr);
+    assert_eq!(snapshot.regs.transmit_q_ptr, snapshot.tx.base_paddr);
+    assert_eq!(
+        snapshot.regs.receive_q1_ptr,
+        snapshot.rx_priority.base_paddr
+    );
+
+    // Both rings carry on from their first entry
+    let sent = frame(80);
+    send(&mut dev, &sent);
+    assert_eq!(mock.gem_transmitted(tx_base), Some(sent));
+    let received = frame(120);
+    assert!(mock.gem_receive(rx_base, &received));
+    let (rx, _tx) = dev.receive(Instant::ZERO).unwrap();
+    rx.consume(|buf| assert_eq!(buf, &received[..]));
+}
+
+#[test]
+fn multicast_hash() {
+    let mock = MockPlatform::new(DESC_SIZE, BUF_SIZE);
+    let mut dev = driver(&mock);
+    let hash = || ((mock.reg(HASH_TOP) as u64) << 32) | mock.reg(HASH_BOTTOM) as u64;
+    let enabled = || mock.reg(NETWORK_CONFIG) & MULTICAST_HASH_ENABLE != 0;
+
+    // Bins worked out by hand from the TRM: bit n of the bin is the XOR of address bits n, n + 6,
+    // ..., n + 42, counting from the least significant bit of the first byte
+    let groups = [
+        // Broadcast, every bit of the bin is the XOR of eight ones
+        ([0xFF; 6], 0),
+        // IPv6 all-nodes
+        ([0x33, 0x33, 0x00, 0x00, 0x00, 0x01], 44),
+        // IPv4 all-hosts
+        ([0x01, 0x00, 0x5E, 0x00, 0x00, 0x01], 38),
+    ];
+    for (group, bin) in groups {
+        dev.set_multicast_filter(&[group]);
+        assert_eq!(hash(), 1 << bin, "{group:02x?}");
+        assert!(enabled());
+    }
+
+    dev.set_multicast_filter(&groups.map(|(group, _)| group));
+    assert_eq!(hash(), 1 | (1 << 44) | (1 << 38));
+    assert!(enabled());
+
+    dev.set_multicast_filter(&[]);
+    assert_eq!(hash(), 0);
+    assert!(!enabled());
+}
+
+#[test]
+fn unicast_filter_writes_bottom_first() {
+    let addrs = [
+        [0x02, 0x00, 0x00, 0x00, 0x00, 0x01],
+        [0x02, 0x11, 0x22, 0x33, 0x44, 0x55],
+    ];
+    let writes: Vec<_> = filter_writes(&addrs).unwrap().collect();
+    assert_eq!(
+        writes,
+        [
+            FilterWrite::Bottom {
+                filter: 0,
+                val: 0x0000_0002
+            },
+            FilterWrite::Top {
+                filter: 0,
+                val: 0x0100
+            },
+            FilterWrite::Bottom {
+                filter: 1,
+                val: 0x3322_1102
+            },
+            FilterWrite::Top {
+                filter: 1,
+                val: 0x5544
+            },
+            // Left off
+            FilterWrite::Bottom { filter: 2, val: 0 },
+        ]
+    );
+}
+
+#[test]
+fn unicast_filter() {
+    let mock = MockPlatform::new(DESC_SIZE, BUF_SIZE);
+    let mut dev = driver(&mock);
+    let filter = |i: usize| {
+        let offset = SPECIFIC_ADDRESS + i * SPECIFIC_ADDRESS_STRIDE;
+        (mock.reg(offset), mock.reg(offset + 4))
+    };
+
+    let addrs = [
+        [0x02, 0x00, 0x00, 0x00, 0x00, 0x01],
+        [0x02, 0x00, 0x00, 0x00, 0x00, 0x02],
+        [0x02, 0x00, 0x00, 0x00, 0x00, 0x03],
+    ];
+    dev.set_unicast_filter(&addrs).unwrap();
+    for i in 0..3 {
+        assert_eq!(filter(i), (0x0000_0002, (i as u32 + 1) << 8));
+    }
+
+    // The unused filters are turned off by clearing their bottom registers
+    dev.set_unicast_filter(&addrs[..1]).unwrap();
+    assert_eq!(filter(0), (0x0000_0002, 0x0100));
+    assert_eq!(filter(1).0, 0);
+    assert_eq!(filter(2).0, 0);
+
+    // Nothing is written when they don't all fit
+    let too_many = [[0x02, 0, 0, 0, 0, 0x04]; 4];
+    assert_eq!(dev.set_unicast_filter(&too_many), Err(TooManyAddresses));
+    assert!(filter_writes(&too_many).is_err());
+    assert_eq!(filter(0), (0x0000_0002, 0x0100));
+}
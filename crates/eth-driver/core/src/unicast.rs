@@ -0,0 +1,63 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+use super::Driver;
+use crate::regs::NUM_EXTRA_SPECIFIC_ADDRESSES;
+use core::iter;
+use tock_registers::interfaces::Writeable;
+
+// Reception of frames sent to unicast addresses besides the GEM's own, through specific address
+// filters 2 to 4, so that clients of a network virtualiser can each have their own.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TooManyAddresses;
+
+/// A register write made by `set_unicast_filter`, to extra filter `filter`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum FilterWrite {
+    Bottom { filter: usize, val: u32 },
+    Top { filter: usize, val: u32 },
+}
+
+/// The writes that set the filters to `addrs`, in order. Writing a filter's bottom register
+/// turns it off until its top register is written, so each address goes in bottom first and
+/// the filters left over only have their bottom cleared.
+pub(crate) fn filter_writes(
+    addrs: &[[u8; 6]],
+) -> Result<impl Iterator<Item = FilterWrite> + '_, TooManyAddresses> {
+    if addrs.len() > NUM_EXTRA_SPECIFIC_ADDRESSES {
+        return Err(TooManyAddresses);
+    }
+    Ok((0..NUM_EXTRA_SPECIFIC_ADDRESSES).flat_map(move |filter| {
+        let (bottom, top) = match addrs.get(filter) {
+            Some(addr) => (
+                u32::from_le_bytes([addr[0], addr[1], addr[2], addr[3]]),
+                Some(u16::from_le_bytes([addr[4], addr[5]]) as u32),
+            ),
+            None => (0, None),
+        };
+        iter::once(FilterWrite::Bottom {
+            filter,
+            val: bottom,
+        })
+        .chain(top.map(|val| FilterWrite::Top { filter, val }))
+    }))
+}
+
+impl Driver {
+    /// Also receive frames sent to `addrs`, replacing any set before. There is room for three.
+    pub fn set_unicast_filter(&mut self, addrs: &[[u8; 6]]) -> Result<(), TooManyAddresses> {
+        for write in filter_writes(addrs)? {
+            match write {
+                FilterWrite::Bottom { filter, val } => {
+                    self.regs.specific_address[filter].bottom.set(val)
+                }
+                FilterWrite::Top { filter, val } => self.regs.specific_address[filter].top.set(val),
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,231 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+#![no_std]
+
+use core::fmt;
+
+// Values shared between the driver and its clients so both sides of the rings agree.
+
+/// Largest frame (excluding FCS) the driver will send or receive.
+#[cfg(not(feature = "jumbo"))]
+pub const MTU: usize = 1600;
+
+/// With jumbo frames enabled this is the GEM's maximum jumbo frame length.
+#[cfg(feature = "jumbo")]
+pub const MTU: usize = 10240;
+
+/// MAC addresses of the network virtualiser's clients. The driver receives frames for all of
+/// them, however many clients the virtualiser has.
+pub mod virt {
+    /// The port's own address, and the three more the GEM can filter on
+    pub const MAX_CLIENTS: usize = 4;
+
+    /// Client 0 has the port's own address. The others have it with the locally administered
+    /// bit set and the client number added to the last byte.
+    pub fn client_mac(port_mac: [u8; 6], client: usize) -> [u8; 6] {
+        assert!(client < MAX_CLIENTS);
+        let mut mac = port_mac;
+        if client > 0 {
+            mac[0] |= 0x02;
+            mac[5] = mac[5].wrapping_add(client as u8);
+        }
+        mac
+    }
+}
+
+/// Protected calls on the driver's control channel. The message label selects the operation,
+/// replies are labelled `OK` or `ERROR`.
+pub mod control {
+    /// Stop reception and transmission. Message register 0 is non-zero to also power down the
+    /// PHY.
+    pub const STOP: u64 = 1;
+    /// Restart after `STOP`
+    pub const START: u64 = 2;
+    /// Turn packet capture on if message register 0 is non-zero, off otherwise
+    pub const CAPTURE: u64 = 3;
+    /// Log a dump of the descriptor rings and GEM registers
+    pub const SNAPSHOT: u64 = 4;
+    /// Reply with a [`Status`](super::Status) in the message registers
+    pub const STATUS: u64 = 5;
+    /// Take the oldest of the driver's log records, as a [`LogRecord`](super::LogRecord) in the
+    /// message registers, or none once there are no more. Also works when the driver failed to
+    /// initialize. The driver notifies the control channel when it has queued a record.
+    pub const LOG: u64 = 6;
+
+    pub const OK: u64 = 0;
+    pub const ERROR: u64 = 1;
+}
+
+/// The link and the driver's counters, as returned by `control::STATUS`. Counters run from
+/// boot and include frames dropped by the GEM itself.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Status {
+    /// 10, 100 or 1000
+    pub speed_mbps: u64,
+    pub full_duplex: bool,
+    /// Not stopped with `control::STOP`
+    pub running: bool,
+    pub rx_frames: u64,
+    pub rx_bytes: u64,
+    /// Frames with bad FCS, length or alignment, and frames the GEM had nowhere to put
+    pub rx_errors: u64,
+    pub tx_frames: u64,
+    pub tx_bytes: u64,
+    /// Frames the driver dropped because the TX ring was full
+    pub tx_dropped: u64,
+}
+
+impl Status {
+    /// Message registers the status takes up
+    pub const WORDS: usize = 9;
+
+    pub fn to_words(&self) -> [u64; Self::WORDS] {
+        [
+            self.speed_mbps,
+            self.full_duplex as u64,
+            self.running as u64,
+            self.rx_frames,
+            self.rx_bytes,
+            self.rx_errors,
+            self.tx_frames,
+            self.tx_bytes,
+            self.tx_dropped,
+        ]
+    }
+
+    pub fn from_words(words: &[u64; Self::WORDS]) -> Self {
+        Self {
+            speed_mbps: words[0],
+            full_duplex: words[1] != 0,
+            running: words[2] != 0,
+            rx_frames: words[3],
+            rx_bytes: words[4],
+            rx_errors: words[5],
+            tx_frames: words[6],
+            tx_bytes: words[7],
+            tx_dropped: words[8],
+        }
+    }
+}
+
+/// One of the driver's log records, as returned by `control::LOG`. The target and message are
+/// truncated to `MAX_TEXT` bytes between them.
+#[derive(Clone)]
+pub struct LogRecord {
+    /// As `log::Level`, from 1 for errors to 5 for traces
+    pub level: u8,
+    /// Records the driver dropped just before this one, having had no room for them
+    pub dropped: u32,
+    target_len: usize,
+    len: usize,
+    text: [u8; Self::MAX_TEXT],
+}
+
+impl LogRecord {
+    pub const MAX_TEXT: usize = 256;
+    /// Most message registers a record takes up
+    pub const MAX_WORDS: usize = 2 + Self::MAX_TEXT / 8;
+
+    pub const EMPTY: Self = Self {
+        level: 0,
+        dropped: 0,
+        target_len: 0,
+        len: 0,
+        text: [0; Self::MAX_TEXT],
+    };
+
+    pub fn new(level: u8, dropped: u32, target: &str, message: fmt::Arguments) -> Self {
+        let mut record = Self {
+            level,
+            dropped,
+            ..Self::EMPTY
+        };
+        // Truncation is all that can go wrong, and that isn't an error
+        let _ = fmt::Write::write_str(&mut record, target);
+        record.target_len = record.len;
+        let _ = fmt::write(&mut record, message);
+        record
+    }
+
+    pub fn target(&self) -> &str {
+        // Only ever filled in from strs, on character boundaries
+        core::str::from_utf8(&self.text[..self.target_len]).unwrap_or_default()
+    }
+
+    pub fn message(&self) -> &str {
+        core::str::from_utf8(&self.text[self.target_len..self.len]).unwrap_or_default()
+    }
+
+    /// The level, the number dropped and the two lengths, then the text packed little endian
+    /// into as many words as it needs. Returns how many were written.
+    pub fn to_words(&self, words: &mut [u64; Self::MAX_WORDS]) -> usize {
+        words[0] = self.level as u64 | (self.dropped as u64) << 32;
+        words[1] = self.target_len as u64 | (self.len as u64) << 32;
+        let chunks = self.text[..self.len].chunks(8);
+        let count = chunks.len();
+        for (word, chunk) in words[2..].iter_mut().zip(chunks) {
+            let mut bytes = [0; 8];
+            bytes[..chunk.len()].copy_from_slice(chunk);
+            *word = u64::from_le_bytes(bytes);
+        }
+        2 + count
+    }
+
+    pub fn from_words(words: &[u64]) -> Option<Self> {
+        let [header, lens] = *words.get(..2)? else {
+            return None;
+        };
+        let (target_len, len) = ((lens & 0xFFFF_FFFF) as usize, (lens >> 32) as usize);
+        if target_len > len || len > Self::MAX_TEXT {
+            return None;
+        }
+        let mut record = Self {
+            level: header as u8,
+            dropped: (header >> 32) as u32,
+            target_len,
+            len,
+            ..Self::EMPTY
+        };
+        for (chunk, word) in record.text[..len]
+            .chunks_mut(8)
+            .zip(words.get(2..2 + len.div_ceil(8))?)
+        {
+            chunk.copy_from_slice(&word.to_le_bytes()[..chunk.len()]);
+        }
+        core::str::from_utf8(&record.text[..target_len]).ok()?;
+        core::str::from_utf8(&record.text[target_len..len]).ok()?;
+        Some(record)
+    }
+}
+
+impl fmt::Write for LogRecord {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let room = Self::MAX_TEXT - self.len;
+        let mut take = s.len().min(room);
+        while !s.is_char_boundary(take) {
+            take -= 1;
+        }
+        self.text[self.len..self.len + take].copy_from_slice(&s.as_bytes()[..take]);
+        self.len += take;
+        if take < s.len() {
+            Err(fmt::Error)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl fmt::Debug for LogRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LogRecord")
+            .field("level", &self.level)
+            .field("dropped", &self.dropped)
+            .field("target", &self.target())
+            .field("message", &self.message())
+            .finish()
+    }
+}
@@ -0,0 +1,114 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+use crate::managed::Control;
+use capture_ring::{Direction, Producer};
+use core::cell::Cell;
+use eth_driver_core::{GemRxToken, TxObserver};
+use sel4_microkit::Channel;
+use smoltcp::phy;
+
+// Copies frames passing through the driver into the capture region as pcapng, for the capture
+// PD to drain. Nothing is copied unless capture has been turned on over the control channel.
+//
+// Received frames are copied as the client takes them, and sent frames once the GEM has sent
+// them, so frames the GEM failed to send aren't captured.
+
+/// Which frames are captured, and how much of each
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Filter {
+    pub rx: bool,
+    pub tx: bool,
+    /// Only capture frames with this EtherType (not looking through VLAN tags)
+    pub ethertype: Option<u16>,
+    pub snap_len: u32,
+}
+
+impl Filter {
+    fn matches(&self, direction: Direction, frame: &[u8]) -> bool {
+        let direction = match direction {
+            Direction::Inbound => self.rx,
+            Direction::Outbound => self.tx,
+        };
+        let ethertype = match self.ethertype {
+            Some(ethertype) => frame.get(12..14) == Some(&ethertype.to_be_bytes()[..]),
+            None => true,
+        };
+        direction && ethertype
+    }
+}
+
+pub struct Tap {
+    producer: Producer,
+    filter: Filter,
+    control: &'static Control,
+    consumer: Channel,
+    pending: Cell<bool>,
+}
+
+impl TxObserver for Tap {
+    fn sent(&self, frame: &[u8]) {
+        self.capture(Direction::Outbound, frame);
+    }
+}
+
+impl Tap {
+    /// # Safety
+    ///
+    /// See `Producer::new`
+    pub unsafe fn new(
+        region: *mut u8,
+        region_size: usize,
+        filter: Filter,
+        control: &'static Control,
+        consumer: Channel,
+    ) -> Self {
+        Self {
+            producer: Producer::new(region, region_size, filter.snap_len),
+            filter,
+            control,
+            consumer,
+            pending: Cell::new(false),
+        }
+    }
+
+    fn capture(&self, direction: Direction, frame: &[u8]) {
+        if !self.control.capture_enabled() || !self.filter.matches(direction, frame) {
+            return;
+        }
+        if self
+            .producer
+            .write_packet(timer_interface::counter_us(), direction, frame)
+        {
+            self.pending.set(true);
+        }
+    }
+
+    /// Let the capture PD know there is something to drain
+    pub fn flush(&self) {
+        if self.pending.replace(false) {
+            self.consumer.notify();
+        }
+    }
+}
+
+pub struct TapRxToken<'a> {
+    pub(crate) inner: GemRxToken<'a>,
+    pub(crate) tap: &'a Tap,
+}
+
+impl phy::RxToken for TapRxToken<'_> {
+    fn consume<R, F>(self, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        let tap = self.tap;
+        self.inner.consume(|frame| {
+            tap.capture(Direction::Inbound, frame);
+            f(frame)
+        })
+    }
+}
@@ -9,24 +9,99 @@ pub mod channels {
 
     pub const DEVICE: Channel = Channel::new(0);
     pub const CLIENT: Channel = Channel::new(1);
+    pub const CONTROL: Channel = Channel::new(2);
+    pub const CAPTURE: Channel = Channel::new(3);
 }
 
 pub mod sizes {
+    pub const DRIVER_DESC: usize = 0x1000;
+    #[cfg(not(feature = "jumbo"))]
     pub const DRIVER_DMA: usize = 0x20_0000;
+    #[cfg(feature = "jumbo")]
+    pub const DRIVER_DMA: usize = 0x40_0000;
+    #[cfg(not(feature = "jumbo"))]
     pub const NET_CLIENT_DMA: usize = 0x20_0000;
+    #[cfg(feature = "jumbo")]
+    pub const NET_CLIENT_DMA: usize = 0x40_0000;
+    pub const NET_CAPTURE: usize = 0x10_0000;
+}
+
+pub mod multicast {
+    use eth_driver_interface::virt::MAX_CLIENTS;
+
+    /// IPv6 all-nodes, and for each of `macs` the solicited-node group for addresses with an
+    /// interface identifier derived from it, which covers both the link-local and SLAAC
+    /// addresses
+    pub fn groups(macs: &[[u8; 6]; MAX_CLIENTS]) -> [[u8; 6]; 1 + MAX_CLIENTS] {
+        let mut groups = [[0x33, 0x33, 0x00, 0x00, 0x00, 0x01]; 1 + MAX_CLIENTS];
+        for (group, mac) in groups[1..].iter_mut().zip(macs) {
+            *group = [0x33, 0x33, 0xFF, mac[3], mac[4], mac[5]];
+        }
+        groups
+    }
+}
+
+pub mod capture {
+    use crate::capture::Filter;
+
+    /// Capture can also be turned on and off over the control channel
+    pub const ENABLED_AT_BOOT: bool = false;
+
+    pub const FILTER: Filter = Filter {
+        rx: true,
+        tx: true,
+        ethertype: None,
+        snap_len: 256,
+    };
+}
+
+pub mod screeners {
+    use eth_driver_core::{Compare, CompareBase, RxQueue, Type1Screener, Type2Screener};
+
+    const ETHERTYPE_IPV4: u16 = 0x0800;
+    const ETHERTYPE_ARP: u16 = 0x0806;
+    const IP_PROTOCOL_ICMP: u16 = 1;
+
+    pub const TYPE_1: &[Type1Screener] = &[];
+
+    // Keep ARP and ICMP on the priority queue so bulk traffic can't starve them
+    pub const TYPE_2: &[Type2Screener] = &[
+        Type2Screener {
+            ethertype: Some(ETHERTYPE_ARP),
+            vlan_priority: None,
+            compare: None,
+            queue: RxQueue::Priority,
+        },
+        Type2Screener {
+            ethertype: Some(ETHERTYPE_IPV4),
+            vlan_priority: None,
+            // TTL and protocol are the 16 bits at offset 8 of the IPv4 header
+            compare: Some(Compare {
+                base: CompareBase::AfterEthertype,
+                offset: 8,
+                value: IP_PROTOCOL_ICMP,
+                mask: 0x00FF,
+            }),
+            queue: RxQueue::Priority,
+        },
+    ];
 }
 
 pub mod log {
     use sel4_logging::{LevelFilter, Logger, LoggerBuilder};
     use sel4_microkit::debug_print;
 
-    const LOG_LEVEL: LevelFilter = {
+    pub const LOG_LEVEL: LevelFilter = {
         // LevelFilter::Trace
         // LevelFilter::Debug
         LevelFilter::Info
         // LevelFilter::Warn
     };
 
+    /// Records held for `ping` to pass on to syslog, see `logger`
+    pub const QUEUE_LEN: usize = 16;
+
+    /// Writes to the serial console, `logger::LOGGER` passes records on to it
     pub static LOGGER: Logger = LoggerBuilder::const_default()
         .level_filter(LOG_LEVEL)
         .write(|s| debug_print!("{}", s))
@@ -0,0 +1,111 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+use crate::config;
+use crate::logger::LOGGER;
+use crate::managed::{Control, DriverRef, DRIVER};
+use eth_driver_core::InitError;
+use eth_driver_interface::{control, LogRecord, Status};
+use log::warn;
+use sel4_microkit::{with_msg_regs, with_msg_regs_mut, Channel, Handler, MessageInfo};
+use sel4_microkit_driver_adapters::net::driver::HandlerImpl;
+use sel4_microkit_message::MessageInfoExt;
+
+type DriverHandlerImpl = HandlerImpl<DriverRef>;
+
+pub static CONTROL: Control = Control::new();
+
+// Built once and never moved, and there is no heap to box it on
+#[allow(clippy::large_enum_variant)]
+pub enum DriverHandler {
+    Running(DriverHandlerImpl),
+    /// The driver failed to initialize. The GEM's IRQ is never acked, so whatever state the
+    /// GEM was left in stays quiet, and the client's calls fail.
+    Failed(InitError),
+}
+
+impl DriverHandler {
+    fn log() -> MessageInfo {
+        let count = match LOGGER.take() {
+            Some(record) => with_msg_regs_mut(|mrs| {
+                record.to_words((&mut mrs[..LogRecord::MAX_WORDS]).try_into().unwrap())
+            }),
+            None => 0,
+        };
+        MessageInfo::new(control::OK, count)
+    }
+
+    fn control(msg_info: &MessageInfo) -> MessageInfo {
+        let flag = || msg_info.count() > 0 && with_msg_regs(|mrs| mrs[0]) != 0;
+        let label = msg_info.label();
+        if label == control::CAPTURE {
+            CONTROL.set_capture(flag());
+            return MessageInfo::new(control::OK, 0);
+        }
+        let done = |ok| MessageInfo::new(if ok { control::OK } else { control::ERROR }, 0);
+        // `HandlerImpl` only uses the driver while it handles its own calls
+        let reply = unsafe {
+            DRIVER.with(|driver| match label {
+                control::STOP => {
+                    driver.stop(flag());
+                    Some(done(!driver.is_running()))
+                }
+                control::START => {
+                    driver.start();
+                    Some(done(driver.is_running()))
+                }
+                control::SNAPSHOT => {
+                    driver.log_snapshot();
+                    Some(done(true))
+                }
+                control::STATUS => {
+                    let words = driver.status().to_words();
+                    with_msg_regs_mut(|mrs| mrs[..Status::WORDS].copy_from_slice(&words));
+                    Some(MessageInfo::new(control::OK, Status::WORDS))
+                }
+                _ => None,
+            })
+        };
+        reply.flatten().unwrap_or_else(|| {
+            warn!("Unknown control request: {label}");
+            MessageInfo::new(control::ERROR, 0)
+        })
+    }
+}
+
+impl Handler for DriverHandler {
+    type Error = <DriverHandlerImpl as Handler>::Error;
+
+    fn notified(&mut self, channel: Channel) -> Result<(), Self::Error> {
+        match self {
+            Self::Running(handler) => handler.notified(channel),
+            Self::Failed(_) => Ok(()),
+        }
+    }
+
+    fn protected(
+        &mut self,
+        channel: Channel,
+        msg_info: MessageInfo,
+    ) -> Result<MessageInfo, Self::Error> {
+        if channel == config::channels::CONTROL && msg_info.label() == control::LOG {
+            return Ok(Self::log());
+        }
+        match self {
+            Self::Running(_) if channel == config::channels::CONTROL => {
+                Ok(Self::control(&msg_info))
+            }
+            Self::Running(handler) => handler.protected(channel, msg_info),
+            Self::Failed(_) if channel == config::channels::CONTROL => {
+                Ok(MessageInfo::new(control::ERROR, 0))
+            }
+            Self::Failed(err) => {
+                warn!("Driver unavailable: {err:?}");
+                Ok(MessageInfo::send_unspecified_error())
+            }
+        }
+    }
+}
@@ -0,0 +1,90 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+use crate::config;
+use core::sync::atomic::{AtomicU32, Ordering};
+use eth_driver_interface::LogRecord;
+use log::{Log, Metadata, Record};
+use spin::Mutex;
+
+// Every record goes to the serial console, and is also queued for `ping` to take with
+// `control::LOG` and pass on to its syslog collector. The control channel is notified for each
+// one. Once the queue is full, further records are dropped and counted until `ping` catches up.
+
+pub static LOGGER: Logger = Logger {
+    serial: &config::log::LOGGER,
+    queue: Mutex::new(Queue {
+        records: [const { LogRecord::EMPTY }; config::log::QUEUE_LEN],
+        head: 0,
+        len: 0,
+    }),
+    dropped: AtomicU32::new(0),
+};
+
+struct Queue {
+    records: [LogRecord; config::log::QUEUE_LEN],
+    head: usize,
+    len: usize,
+}
+
+pub struct Logger {
+    serial: &'static sel4_logging::Logger,
+    queue: Mutex<Queue>,
+    // Since the last record that was queued
+    dropped: AtomicU32,
+}
+
+impl Logger {
+    /// The oldest queued record, if any
+    pub fn take(&self) -> Option<LogRecord> {
+        let mut queue = self.queue.lock();
+        if queue.len == 0 {
+            return None;
+        }
+        let head = queue.head;
+        let record = queue.records[head].clone();
+        queue.head = (head + 1) % config::log::QUEUE_LEN;
+        queue.len -= 1;
+        Some(record)
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.serial.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        self.serial.log(record);
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        // Nothing logs while the queue is locked, but a record that comes in while it is can
+        // only be dropped
+        let Some(mut queue) = self.queue.try_lock() else {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            return;
+        };
+        if queue.len == config::log::QUEUE_LEN {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        let tail = (queue.head + queue.len) % config::log::QUEUE_LEN;
+        queue.records[tail] = LogRecord::new(
+            record.level() as u8,
+            self.dropped.swap(0, Ordering::Relaxed),
+            record.target(),
+            *record.args(),
+        );
+        queue.len += 1;
+        drop(queue);
+        config::channels::CONTROL.notify();
+    }
+
+    fn flush(&self) {
+        self.serial.flush();
+    }
+}
@@ -8,31 +8,70 @@
 #![no_std]
 #![no_main]
 
-use eth_driver_core::{DmaDef, Driver};
-use log::info;
+use core::array;
+use eth_driver_core::{DmaDef, Driver, InitError};
+use eth_driver_interface::virt;
+use log::{error, info};
 use sel4_driver_interfaces::HandleInterrupt;
 use sel4_externally_shared::{ExternallySharedRef, ExternallySharedRefExt};
 use sel4_microkit::{memory_region_symbol, protection_domain};
 use sel4_microkit_driver_adapters::net::driver::HandlerImpl;
 use sel4_shared_ring_buffer::{roles::Use, RingBuffers};
 
+mod capture;
 mod config;
+mod handler;
+mod logger;
+mod managed;
+
+use capture::Tap;
+use handler::{DriverHandler, CONTROL};
+use managed::{DriverRef, ManagedDriver, DRIVER};
 
 #[protection_domain]
-fn init() -> HandlerImpl<Driver> {
-    config::log::LOGGER.set().unwrap();
+fn init() -> DriverHandler {
+    log::set_logger(&logger::LOGGER).unwrap();
+    log::set_max_level(config::log::LOG_LEVEL);
+    match init_driver() {
+        Ok(handler) => DriverHandler::Running(handler),
+        Err(err) => {
+            error!("Failed to initialize driver: {err:?}");
+            DriverHandler::Failed(err)
+        }
+    }
+}
+
+fn init_driver() -> Result<HandlerImpl<DriverRef>, InitError> {
     let mut dev = {
-        let dma = DmaDef {
+        let desc_dma = DmaDef {
+            vaddr: memory_region_symbol!(net_driver_desc_vaddr: *mut ()),
+            paddr: memory_region_symbol!(net_driver_desc_paddr: *mut ()),
+            size: config::sizes::DRIVER_DESC,
+        };
+        let buf_dma = DmaDef {
             vaddr: memory_region_symbol!(net_driver_dma_vaddr: *mut ()),
             paddr: memory_region_symbol!(net_driver_dma_paddr: *mut ()),
             size: config::sizes::DRIVER_DMA,
         };
         Driver::new(
             memory_region_symbol!(gem_register_block: *mut ()).as_ptr(),
-            dma,
-        )
+            desc_dma,
+            buf_dma,
+        )?
     };
 
+    for (i, screener) in config::screeners::TYPE_1.iter().enumerate() {
+        dev.set_type1_screener(i, screener).unwrap();
+    }
+    for (i, screener) in config::screeners::TYPE_2.iter().enumerate() {
+        dev.set_type2_screener(i, screener).unwrap();
+    }
+    // Frames for any of the network virtualiser's clients, see `eth_driver_interface::virt`
+    let client_macs: [_; virt::MAX_CLIENTS] =
+        array::from_fn(|client| virt::client_mac(dev.mac_address(), client));
+    dev.set_unicast_filter(&client_macs[1..]).unwrap();
+    dev.set_multicast_filter(&config::multicast::groups(&client_macs));
+
     let client_region = unsafe {
         ExternallySharedRef::<'static, _>::new(
             memory_region_symbol!(net_client_dma_vaddr: *mut [u8], n = config::sizes::NET_CLIENT_DMA),
@@ -55,18 +94,30 @@ fn init() -> HandlerImpl<Driver> {
             notify_client,
         );
 
+    let tap = unsafe {
+        Tap::new(
+            memory_region_symbol!(net_capture_vaddr: *mut u8).as_ptr(),
+            config::sizes::NET_CAPTURE,
+            config::capture::FILTER,
+            &CONTROL,
+            config::channels::CAPTURE,
+        )
+    };
+
     info!("Finished Initializing Driver");
     dev.handle_interrupt();
     info!("Acked driver IRQ");
-    config::channels::DEVICE.irq_ack().unwrap();
-    info!("Acked physical IRQ");
+    match config::channels::DEVICE.irq_ack() {
+        Ok(()) => info!("Acked physical IRQ"),
+        Err(err) => error!("Failed to ack physical IRQ: {err:?}"),
+    }
 
-    HandlerImpl::<Driver>::new(
-        dev,
+    Ok(HandlerImpl::new(
+        DRIVER.init(ManagedDriver::new(dev, tap)),
         client_region,
         rx_ring_buffers,
         tx_ring_buffers,
         config::channels::DEVICE,
         config::channels::CLIENT,
-    )
+    ))
 }
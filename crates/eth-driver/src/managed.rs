@@ -0,0 +1,180 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+use crate::capture::{Tap, TapRxToken};
+use crate::config;
+use core::cell::UnsafeCell;
+use core::sync::atomic::{AtomicBool, Ordering};
+use eth_driver_core::{Driver, GemTxToken};
+use eth_driver_interface::Status;
+use log::info;
+use sel4_driver_interfaces::net::{GetNetDeviceMeta, MacAddress};
+use sel4_driver_interfaces::HandleInterrupt;
+use smoltcp::{
+    phy::{Device, DeviceCapabilities},
+    time::Instant,
+};
+
+// The adapters' `HandlerImpl` owns the device it is given and has no way to reach it from
+// outside. It is given a `DriverRef` to the one `ManagedDriver` in `DRIVER` instead, so that
+// `DriverHandler` can apply control requests to the driver directly.
+
+pub static DRIVER: DriverCell = DriverCell(UnsafeCell::new(None));
+
+/// Only used from the PD's one thread, by `HandlerImpl` through its `DriverRef` while it handles
+/// a notification or a client's call, and by `DriverHandler` while it handles a control call.
+/// Neither holds on to the driver past the call, so it is never reached twice at once.
+pub struct DriverCell(UnsafeCell<Option<ManagedDriver>>);
+
+unsafe impl Sync for DriverCell {}
+
+impl DriverCell {
+    /// Call once, from `init`
+    pub fn init(&'static self, driver: ManagedDriver) -> DriverRef {
+        unsafe { *self.0.get() = Some(driver) };
+        DriverRef(self)
+    }
+
+    /// # Safety
+    ///
+    /// Not while `HandlerImpl` is using its `DriverRef`
+    pub unsafe fn with<R>(&self, f: impl FnOnce(&mut ManagedDriver) -> R) -> Option<R> {
+        (*self.0.get()).as_mut().map(f)
+    }
+}
+
+pub struct DriverRef(&'static DriverCell);
+
+// `DriverCell::init` set the driver before making the `DriverRef`, see `DriverCell` for the rest
+impl DriverRef {
+    fn driver(&self) -> &ManagedDriver {
+        unsafe { (*self.0 .0.get()).as_ref() }.unwrap()
+    }
+
+    fn driver_mut(&mut self) -> &mut ManagedDriver {
+        unsafe { (*self.0 .0.get()).as_mut() }.unwrap()
+    }
+}
+
+pub struct Control {
+    capture: AtomicBool,
+}
+
+impl Control {
+    pub const fn new() -> Self {
+        Self {
+            capture: AtomicBool::new(config::capture::ENABLED_AT_BOOT),
+        }
+    }
+
+    /// Takes effect straight away, the tap checks this for every frame
+    pub fn set_capture(&self, enabled: bool) {
+        self.capture.store(enabled, Ordering::Relaxed);
+    }
+
+    pub fn capture_enabled(&self) -> bool {
+        self.capture.load(Ordering::Relaxed)
+    }
+}
+
+pub struct ManagedDriver {
+    driver: Driver,
+    tap: Tap,
+}
+
+impl ManagedDriver {
+    pub fn new(driver: Driver, tap: Tap) -> Self {
+        Self { driver, tap }
+    }
+
+    pub fn stop(&mut self, power_down_phy: bool) {
+        self.driver.stop(power_down_phy, &self.tap);
+        self.tap.flush();
+    }
+
+    pub fn start(&mut self) {
+        self.driver.start();
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.driver.is_running()
+    }
+
+    pub fn log_snapshot(&self) {
+        info!("{}", self.driver.snapshot());
+    }
+
+    pub fn status(&self) -> Status {
+        self.driver.status()
+    }
+}
+
+impl Device for ManagedDriver {
+    type RxToken<'a> = TapRxToken<'a>;
+    type TxToken<'a> = GemTxToken<'a>;
+
+    fn receive(&mut self, _timestamp: Instant) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
+        let tap = &self.tap;
+        let (rx, tx) = self.driver.receive_observed(tap)?;
+        Some((TapRxToken { inner: rx, tap }, tx))
+    }
+
+    fn transmit(&mut self, _timestamp: Instant) -> Option<Self::TxToken<'_>> {
+        self.driver.transmit_observed(&self.tap)
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        self.driver.capabilities()
+    }
+}
+
+impl HandleInterrupt for ManagedDriver {
+    fn handle_interrupt(&mut self) {
+        self.driver.handle_interrupt();
+        // Sent frames are followed by a transmit complete interrupt
+        self.driver.reclaim_tx(&self.tap);
+        self.tap.flush();
+    }
+}
+
+impl GetNetDeviceMeta for ManagedDriver {
+    type Error = <Driver as GetNetDeviceMeta>::Error;
+
+    fn get_mac_address(&mut self) -> Result<MacAddress, Self::Error> {
+        self.driver.get_mac_address()
+    }
+}
+
+impl Device for DriverRef {
+    type RxToken<'a> = TapRxToken<'a>;
+    type TxToken<'a> = GemTxToken<'a>;
+
+    fn receive(&mut self, timestamp: Instant) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
+        self.driver_mut().receive(timestamp)
+    }
+
+    fn transmit(&mut self, timestamp: Instant) -> Option<Self::TxToken<'_>> {
+        self.driver_mut().transmit(timestamp)
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        self.driver().capabilities()
+    }
+}
+
+impl HandleInterrupt for DriverRef {
+    fn handle_interrupt(&mut self) {
+        self.driver_mut().handle_interrupt();
+    }
+}
+
+impl GetNetDeviceMeta for DriverRef {
+    type Error = <ManagedDriver as GetNetDeviceMeta>::Error;
+
+    fn get_mac_address(&mut self) -> Result<MacAddress, Self::Error> {
+        self.driver_mut().get_mac_address()
+    }
+}
@@ -0,0 +1,78 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+// Runs the driver as a Linux userspace driver, answering ARP and ICMP echo like the `ping` PD.
+//
+// The GEM has to be bound to uio_pdrv_genirq, and DMA memory provided by u-dma-buf.
+
+use eth_driver_core::Driver;
+use log::{info, LevelFilter, Log, Metadata, Record};
+use smoltcp::{
+    iface::{Config, Interface, SocketSet},
+    time::Instant,
+    wire::{EthernetAddress, IpCidr},
+};
+use std::{env, io, process, time::Duration};
+
+mod uio;
+
+use uio::UioPlatform;
+
+struct StderrLogger;
+
+impl Log for StderrLogger {
+    fn enabled(&self, _metadata: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        eprintln!("{}: {}", record.level(), record.args());
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: StderrLogger = StderrLogger;
+
+fn main() -> io::Result<()> {
+    log::set_logger(&LOGGER).unwrap();
+    log::set_max_level(LevelFilter::Info);
+
+    let args: Vec<String> = env::args().collect();
+    let [_, uio, udmabuf, cidr] = &args[..] else {
+        eprintln!(
+            "usage: {} <uio device> <u-dma-buf device> <ip/prefix>",
+            args[0]
+        );
+        process::exit(1);
+    };
+    let Ok(cidr) = cidr.parse::<IpCidr>() else {
+        eprintln!("invalid address: {cidr}");
+        process::exit(1);
+    };
+
+    let platform = UioPlatform::open(uio, udmabuf)?;
+    let mut dev = Driver::from_platform(&platform)
+        .map_err(|err| io::Error::other(format!("failed to initialize driver: {err:?}")))?;
+
+    let mac_address = EthernetAddress(dev.mac_address());
+    let mut iface = Interface::new(Config::new(mac_address.into()), &mut dev, Instant::now());
+    iface.update_ip_addrs(|ip_addrs| {
+        ip_addrs.push(cidr).unwrap();
+    });
+    let mut sockets = SocketSet::new(vec![]);
+    info!("Serving {cidr} on {uio}");
+
+    loop {
+        let timestamp = Instant::now();
+        iface.poll(timestamp, &mut dev, &mut sockets);
+        let timeout = iface.poll_delay(timestamp, &sockets).map(Duration::from);
+        if platform.uio.wait_irq(timeout)? {
+            dev.ack_interrupts();
+            platform.uio.enable_irq()?;
+        }
+    }
+}
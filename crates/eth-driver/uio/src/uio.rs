@@ -0,0 +1,177 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+use eth_driver_core::{DmaDef, Platform};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::os::fd::AsRawFd;
+use std::os::unix::fs::OpenOptionsExt;
+use std::ptr::{self, NonNull};
+use std::time::Duration;
+
+// The first DESC_REGION bytes of the u-dma-buf hold the descriptor rings, the rest the buffers
+const DESC_REGION: usize = 0x1000;
+
+fn read_sysfs(path: &str) -> io::Result<usize> {
+    let contents = fs::read_to_string(path)?;
+    let contents = contents.trim();
+    let parsed = match contents.strip_prefix("0x") {
+        Some(hex) => usize::from_str_radix(hex, 16),
+        None => contents.parse(),
+    };
+    parsed.map_err(|err| io::Error::new(io::ErrorKind::InvalidData, format!("{path}: {err}")))
+}
+
+fn open_rw(path: &str, flags: i32) -> io::Result<File> {
+    OpenOptions::new()
+        .read(true)
+        .write(true)
+        .custom_flags(flags)
+        .open(path)
+}
+
+struct Mapping {
+    ptr: NonNull<()>,
+    len: usize,
+}
+
+impl Mapping {
+    fn new(file: &File, offset: usize, len: usize) -> io::Result<Self> {
+        let ptr = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED,
+                file.as_raw_fd(),
+                offset as libc::off_t,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self {
+            ptr: NonNull::new(ptr.cast()).unwrap(),
+            len,
+        })
+    }
+}
+
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        unsafe { libc::munmap(self.ptr.as_ptr().cast(), self.len) };
+    }
+}
+
+/// The GEM bound to `uio_pdrv_genirq`. Map 0 must be its register block.
+pub struct Uio {
+    file: File,
+    regs: Mapping,
+}
+
+impl Uio {
+    /// `name` is the device name, e.g. `uio0`
+    pub fn open(name: &str) -> io::Result<Self> {
+        let file = open_rw(&format!("/dev/{name}"), 0)?;
+        let size = read_sysfs(&format!("/sys/class/uio/{name}/maps/map0/size"))?;
+        let regs = Mapping::new(&file, 0, size)?;
+        let uio = Self { file, regs };
+        uio.enable_irq()?;
+        Ok(uio)
+    }
+
+    /// Unmask the interrupt. The kernel masks it again each time it fires.
+    pub fn enable_irq(&self) -> io::Result<()> {
+        (&self.file).write_all(&1u32.to_ne_bytes())
+    }
+
+    /// Wait for the interrupt for at most `timeout`. Returns whether it fired.
+    pub fn wait_irq(&self, timeout: Option<Duration>) -> io::Result<bool> {
+        let mut fds = libc::pollfd {
+            fd: self.file.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let timeout = timeout.map_or(-1, |timeout| {
+            timeout.as_millis().try_into().unwrap_or(i32::MAX)
+        });
+        match unsafe { libc::poll(&mut fds, 1, timeout) } {
+            ..=-1 => Err(io::Error::last_os_error()),
+            0 => Ok(false),
+            _ => {
+                // Total interrupt count, not needed
+                let mut count = [0; 4];
+                (&self.file).read_exact(&mut count)?;
+                Ok(true)
+            }
+        }
+    }
+}
+
+/// Physically contiguous memory from the `u-dma-buf` driver
+pub struct UdmaBuf {
+    mapping: Mapping,
+    paddr: usize,
+}
+
+impl UdmaBuf {
+    /// `name` is the device name, e.g. `udmabuf0`
+    pub fn open(name: &str) -> io::Result<Self> {
+        // O_SYNC gives an uncached mapping
+        let file = open_rw(&format!("/dev/{name}"), libc::O_SYNC)?;
+        let size = read_sysfs(&format!("/sys/class/u-dma-buf/{name}/size"))?;
+        let paddr = read_sysfs(&format!("/sys/class/u-dma-buf/{name}/phys_addr"))?;
+        if size <= DESC_REGION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{name} is too small"),
+            ));
+        }
+        Ok(Self {
+            mapping: Mapping::new(&file, 0, size)?,
+            paddr,
+        })
+    }
+
+    fn dma_def(&self, offset: usize, size: usize) -> DmaDef {
+        unsafe {
+            DmaDef {
+                vaddr: self.mapping.ptr.byte_add(offset),
+                paddr: NonNull::new_unchecked((self.paddr + offset) as *mut ()),
+                size,
+            }
+        }
+    }
+}
+
+pub struct UioPlatform {
+    pub uio: Uio,
+    dma: UdmaBuf,
+}
+
+impl UioPlatform {
+    pub fn open(uio: &str, udmabuf: &str) -> io::Result<Self> {
+        Ok(Self {
+            uio: Uio::open(uio)?,
+            dma: UdmaBuf::open(udmabuf)?,
+        })
+    }
+}
+
+impl Platform for UioPlatform {
+    fn register_block(&self) -> *mut () {
+        self.uio.regs.ptr.as_ptr()
+    }
+
+    fn desc_dma(&self) -> DmaDef {
+        self.dma.dma_def(0, DESC_REGION)
+    }
+
+    fn buf_dma(&self) -> DmaDef {
+        self.dma
+            .dma_def(DESC_REGION, self.dma.mapping.len - DESC_REGION)
+    }
+}
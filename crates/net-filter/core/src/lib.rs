@@ -0,0 +1,216 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+#![no_std]
+
+extern crate alloc;
+
+// The rules net-filter checks received frames against, apart from seL4 and the rings so that
+// the tests run on the host. The first rule that matches decides what happens to a frame, and
+// frames no rule matches get the default action.
+//
+// Each rule's hits are counted in `Hits`, which net-filter serves on its stats channel. The
+// rules themselves can be loaded at boot, see `table`.
+//
+// Rules only look at the headers of the frame itself: IPv6 extension headers aren't followed,
+// so a packet with them has their type as its protocol, and IPv4 fragments other than the first
+// have no ports. A rule asking about a header the frame doesn't have doesn't match it.
+
+use core::ops::RangeInclusive;
+use core::sync::atomic::{AtomicU64, Ordering};
+use net_filter_interface::MAX_RULES;
+use smoltcp::wire::{
+    EthernetFrame, EthernetProtocol, IpAddress, IpCidr, IpProtocol, Ipv4Packet, Ipv6Packet,
+    TcpPacket, UdpPacket,
+};
+
+pub mod table;
+#[cfg(test)]
+mod tests;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Accept,
+    Drop,
+}
+
+/// Matches frames with all of the fields it sets
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rule {
+    pub action: Action,
+    pub ethertype: Option<EthernetProtocol>,
+    pub src: Option<IpCidr>,
+    pub dst: Option<IpCidr>,
+    /// IPv4 protocol or IPv6 next header
+    pub protocol: Option<IpProtocol>,
+    /// TCP or UDP
+    pub src_ports: Option<RangeInclusive<u16>>,
+    pub dst_ports: Option<RangeInclusive<u16>>,
+    /// TCP, whether the segment opens a connection: SYN set and ACK clear
+    pub tcp_syn: Option<bool>,
+    /// ICMP or ICMPv6
+    pub icmp_type: Option<u8>,
+}
+
+impl Rule {
+    /// Accepts everything, for filling in the fields a rule doesn't set
+    pub const ACCEPT: Self = Self {
+        action: Action::Accept,
+        ethertype: None,
+        src: None,
+        dst: None,
+        protocol: None,
+        src_ports: None,
+        dst_ports: None,
+        tcp_syn: None,
+        icmp_type: None,
+    };
+
+    fn matches(&self, headers: &Headers) -> bool {
+        field(&self.ethertype, Some(headers.ethertype), |want, have| {
+            *want == have
+        }) && field(&self.src, headers.src, |cidr, addr| {
+            cidr.contains_addr(&addr)
+        }) && field(&self.dst, headers.dst, |cidr, addr| {
+            cidr.contains_addr(&addr)
+        }) && field(&self.protocol, headers.protocol, |want, have| *want == have)
+            && field(&self.src_ports, headers.ports, |ports, (src, _)| {
+                ports.contains(&src)
+            })
+            && field(&self.dst_ports, headers.ports, |ports, (_, dst)| {
+                ports.contains(&dst)
+            })
+            && field(&self.tcp_syn, headers.tcp_syn, |want, have| *want == have)
+            && field(&self.icmp_type, headers.icmp_type, |want, have| {
+                *want == have
+            })
+    }
+}
+
+fn field<T, U>(want: &Option<T>, have: Option<U>, matches: impl FnOnce(&T, U) -> bool) -> bool {
+    match want {
+        None => true,
+        Some(want) => have.is_some_and(|have| matches(want, have)),
+    }
+}
+
+/// What the rules are checked against
+#[derive(Debug)]
+pub struct Headers {
+    ethertype: EthernetProtocol,
+    src: Option<IpAddress>,
+    dst: Option<IpAddress>,
+    protocol: Option<IpProtocol>,
+    ports: Option<(u16, u16)>,
+    tcp_syn: Option<bool>,
+    icmp_type: Option<u8>,
+}
+
+impl Headers {
+    fn parse(frame: &[u8]) -> Option<Self> {
+        let frame = EthernetFrame::new_checked(frame).ok()?;
+        let mut headers = Self {
+            ethertype: frame.ethertype(),
+            src: None,
+            dst: None,
+            protocol: None,
+            ports: None,
+            tcp_syn: None,
+            icmp_type: None,
+        };
+        let (protocol, payload) = match headers.ethertype {
+            EthernetProtocol::Ipv4 => {
+                let Ok(packet) = Ipv4Packet::new_checked(frame.payload()) else {
+                    return Some(headers);
+                };
+                headers.src = Some(packet.src_addr().into());
+                headers.dst = Some(packet.dst_addr().into());
+                if packet.frag_offset() != 0 {
+                    headers.protocol = Some(packet.next_header());
+                    return Some(headers);
+                }
+                (packet.next_header(), packet.payload())
+            }
+            EthernetProtocol::Ipv6 => {
+                let Ok(packet) = Ipv6Packet::new_checked(frame.payload()) else {
+                    return Some(headers);
+                };
+                headers.src = Some(packet.src_addr().into());
+                headers.dst = Some(packet.dst_addr().into());
+                (packet.next_header(), packet.payload())
+            }
+            _ => return Some(headers),
+        };
+        headers.protocol = Some(protocol);
+        match protocol {
+            IpProtocol::Tcp => {
+                if let Ok(packet) = TcpPacket::new_checked(payload) {
+                    headers.ports = Some((packet.src_port(), packet.dst_port()));
+                    headers.tcp_syn = Some(packet.syn() && !packet.ack());
+                }
+            }
+            IpProtocol::Udp => {
+                headers.ports = UdpPacket::new_checked(payload)
+                    .ok()
+                    .map(|packet| (packet.src_port(), packet.dst_port()))
+            }
+            // The type is the first byte of both
+            IpProtocol::Icmp | IpProtocol::Icmpv6 => headers.icmp_type = payload.first().copied(),
+            _ => {}
+        }
+        Some(headers)
+    }
+}
+
+/// What the rules made of a frame
+#[derive(Debug)]
+pub struct Verdict {
+    pub action: Action,
+    /// The index of the rule that matched, `None` when the default action was taken
+    pub rule: Option<usize>,
+    pub headers: Headers,
+}
+
+/// Checks a received frame against `rules`, with `default` for frames none of them match.
+/// `None` for frames too short to have an Ethernet header.
+pub fn check(rules: &[Rule], default: Action, frame: &[u8]) -> Option<Verdict> {
+    let headers = Headers::parse(frame)?;
+    let rule = rules.iter().position(|rule| rule.matches(&headers));
+    Some(Verdict {
+        action: rule.map_or(default, |rule| rules[rule].action),
+        rule,
+        headers,
+    })
+}
+
+/// Frames matched by each rule, and then by none
+pub struct Hits([AtomicU64; MAX_RULES + 1]);
+
+impl Hits {
+    pub const fn new() -> Self {
+        Self([const { AtomicU64::new(0) }; MAX_RULES + 1])
+    }
+
+    /// Counts the frame `verdict` was made of
+    pub fn count(&self, verdict: &Verdict) {
+        self.0[verdict.rule.unwrap_or(MAX_RULES)].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// The counters of the first `rules` rules, followed by the default policy's, as in a
+    /// `net_filter_interface::HITS` reply
+    pub fn to_words(&self, rules: usize) -> impl Iterator<Item = u64> + '_ {
+        self.0[..rules.min(MAX_RULES)]
+            .iter()
+            .chain([&self.0[MAX_RULES]])
+            .map(|hits| hits.load(Ordering::Relaxed))
+    }
+}
+
+impl Default for Hits {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,160 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+use crate::{Action, Rule};
+use alloc::vec::Vec;
+use core::ops::RangeInclusive;
+use log::{info, warn};
+use net_filter_interface::MAX_RULES;
+use ping_core::settings::{lines, ParseError};
+use smoltcp::wire::{EthernetProtocol, IpProtocol};
+
+// The rule table net-filter reads at boot from the `.filter_rules` section of its image, which
+// the Makefile fills in from `FILTER_RULES`. It is checksummed text like ping's network
+// settings (see `ping_core::settings`), with the default policy and the rules in order:
+//
+//   version=1
+//   default=drop
+//   rule=accept ethertype=arp
+//   rule=accept protocol=icmp icmp_type=8
+//   rule=accept protocol=udp src_ports=67 dst_ports=68
+//   rule=accept protocol=tcp tcp_syn=false
+//   rule=accept protocol=udp dst_ports=49152-65535
+//   rule=accept src=10.0.0.0/8 protocol=tcp dst_ports=23
+//   crc32=0123abcd
+//
+// A rule is its action followed by the fields of `Rule` it sets. Ethertypes and protocols are
+// named or numbered, and port ranges are a port or two joined by a dash. Rules in the text
+// replace the built-in ones as a whole. Unlike ping's settings, anything not understood makes
+// the text invalid, since a field left out would have a rule match more.
+
+const VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Table {
+    /// At most `MAX_RULES`
+    pub rules: Vec<Rule>,
+    pub default: Action,
+}
+
+impl Table {
+    /// The table in `region`, up to its first NUL byte, or `defaults` if there is none or it is
+    /// invalid
+    pub fn load(region: &[u8], defaults: Self) -> Self {
+        let len = region.iter().position(|&b| b == 0).unwrap_or(region.len());
+        if len == 0 {
+            info!("No rule table, using the built-in rules");
+            return defaults;
+        }
+        match Self::parse(&region[..len], defaults.clone()) {
+            Ok(table) => {
+                info!("Loaded rule table");
+                table
+            }
+            Err(err) => {
+                warn!("Ignoring rule table, {err}");
+                defaults
+            }
+        }
+    }
+
+    /// The default policy is kept from `defaults` if `text` doesn't set it, and so are the rules
+    /// if it has none
+    pub fn parse(text: &[u8], defaults: Self) -> Result<Self, ParseError> {
+        let mut default = defaults.default;
+        let mut rules = Vec::new();
+        for (line, text) in lines(text, VERSION)? {
+            let bad = |reason| ParseError::BadLine { line, reason };
+            let (key, value) = text.split_once('=').ok_or(bad("expected key=value"))?;
+            match key.trim() {
+                "default" => {
+                    default = action(value.trim()).ok_or(bad("expected accept or drop"))?
+                }
+                "rule" if rules.len() == MAX_RULES => return Err(bad("too many rules")),
+                "rule" => rules.push(rule(value).map_err(bad)?),
+                _ => return Err(bad("unknown setting")),
+            }
+        }
+        if rules.is_empty() {
+            rules = defaults.rules;
+        }
+        Ok(Self { rules, default })
+    }
+}
+
+fn action(text: &str) -> Option<Action> {
+    match text {
+        "accept" => Some(Action::Accept),
+        "drop" => Some(Action::Drop),
+        _ => None,
+    }
+}
+
+fn rule(text: &str) -> Result<Rule, &'static str> {
+    let mut words = text.split_whitespace();
+    let action = words
+        .next()
+        .and_then(action)
+        .ok_or("expected accept or drop")?;
+    let mut rule = Rule {
+        action,
+        ..Rule::ACCEPT
+    };
+    for word in words {
+        let (field, value) = word.split_once('=').ok_or("expected field=value")?;
+        match field {
+            "ethertype" => rule.ethertype = Some(ethertype(value).ok_or("bad ethertype")?),
+            "src" => rule.src = Some(value.parse().map_err(|_| "expected an address/prefix")?),
+            "dst" => rule.dst = Some(value.parse().map_err(|_| "expected an address/prefix")?),
+            "protocol" => rule.protocol = Some(protocol(value).ok_or("bad protocol")?),
+            "src_ports" => rule.src_ports = Some(ports(value).ok_or("bad port range")?),
+            "dst_ports" => rule.dst_ports = Some(ports(value).ok_or("bad port range")?),
+            "tcp_syn" => {
+                rule.tcp_syn = Some(match value {
+                    "true" => true,
+                    "false" => false,
+                    _ => return Err("expected true or false"),
+                })
+            }
+            "icmp_type" => rule.icmp_type = Some(value.parse().map_err(|_| "bad ICMP type")?),
+            _ => return Err("unknown field"),
+        }
+    }
+    Ok(rule)
+}
+
+fn number<T: TryFrom<u32>>(text: &str) -> Option<T> {
+    let n = match text.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+        None => text.parse().ok()?,
+    };
+    n.try_into().ok()
+}
+
+fn ethertype(text: &str) -> Option<EthernetProtocol> {
+    match text {
+        "arp" => Some(EthernetProtocol::Arp),
+        "ipv4" => Some(EthernetProtocol::Ipv4),
+        "ipv6" => Some(EthernetProtocol::Ipv6),
+        _ => number::<u16>(text).map(EthernetProtocol::from),
+    }
+}
+
+fn protocol(text: &str) -> Option<IpProtocol> {
+    match text {
+        "icmp" => Some(IpProtocol::Icmp),
+        "tcp" => Some(IpProtocol::Tcp),
+        "udp" => Some(IpProtocol::Udp),
+        "icmpv6" => Some(IpProtocol::Icmpv6),
+        _ => number::<u8>(text).map(IpProtocol::from),
+    }
+}
+
+fn ports(text: &str) -> Option<RangeInclusive<u16>> {
+    let (start, end) = text.split_once('-').unwrap_or((text, text));
+    let (start, end) = (start.parse().ok()?, end.parse().ok()?);
+    (start <= end).then_some(start..=end)
+}
@@ -0,0 +1,366 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+use super::*;
+use crate::table::Table;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use ping_core::settings::{crc32, ParseError};
+use smoltcp::wire::{Ipv4Address, Ipv4Cidr};
+
+const SRC: [u8; 4] = [192, 168, 1, 2];
+const DST: [u8; 4] = [192, 168, 1, 10];
+
+fn ipv4(protocol: u8, payload: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::new();
+    // Destination and source MACs, then the ethertype
+    frame.extend_from_slice(&[0x02, 0, 0, 0, 0, 1, 0x02, 0, 0, 0, 0, 2, 0x08, 0x00]);
+    let total_len = (20 + payload.len()) as u16;
+    frame.extend_from_slice(&[0x45, 0]);
+    frame.extend_from_slice(&total_len.to_be_bytes());
+    frame.extend_from_slice(&[0, 0, 0, 0, 64, protocol, 0, 0]);
+    frame.extend_from_slice(&SRC);
+    frame.extend_from_slice(&DST);
+    frame.extend_from_slice(payload);
+    frame
+}
+
+fn udp(src_port: u16, dst_port: u16) -> Vec<u8> {
+    let mut datagram = Vec::new();
+    datagram.extend_from_slice(&src_port.to_be_bytes());
+    datagram.extend_from_slice(&dst_port.to_be_bytes());
+    datagram.extend_from_slice(&[0, 8, 0, 0]);
+    ipv4(17, &datagram)
+}
+
+const SYN: u8 = 0x02;
+const ACK: u8 = 0x10;
+
+fn tcp_flags(src_port: u16, dst_port: u16, flags: u8) -> Vec<u8> {
+    let mut segment = Vec::new();
+    segment.extend_from_slice(&src_port.to_be_bytes());
+    segment.extend_from_slice(&dst_port.to_be_bytes());
+    // Sequence and acknowledgement numbers, a 20 byte header with `flags`, then the window,
+    // checksum and urgent pointer
+    segment.extend_from_slice(&[0; 8]);
+    segment.extend_from_slice(&[0x50, flags, 0xff, 0xff, 0, 0, 0, 0]);
+    ipv4(6, &segment)
+}
+
+// Opening a connection
+fn tcp(src_port: u16, dst_port: u16) -> Vec<u8> {
+    tcp_flags(src_port, dst_port, SYN)
+}
+
+#[test]
+fn parse_udp() {
+    let headers = Headers::parse(&udp(123, 40000)).unwrap();
+    assert_eq!(headers.ethertype, EthernetProtocol::Ipv4);
+    assert_eq!(headers.src, Some(IpAddress::v4(192, 168, 1, 2)));
+    assert_eq!(headers.dst, Some(IpAddress::v4(192, 168, 1, 10)));
+    assert_eq!(headers.protocol, Some(IpProtocol::Udp));
+    assert_eq!(headers.ports, Some((123, 40000)));
+    assert_eq!(headers.icmp_type, None);
+}
+
+#[test]
+fn parse_icmp() {
+    let headers = Headers::parse(&ipv4(1, &[8, 0, 0, 0, 0, 0, 0, 0])).unwrap();
+    assert_eq!(headers.protocol, Some(IpProtocol::Icmp));
+    assert_eq!(headers.ports, None);
+    assert_eq!(headers.icmp_type, Some(8));
+}
+
+#[test]
+fn parse_truncated() {
+    assert!(Headers::parse(&[0; 10]).is_none());
+
+    // An Ethernet header alone still has its ethertype checked
+    let frame = udp(123, 40000);
+    let headers = Headers::parse(&frame[..14]).unwrap();
+    assert_eq!(headers.ethertype, EthernetProtocol::Ipv4);
+    assert_eq!(headers.src, None);
+
+    // A TCP header cut short has no ports
+    let mut frame = tcp(1234, 80);
+    frame.truncate(frame.len() - 4);
+    frame[17] -= 4;
+    let headers = Headers::parse(&frame).unwrap();
+    assert_eq!(headers.protocol, Some(IpProtocol::Tcp));
+    assert_eq!(headers.ports, None);
+}
+
+#[test]
+fn parse_later_fragment() {
+    let mut frame = udp(123, 40000);
+    // A fragment offset of 8 bytes
+    frame[21] = 1;
+    let headers = Headers::parse(&frame).unwrap();
+    assert_eq!(headers.protocol, Some(IpProtocol::Udp));
+    assert_eq!(headers.ports, None);
+}
+
+#[test]
+fn rule_matches_every_field_it_sets() {
+    let rule = Rule {
+        src: Some(IpCidr::Ipv4(Ipv4Cidr::new(
+            Ipv4Address::new(192, 168, 0, 0),
+            16,
+        ))),
+        protocol: Some(IpProtocol::Tcp),
+        dst_ports: Some(20..=80),
+        ..Rule::ACCEPT
+    };
+    let matches = |frame: Vec<u8>| rule.matches(&Headers::parse(&frame).unwrap());
+
+    assert!(matches(tcp(1234, 80)));
+    assert!(matches(tcp(1234, 20)));
+    assert!(!matches(tcp(1234, 81)));
+    assert!(!matches(udp(1234, 80)));
+    // Nothing to check the ports of
+    assert!(!matches(ipv4(6, &[])));
+
+    let mut other_network = tcp(1234, 80);
+    other_network[26] = 10;
+    assert!(!matches(other_network));
+}
+
+#[test]
+fn rule_matches_tcp_syn() {
+    let rule = Rule {
+        protocol: Some(IpProtocol::Tcp),
+        tcp_syn: Some(false),
+        ..Rule::ACCEPT
+    };
+    let matches = |frame: Vec<u8>| rule.matches(&Headers::parse(&frame).unwrap());
+
+    assert!(!matches(tcp(1234, 80)));
+    // The answer to a connection from here, and the rest of the connection
+    assert!(matches(tcp_flags(80, 50000, SYN | ACK)));
+    assert!(matches(tcp_flags(80, 50000, ACK)));
+    // Only TCP has the flag
+    assert_eq!(Headers::parse(&tcp(1234, 80)).unwrap().tcp_syn, Some(true));
+    assert_eq!(Headers::parse(&udp(1234, 80)).unwrap().tcp_syn, None);
+}
+
+#[test]
+fn accept_matches_everything() {
+    for frame in [tcp(1234, 80), udp(1, 2), ipv4(1, &[]), ipv4(47, &[])] {
+        assert!(Rule::ACCEPT.matches(&Headers::parse(&frame).unwrap()));
+    }
+}
+
+const RULES: &[Rule] = &[
+    Rule {
+        action: Action::Drop,
+        protocol: Some(IpProtocol::Udp),
+        src_ports: Some(53..=53),
+        ..Rule::ACCEPT
+    },
+    Rule {
+        protocol: Some(IpProtocol::Udp),
+        ..Rule::ACCEPT
+    },
+    Rule {
+        protocol: Some(IpProtocol::Tcp),
+        dst_ports: Some(80..=80),
+        ..Rule::ACCEPT
+    },
+];
+
+fn verdict(default: Action, frame: &[u8]) -> (Action, Option<usize>) {
+    let verdict = check(RULES, default, frame).unwrap();
+    (verdict.action, verdict.rule)
+}
+
+#[test]
+fn check_uses_the_first_matching_rule() {
+    // Rule 1 would accept it too
+    assert_eq!(
+        verdict(Action::Accept, &udp(53, 40000)),
+        (Action::Drop, Some(0))
+    );
+    assert_eq!(
+        verdict(Action::Drop, &udp(123, 40000)),
+        (Action::Accept, Some(1))
+    );
+    assert_eq!(
+        verdict(Action::Drop, &tcp(1234, 80)),
+        (Action::Accept, Some(2))
+    );
+}
+
+#[test]
+fn check_falls_back_to_the_default() {
+    for default in [Action::Accept, Action::Drop] {
+        assert_eq!(verdict(default, &tcp(1234, 8080)), (default, None));
+        assert_eq!(verdict(default, &ipv4(1, &[8, 0])), (default, None));
+    }
+    assert_eq!(
+        check(&[], Action::Accept, &udp(53, 40000)).unwrap().action,
+        Action::Accept
+    );
+    assert!(check(RULES, Action::Accept, &[0; 10]).is_none());
+}
+
+#[test]
+fn hits_layout() {
+    let hits = Hits::new();
+    for frame in [
+        udp(53, 40000),
+        udp(53, 40001),
+        tcp(1234, 80),
+        tcp(1234, 8080),
+        tcp(1234, 8081),
+        tcp(1234, 8082),
+    ] {
+        hits.count(&check(RULES, Action::Drop, &frame).unwrap());
+    }
+    // Each rule's in order, then the default's
+    let words: Vec<u64> = hits.to_words(RULES.len()).collect();
+    assert_eq!(words, [2, 0, 1, 3]);
+    assert_eq!(
+        net_filter_interface::split_hits(&words),
+        Some((&[2, 0, 1][..], 3))
+    );
+
+    // The default's counter doesn't follow the last rule's, whatever the number of rules
+    let last = Verdict {
+        rule: Some(MAX_RULES - 1),
+        ..check(RULES, Action::Drop, &tcp(1234, 80)).unwrap()
+    };
+    hits.count(&last);
+    let words: Vec<u64> = hits.to_words(MAX_RULES).collect();
+    assert_eq!(words.len(), MAX_RULES + 1);
+    assert_eq!(words[MAX_RULES - 1], 1);
+    assert_eq!(words[MAX_RULES], 3);
+    assert_eq!(hits.to_words(0).collect::<Vec<_>>(), [3]);
+    assert_eq!(net_filter_interface::split_hits(&[]), None);
+}
+
+fn with_crc(body: &str) -> String {
+    format!("{body}crc32={:08x}\n", crc32(body.as_bytes()))
+}
+
+fn table(body: &str) -> Result<Table, ParseError> {
+    Table::parse(with_crc(body).as_bytes(), defaults())
+}
+
+fn defaults() -> Table {
+    Table {
+        rules: RULES.into(),
+        default: Action::Drop,
+    }
+}
+
+#[test]
+fn table_with_every_field() {
+    let table = table(
+        "version=1\n\
+         default=accept\n\
+         # Comments are skipped\n\
+         rule=accept ethertype=arp\n\
+         rule=drop ethertype=0x88cc\n\
+         rule=accept src=10.0.0.0/8 dst=fe80::/10 protocol=tcp dst_ports=23 tcp_syn=true\n\
+         rule=accept protocol=17 src_ports=67 dst_ports=49152-65535\n\
+         rule=accept protocol=icmpv6 icmp_type=135\n",
+    )
+    .unwrap();
+    assert_eq!(table.default, Action::Accept);
+    assert_eq!(
+        table.rules,
+        [
+            Rule {
+                ethertype: Some(EthernetProtocol::Arp),
+                ..Rule::ACCEPT
+            },
+            Rule {
+                action: Action::Drop,
+                ethertype: Some(EthernetProtocol::Unknown(0x88cc)),
+                ..Rule::ACCEPT
+            },
+            Rule {
+                src: Some("10.0.0.0/8".parse().unwrap()),
+                dst: Some("fe80::/10".parse().unwrap()),
+                protocol: Some(IpProtocol::Tcp),
+                dst_ports: Some(23..=23),
+                tcp_syn: Some(true),
+                ..Rule::ACCEPT
+            },
+            Rule {
+                protocol: Some(IpProtocol::Udp),
+                src_ports: Some(67..=67),
+                dst_ports: Some(49152..=65535),
+                ..Rule::ACCEPT
+            },
+            Rule {
+                protocol: Some(IpProtocol::Icmpv6),
+                icmp_type: Some(135),
+                ..Rule::ACCEPT
+            },
+        ]
+    );
+}
+
+#[test]
+fn table_keeps_defaults() {
+    assert_eq!(table("version=1\n"), Ok(defaults()));
+    // Only the default policy
+    let table = table("version=1\ndefault=accept\n").unwrap();
+    assert_eq!(table.rules, RULES);
+    assert_eq!(table.default, Action::Accept);
+}
+
+#[test]
+fn table_errors() {
+    for (body, line) in [
+        ("version=1\nrule=allow protocol=tcp\n", 2),
+        (
+            "version=1\nrule=accept protocol=tcp\nrule=accept proto=tcp\n",
+            3,
+        ),
+        ("version=1\nrule=accept protocol=sctp\n", 2),
+        ("version=1\nrule=accept dst_ports=80-20\n", 2),
+        ("version=1\nrule=accept dst_ports=65536\n", 2),
+        ("version=1\nrule=accept src=10.0.0.0\n", 2),
+        ("version=1\nrule=accept tcp_syn=yes\n", 2),
+        ("version=1\ndefault=reject\n", 2),
+        ("version=1\npolicy=drop\n", 2),
+    ] {
+        assert!(
+            matches!(table(body), Err(ParseError::BadLine { line: l, .. }) if l == line),
+            "{body}"
+        );
+    }
+
+    let too_many = format!("version=1\n{}", "rule=drop\n".repeat(MAX_RULES + 1));
+    assert!(matches!(
+        table(&too_many),
+        Err(ParseError::BadLine { line, .. }) if line == MAX_RULES + 2
+    ));
+    assert_eq!(table("version=2\n"), Err(ParseError::UnsupportedVersion));
+}
+
+#[test]
+fn table_load_falls_back_to_defaults() {
+    assert_eq!(Table::load(&[0; 64], defaults()), defaults());
+    assert_eq!(Table::load(b"garbage\0", defaults()), defaults());
+
+    let mut region = with_crc("version=1\nrule=drop\n").into_bytes();
+    region.resize(4096, 0);
+    assert_eq!(
+        Table::load(&region, defaults()),
+        Table {
+            rules: vec![Rule {
+                action: Action::Drop,
+                ..Rule::ACCEPT
+            }],
+            default: Action::Drop,
+        }
+    );
+}
@@ -0,0 +1,26 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+#![no_std]
+
+// Protected calls on the packet filter's stats channel. The message label selects the
+// operation, replies are labelled `OK` or `ERROR`.
+
+/// Most rules the filter can have, so that their counters fit in the message registers
+pub const MAX_RULES: usize = 32;
+
+/// Replies with how many received frames each rule matched, in order, followed by how many
+/// matched none and got the default policy. Counters run from boot.
+pub const HITS: u64 = 1;
+
+/// A `HITS` reply's counters, split into the rules' and the default policy's
+pub fn split_hits(words: &[u64]) -> Option<(&[u64], u64)> {
+    let (default, rules) = words.split_last()?;
+    Some((rules, *default))
+}
+
+pub const OK: u64 = 0;
+pub const ERROR: u64 = 1;
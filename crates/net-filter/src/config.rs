@@ -0,0 +1,177 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+pub mod channels {
+    use sel4_microkit::Channel;
+
+    pub const DRIVER: Channel = Channel::new(0);
+    pub const CLIENT: Channel = Channel::new(1);
+    /// See `net_filter_interface`
+    pub const STATS: Channel = Channel::new(2);
+}
+
+pub mod sizes {
+    /// Shared with the driver
+    #[cfg(not(feature = "jumbo"))]
+    pub const DRIVER_DMA: usize = 0x20_0000;
+    #[cfg(feature = "jumbo")]
+    pub const DRIVER_DMA: usize = 0x40_0000;
+    /// Shared with the client
+    #[cfg(not(feature = "jumbo"))]
+    pub const CLIENT_DMA: usize = 0x20_0000;
+    #[cfg(feature = "jumbo")]
+    pub const CLIENT_DMA: usize = 0x40_0000;
+    /// The size of the `.filter_rules` section, which support/net-config.py reads from the image
+    pub const FILTER_RULES: usize = 0x1000;
+}
+
+pub mod rules {
+    use core::ops::RangeInclusive;
+    use net_filter_core::{Action, Rule};
+    use ping_core::{dns, sntp};
+    use smoltcp::wire::{
+        EthernetProtocol, IpCidr, IpProtocol, Ipv4Address, Ipv4Cidr, Ipv6Address, Ipv6Cidr,
+    };
+
+    const ICMPV4_ECHO_REQUEST: u8 = 8;
+    const ICMPV4_ECHO_REPLY: u8 = 0;
+    const ICMPV4_DEST_UNREACHABLE: u8 = 3;
+
+    // Where ping's own clients send from, and socket clients should too for replies to get in
+    const EPHEMERAL_PORTS: RangeInclusive<u16> = 49152..=65535;
+
+    // Networks the shell may be reached from
+    const PRIVATE_NETWORKS: [IpCidr; 5] = [
+        IpCidr::Ipv4(Ipv4Cidr::new(Ipv4Address::new(10, 0, 0, 0), 8)),
+        IpCidr::Ipv4(Ipv4Cidr::new(Ipv4Address::new(172, 16, 0, 0), 12)),
+        IpCidr::Ipv4(Ipv4Cidr::new(Ipv4Address::new(192, 168, 0, 0), 16)),
+        // Link-local and unique local
+        IpCidr::Ipv6(Ipv6Cidr::new(
+            Ipv6Address::new(0xfe80, 0, 0, 0, 0, 0, 0, 0),
+            10,
+        )),
+        IpCidr::Ipv6(Ipv6Cidr::new(
+            Ipv6Address::new(0xfc00, 0, 0, 0, 0, 0, 0, 0),
+            7,
+        )),
+    ];
+
+    const fn tcp_to(port: u16) -> Rule {
+        Rule {
+            protocol: Some(IpProtocol::Tcp),
+            dst_ports: Some(port..=port),
+            ..Rule::ACCEPT
+        }
+    }
+
+    const fn udp_to(port: u16) -> Rule {
+        Rule {
+            protocol: Some(IpProtocol::Udp),
+            dst_ports: Some(port..=port),
+            ..Rule::ACCEPT
+        }
+    }
+
+    // Answers to ping's own requests, from the server's port to ping's
+    const fn udp_reply(server: u16, client: u16) -> Rule {
+        Rule {
+            protocol: Some(IpProtocol::Udp),
+            src_ports: Some(server..=server),
+            dst_ports: Some(client..=client),
+            ..Rule::ACCEPT
+        }
+    }
+
+    const fn shell_from(network: IpCidr) -> Rule {
+        Rule {
+            src: Some(network),
+            ..tcp_to(23)
+        }
+    }
+
+    // The built-in table, used unless the image has another, see `crate::table`
+
+    /// What frames no rule matches get
+    pub const DEFAULT: Action = Action::Drop;
+
+    /// Checked in order, the first that matches decides. At most
+    /// `net_filter_interface::MAX_RULES`.
+    pub const RULES: &[Rule] = &[
+        Rule {
+            ethertype: Some(EthernetProtocol::Arp),
+            ..Rule::ACCEPT
+        },
+        // Neighbor discovery and router advertisements, as well as pings
+        Rule {
+            protocol: Some(IpProtocol::Icmpv6),
+            ..Rule::ACCEPT
+        },
+        Rule {
+            protocol: Some(IpProtocol::Icmp),
+            icmp_type: Some(ICMPV4_ECHO_REQUEST),
+            ..Rule::ACCEPT
+        },
+        Rule {
+            protocol: Some(IpProtocol::Icmp),
+            icmp_type: Some(ICMPV4_ECHO_REPLY),
+            ..Rule::ACCEPT
+        },
+        Rule {
+            protocol: Some(IpProtocol::Icmp),
+            icmp_type: Some(ICMPV4_DEST_UNREACHABLE),
+            ..Rule::ACCEPT
+        },
+        // DHCP, SNTP and DNS. The servers are only known at run time, from DHCP, the board's
+        // settings or a lookup, so only the ports are pinned.
+        udp_reply(67, 68),
+        udp_reply(sntp::NTP_PORT, sntp::LOCAL_PORT),
+        udp_reply(dns::PORT, dns::LOCAL_PORT),
+        // Replies to connections and datagrams from ping and its socket clients. Nothing is
+        // kept of what was sent, so instead any TCP segment that doesn't open a connection gets
+        // through, as does UDP to the ephemeral ports. ping resets or ignores those that aren't
+        // for one of its sockets.
+        Rule {
+            protocol: Some(IpProtocol::Tcp),
+            tcp_syn: Some(false),
+            ..Rule::ACCEPT
+        },
+        Rule {
+            protocol: Some(IpProtocol::Udp),
+            dst_ports: Some(EPHEMERAL_PORTS),
+            ..Rule::ACCEPT
+        },
+        // Echo, discard and daytime
+        tcp_to(7),
+        udp_to(7),
+        udp_to(9),
+        tcp_to(13),
+        udp_to(13),
+        // HTTP
+        tcp_to(80),
+        shell_from(PRIVATE_NETWORKS[0]),
+        shell_from(PRIVATE_NETWORKS[1]),
+        shell_from(PRIVATE_NETWORKS[2]),
+        shell_from(PRIVATE_NETWORKS[3]),
+        shell_from(PRIVATE_NETWORKS[4]),
+    ];
+}
+
+pub mod log {
+    use sel4_logging::{LevelFilter, Logger, LoggerBuilder};
+    use sel4_microkit::debug_print;
+
+    const LOG_LEVEL: LevelFilter = {
+        // LevelFilter::Trace
+        // LevelFilter::Debug
+        LevelFilter::Info
+        // LevelFilter::Warn
+    };
+
+    pub static LOGGER: Logger = LoggerBuilder::const_default()
+        .level_filter(LOG_LEVEL)
+        .write(|s| debug_print!("{}", s))
+        .build();
+}
@@ -0,0 +1,86 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+use crate::rules;
+use alloc::vec::Vec;
+use core::convert::Infallible;
+use net_filter_core::table::Table;
+use net_filter_core::Action;
+use sel4_bounce_buffer_allocator::Basic;
+use sel4_driver_interfaces::net::{GetNetDeviceMeta, MacAddress};
+use sel4_driver_interfaces::HandleInterrupt;
+use sel4_shared_ring_buffer_smoltcp::DeviceImpl;
+use smoltcp::{
+    phy::{self, Device, DeviceCapabilities, RxToken},
+    time::Instant,
+};
+
+// The driver's rings as a device for the adapters' `HandlerImpl`, which serves them to the
+// client with received frames the rules drop left out. A frame has to be copied out to be
+// checked, since a token can't be looked at without being consumed. Frames from the client go
+// straight through.
+//
+// The rings are polled on every call into the device, as the handler is only ever notified on
+// the client's channel (see `main`).
+
+pub struct Filter {
+    inner: DeviceImpl<Basic>,
+    mac: [u8; 6],
+    table: Table,
+}
+
+impl Filter {
+    pub fn new(inner: DeviceImpl<Basic>, mac: [u8; 6], table: Table) -> Self {
+        Self { inner, mac, table }
+    }
+}
+
+pub struct FilterRxToken(Vec<u8>);
+
+impl phy::RxToken for FilterRxToken {
+    fn consume<R, F: FnOnce(&mut [u8]) -> R>(mut self, f: F) -> R {
+        f(&mut self.0)
+    }
+}
+
+impl Device for Filter {
+    type RxToken<'a> = FilterRxToken;
+    type TxToken<'a> = <DeviceImpl<Basic> as Device>::TxToken<'a>;
+
+    fn receive(&mut self, timestamp: Instant) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
+        self.inner.poll();
+        loop {
+            let (rx, tx) = self.inner.receive(timestamp)?;
+            let frame = rx.consume(|frame| Vec::from(&*frame));
+            if rules::check(&self.table.rules, self.table.default, &frame) == Action::Accept {
+                return Some((FilterRxToken(frame), tx));
+            }
+        }
+    }
+
+    fn transmit(&mut self, timestamp: Instant) -> Option<Self::TxToken<'_>> {
+        self.inner.poll();
+        self.inner.transmit(timestamp)
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        self.inner.capabilities()
+    }
+}
+
+impl HandleInterrupt for Filter {
+    fn handle_interrupt(&mut self) {
+        self.inner.poll();
+    }
+}
+
+impl GetNetDeviceMeta for Filter {
+    type Error = Infallible;
+
+    fn get_mac_address(&mut self) -> Result<MacAddress, Self::Error> {
+        Ok(MacAddress(self.mac))
+    }
+}
@@ -0,0 +1,201 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+use eth_driver_interface::MTU;
+use log::{error, info, warn};
+use net_filter_interface::{ERROR, HITS, OK};
+use sel4_bounce_buffer_allocator::{Basic, BounceBufferAllocator};
+use sel4_driver_interfaces::net::GetNetDeviceMeta;
+use sel4_externally_shared::{ExternallySharedRef, ExternallySharedRefExt};
+use sel4_microkit::{
+    memory_region_symbol, protection_domain, with_msg_regs_mut, Channel, Handler, Infallible,
+    MessageInfo,
+};
+use sel4_microkit_driver_adapters::net::client::Client as NetClient;
+use sel4_microkit_driver_adapters::net::driver::HandlerImpl as FilterHandlerImpl;
+use sel4_microkit_message::MessageInfoExt;
+use sel4_shared_ring_buffer::{roles::Use, RingBuffers};
+use sel4_shared_ring_buffer_smoltcp::DeviceImpl;
+use smoltcp::phy::DeviceCapabilities;
+
+mod config;
+mod filter;
+mod rules;
+mod table;
+
+use filter::Filter;
+
+// A packet filter between the driver and its client, which looks like the driver to the
+// client. Received frames are checked against the rule table (see `table` and
+// `net_filter_core`), and those it drops never reach the client. The rules' hit counters are
+// served on the stats channel.
+
+#[protection_domain(
+    heap_size = 1024*1024,
+)]
+fn init() -> PacketFilter {
+    config::log::LOGGER.set().unwrap();
+    let Ok(mac) = NetClient::new(config::channels::DRIVER).get_mac_address() else {
+        error!("Driver unavailable, the client has no network");
+        return PacketFilter::NoDriver;
+    };
+    let mac = mac.0;
+    let notify_driver: fn() = || config::channels::DRIVER.notify();
+    let notify_client: fn() = || config::channels::CLIENT.notify();
+
+    let driver = {
+        let dma_region = unsafe {
+            ExternallySharedRef::<'static, _>::new(
+                memory_region_symbol!(driver_dma_vaddr: *mut [u8], n = config::sizes::DRIVER_DMA),
+            )
+        };
+
+        let bounce_buffer_allocator =
+            BounceBufferAllocator::new(Basic::new(dma_region.as_ptr().len()), 1);
+
+        DeviceImpl::new(
+            Default::default(),
+            dma_region,
+            bounce_buffer_allocator,
+            RingBuffers::from_ptrs_using_default_initialization_strategy_for_role(
+                unsafe { ExternallySharedRef::new(memory_region_symbol!(driver_rx_free: *mut _)) },
+                unsafe { ExternallySharedRef::new(memory_region_symbol!(driver_rx_used: *mut _)) },
+                notify_driver,
+            ),
+            RingBuffers::from_ptrs_using_default_initialization_strategy_for_role(
+                unsafe { ExternallySharedRef::new(memory_region_symbol!(driver_tx_free: *mut _)) },
+                unsafe { ExternallySharedRef::new(memory_region_symbol!(driver_tx_used: *mut _)) },
+                notify_driver,
+            ),
+            128,
+            MTU,
+            {
+                let mut caps = DeviceCapabilities::default();
+                caps.max_transmission_unit = MTU;
+                caps
+            },
+        )
+        .unwrap()
+    };
+
+    let client_region = unsafe {
+        ExternallySharedRef::<'static, _>::new(
+            memory_region_symbol!(client_dma_vaddr: *mut [u8], n = config::sizes::CLIENT_DMA),
+        )
+    };
+
+    let table = table::load();
+    for (i, rule) in table.rules.iter().enumerate() {
+        info!("Rule {i}: {rule:?}");
+    }
+    info!("Default: {:?}", table.default);
+    let rules = table.rules.len();
+
+    let inner = FilterHandlerImpl::new(
+        Filter::new(driver, mac, table),
+        client_region,
+        RingBuffers::<'_, Use, fn()>::from_ptrs_using_default_initialization_strategy_for_role(
+            unsafe { ExternallySharedRef::new(memory_region_symbol!(client_rx_free: *mut _)) },
+            unsafe { ExternallySharedRef::new(memory_region_symbol!(client_rx_used: *mut _)) },
+            notify_client,
+        ),
+        RingBuffers::<'_, Use, fn()>::from_ptrs_using_default_initialization_strategy_for_role(
+            unsafe { ExternallySharedRef::new(memory_region_symbol!(client_tx_free: *mut _)) },
+            unsafe { ExternallySharedRef::new(memory_region_symbol!(client_tx_used: *mut _)) },
+            notify_client,
+        ),
+        // Never passed to `inner`, as the driver's channel isn't an IRQ it could ack
+        config::channels::DRIVER,
+        config::channels::CLIENT,
+    );
+
+    info!("Initialized Packet Filter");
+    PacketFilter::Running(HandlerImpl { inner, rules })
+}
+
+enum PacketFilter {
+    Running(HandlerImpl),
+    /// The driver failed to initialize, so the client's calls fail as well
+    NoDriver,
+}
+
+struct HandlerImpl {
+    inner: FilterHandlerImpl<Filter>,
+    // In the table, for the hit counters
+    rules: usize,
+}
+
+impl HandlerImpl {
+    fn hits(&self) -> MessageInfo {
+        let mut count = 0;
+        with_msg_regs_mut(|mrs| {
+            for (mr, hits) in mrs.iter_mut().zip(rules::HITS.to_words(self.rules)) {
+                *mr = hits;
+                count += 1;
+            }
+        });
+        MessageInfo::new(OK, count)
+    }
+}
+
+impl Handler for HandlerImpl {
+    type Error = Infallible;
+
+    fn notified(&mut self, channel: Channel) -> Result<(), Self::Error> {
+        // Either way frames may need moving in both directions, which `inner` does whatever
+        // channel it is given
+        if channel == config::channels::DRIVER || channel == config::channels::CLIENT {
+            self.inner.notified(config::channels::CLIENT)?;
+        }
+        Ok(())
+    }
+
+    fn protected(
+        &mut self,
+        channel: Channel,
+        msg_info: MessageInfo,
+    ) -> Result<MessageInfo, Self::Error> {
+        if channel == config::channels::STATS {
+            return Ok(match msg_info.label() {
+                HITS => self.hits(),
+                label => {
+                    warn!("Unknown stats request: {label}");
+                    MessageInfo::new(ERROR, 0)
+                }
+            });
+        }
+        // The client asking for the MAC address
+        self.inner.protected(channel, msg_info)
+    }
+}
+
+impl Handler for PacketFilter {
+    type Error = Infallible;
+
+    fn notified(&mut self, channel: Channel) -> Result<(), Self::Error> {
+        match self {
+            Self::Running(handler) => handler.notified(channel),
+            Self::NoDriver => Ok(()),
+        }
+    }
+
+    fn protected(
+        &mut self,
+        channel: Channel,
+        msg_info: MessageInfo,
+    ) -> Result<MessageInfo, Self::Error> {
+        match self {
+            Self::Running(handler) => handler.protected(channel, msg_info),
+            Self::NoDriver if channel == config::channels::STATS => Ok(MessageInfo::new(ERROR, 0)),
+            Self::NoDriver => Ok(MessageInfo::send_unspecified_error()),
+        }
+    }
+}
@@ -0,0 +1,30 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+use crate::config;
+use log::debug;
+use net_filter_core::{Action, Hits, Rule};
+use net_filter_interface::MAX_RULES;
+
+// Counts what the rule table makes of received frames. The rules and their counters are in
+// `net_filter_core`.
+
+const _: () = assert!(config::rules::RULES.len() <= MAX_RULES);
+
+pub static HITS: Hits = Hits::new();
+
+/// Checks a received frame against `rules` and counts the hit. Frames too short to have an
+/// Ethernet header are dropped.
+pub fn check(rules: &[Rule], default: Action, frame: &[u8]) -> Action {
+    let Some(verdict) = net_filter_core::check(rules, default, frame) else {
+        return Action::Drop;
+    };
+    HITS.count(&verdict);
+    if verdict.action == Action::Drop {
+        debug!("Dropped by rule {:?}: {:?}", verdict.rule, verdict.headers);
+    }
+    verdict.action
+}
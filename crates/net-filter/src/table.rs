@@ -0,0 +1,36 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+use crate::config;
+use alloc::vec::Vec;
+use core::ptr;
+use net_filter_core::table::Table;
+
+// The rule table read at boot from the `.filter_rules` section of the image, which the Makefile
+// fills in from `FILTER_RULES`, so the rules can change without rebuilding the crates. The
+// section holds the text described in `net_filter_core::table` up to the first NUL byte.
+// Without it the rules in `config::rules` are used.
+
+// All NUL, and so the built-in rules, unless the Makefile packs a table into it
+#[used]
+#[link_section = ".filter_rules"]
+static PACKED: [u8; config::sizes::FILTER_RULES] = [0; config::sizes::FILTER_RULES];
+
+/// The table packed into the image, or the built-in one if there is none or it is invalid
+pub fn load() -> Table {
+    // Volatile so the compiler can't assume the section still holds what it was built with
+    let packed: Vec<u8> = PACKED
+        .iter()
+        .map(|byte| unsafe { ptr::read_volatile(byte) })
+        .collect();
+    Table::load(
+        &packed,
+        Table {
+            rules: config::rules::RULES.into(),
+            default: config::rules::DEFAULT,
+        },
+    )
+}
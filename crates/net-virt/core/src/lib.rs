@@ -0,0 +1,109 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+#![no_std]
+
+// How net-virt shares the driver among its clients, apart from seL4 and the rings so that the
+// tests run on the host.
+
+extern crate alloc;
+
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+
+#[cfg(test)]
+mod tests;
+
+/// The frames on their way between one client and the driver
+#[derive(Debug)]
+pub struct Queues {
+    pub rx: VecDeque<Vec<u8>>,
+    pub tx: VecDeque<Vec<u8>>,
+    /// Frames for the client dropped because `rx` was full
+    pub rx_dropped: u64,
+    /// Frames from the client dropped for not being sent from its MAC address
+    pub tx_spoofed: u64,
+    rx_len: usize,
+}
+
+impl Queues {
+    /// `rx_len` frames are held for the client before more are dropped
+    pub fn new(rx_len: usize) -> Self {
+        Self {
+            rx: VecDeque::new(),
+            tx: VecDeque::new(),
+            rx_dropped: 0,
+            tx_spoofed: 0,
+            rx_len,
+        }
+    }
+
+    /// Queues a frame for the client, or drops it if the client isn't keeping up
+    pub fn deliver(&mut self, frame: &[u8]) {
+        if self.rx.len() == self.rx_len {
+            self.rx_dropped += 1;
+        } else {
+            self.rx.push_back(frame.into());
+        }
+    }
+}
+
+/// Hands a frame from the driver to the client with its destination address, or to every client
+/// for broadcast and multicast. Unicast frames for no client are dropped.
+pub fn demux(clients: &[([u8; 6], &RefCell<Queues>)], frame: &[u8]) {
+    let Some(dst) = frame.get(..6) else {
+        return;
+    };
+    // The group bit covers broadcast as well as multicast
+    if dst[0] & 0x01 != 0 {
+        for (_, queues) in clients {
+            queues.borrow_mut().deliver(frame);
+        }
+    } else if let Some((_, queues)) = clients.iter().find(|(mac, _)| mac[..] == *dst) {
+        queues.borrow_mut().deliver(frame);
+    }
+}
+
+/// Takes the clients' frames for the driver one from each client in turn, so a busy client
+/// can't starve the others. Each round starts one client further on than the last.
+#[derive(Debug, Default)]
+pub struct RoundRobin {
+    next: usize,
+}
+
+impl RoundRobin {
+    /// Hands frames from the clients' `tx` queues to `send` until it refuses one, which stays
+    /// queued, or there are none left. Returns whether any were sent.
+    pub fn transmit(
+        &mut self,
+        clients: &[&RefCell<Queues>],
+        mut send: impl FnMut(&[u8]) -> bool,
+    ) -> bool {
+        let n = clients.len();
+        let mut sent = false;
+        loop {
+            let first = self.next;
+            self.next = (first + 1) % n;
+            let mut round = false;
+            for i in 0..n {
+                let mut queues = clients[(first + i) % n].borrow_mut();
+                let Some(frame) = queues.tx.front() else {
+                    continue;
+                };
+                if !send(frame) {
+                    return sent;
+                }
+                queues.tx.pop_front();
+                round = true;
+                sent = true;
+            }
+            if !round {
+                return sent;
+            }
+        }
+    }
+}
@@ -0,0 +1,137 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+use super::*;
+use alloc::vec;
+
+const RX_LEN: usize = 4;
+const MACS: [[u8; 6]; 3] = [
+    [0x00, 0x0a, 0x35, 0x00, 0x00, 0x01],
+    [0x02, 0x0a, 0x35, 0x00, 0x00, 0x01],
+    [0x06, 0x0a, 0x35, 0x00, 0x00, 0x01],
+];
+
+fn queues() -> [RefCell<Queues>; 3] {
+    [(); 3].map(|_| RefCell::new(Queues::new(RX_LEN)))
+}
+
+fn clients(queues: &[RefCell<Queues>; 3]) -> [([u8; 6], &RefCell<Queues>); 3] {
+    [0, 1, 2].map(|i| (MACS[i], &queues[i]))
+}
+
+fn frame(dst: [u8; 6], tag: u8) -> Vec<u8> {
+    let mut frame = vec![tag; 60];
+    frame[..6].copy_from_slice(&dst);
+    frame
+}
+
+fn received(queues: &RefCell<Queues>) -> Vec<Vec<u8>> {
+    queues.borrow_mut().rx.drain(..).collect()
+}
+
+#[test]
+fn demux_unicast() {
+    let queues = queues();
+    let clients = clients(&queues);
+    demux(&clients, &frame(MACS[1], 1));
+    demux(&clients, &frame(MACS[2], 2));
+    demux(&clients, &frame(MACS[1], 3));
+
+    assert!(received(&queues[0]).is_empty());
+    assert_eq!(received(&queues[1]), [frame(MACS[1], 1), frame(MACS[1], 3)]);
+    assert_eq!(received(&queues[2]), [frame(MACS[2], 2)]);
+}
+
+#[test]
+fn demux_group() {
+    let queues = queues();
+    let clients = clients(&queues);
+    let broadcast = frame([0xff; 6], 1);
+    let multicast = frame([0x33, 0x33, 0, 0, 0, 1], 2);
+    demux(&clients, &broadcast);
+    demux(&clients, &multicast);
+    for queues in &queues {
+        assert_eq!(received(queues), [broadcast.clone(), multicast.clone()]);
+    }
+}
+
+#[test]
+fn demux_drops_strays() {
+    let queues = queues();
+    let clients = clients(&queues);
+    // For no client, and too short to have a destination
+    demux(&clients, &frame([0x00, 0x0a, 0x35, 0x00, 0x00, 0x02], 1));
+    demux(&clients, &MACS[0][..5]);
+    for queues in &queues {
+        assert!(received(queues).is_empty());
+        assert_eq!(queues.borrow().rx_dropped, 0);
+    }
+}
+
+#[test]
+fn deliver_drops_when_full() {
+    let queues = queues();
+    let clients = clients(&queues);
+    for tag in 0..RX_LEN as u8 + 2 {
+        demux(&clients, &frame(MACS[0], tag));
+    }
+    assert_eq!(queues[0].borrow().rx_dropped, 2);
+    // The oldest are kept
+    let expected: Vec<_> = (0..RX_LEN as u8).map(|tag| frame(MACS[0], tag)).collect();
+    assert_eq!(received(&queues[0]), expected);
+
+    demux(&clients, &frame(MACS[0], 9));
+    assert_eq!(received(&queues[0]), [frame(MACS[0], 9)]);
+}
+
+fn queue_tx(queues: &RefCell<Queues>, tags: &[u8]) {
+    let mut queues = queues.borrow_mut();
+    queues.tx.extend(tags.iter().map(|&tag| vec![tag]));
+}
+
+fn transmit(robin: &mut RoundRobin, queues: &[RefCell<Queues>], limit: usize) -> Vec<u8> {
+    let clients: Vec<_> = queues.iter().collect();
+    let mut sent = Vec::new();
+    robin.transmit(&clients, |frame| {
+        if sent.len() == limit {
+            return false;
+        }
+        sent.push(frame[0]);
+        true
+    });
+    sent
+}
+
+#[test]
+fn transmit_takes_turns() {
+    let queues = queues();
+    queue_tx(&queues[0], &[1, 2, 3, 4]);
+    queue_tx(&queues[1], &[11]);
+    queue_tx(&queues[2], &[21, 22]);
+
+    // One from each client a round, with each round starting one client further on
+    let mut robin = RoundRobin::default();
+    assert_eq!(
+        transmit(&mut robin, &queues, usize::MAX),
+        [1, 11, 21, 22, 2, 3, 4]
+    );
+    assert!(queues.iter().all(|queues| queues.borrow().tx.is_empty()));
+    assert!(!robin.transmit(&queues.each_ref(), |_| unreachable!()));
+}
+
+#[test]
+fn transmit_stops_when_driver_is_full() {
+    let queues = queues();
+    queue_tx(&queues[0], &[1, 2, 3]);
+    queue_tx(&queues[1], &[11, 12, 13]);
+
+    // The refused frame stays queued for the next call
+    let mut robin = RoundRobin::default();
+    assert_eq!(transmit(&mut robin, &queues, 3), [1, 11, 12]);
+    assert_eq!(queues[0].borrow().tx.front(), Some(&vec![2]));
+    assert_eq!(transmit(&mut robin, &queues, 2), [2, 13]);
+    assert_eq!(transmit(&mut robin, &queues, usize::MAX), [3]);
+}
@@ -0,0 +1,51 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+pub mod channels {
+    use sel4_microkit::Channel;
+
+    pub const DRIVER: Channel = Channel::new(0);
+    /// One per client, client `n` gets MAC address `n` of `eth_driver_interface::virt`
+    pub const CLIENTS: [Channel; 1] = [Channel::new(1)];
+}
+
+pub mod sizes {
+    /// Shared with the driver
+    #[cfg(not(feature = "jumbo"))]
+    pub const DRIVER_DMA: usize = 0x20_0000;
+    #[cfg(feature = "jumbo")]
+    pub const DRIVER_DMA: usize = 0x40_0000;
+    /// Shared with each client
+    #[cfg(not(feature = "jumbo"))]
+    pub const CLIENT_DMA: usize = 0x20_0000;
+    #[cfg(feature = "jumbo")]
+    pub const CLIENT_DMA: usize = 0x40_0000;
+}
+
+pub mod queues {
+    /// Frames held for a client whose RX ring is full, before more are dropped
+    pub const RX_LEN: usize = 64;
+    /// Frames taken from each client's TX ring while waiting for their turn to go to the
+    /// driver. Short, so one busy client can't hold on to many of them.
+    pub const TX_LEN: usize = 8;
+}
+
+pub mod log {
+    use sel4_logging::{LevelFilter, Logger, LoggerBuilder};
+    use sel4_microkit::debug_print;
+
+    const LOG_LEVEL: LevelFilter = {
+        // LevelFilter::Trace
+        // LevelFilter::Debug
+        LevelFilter::Info
+        // LevelFilter::Warn
+    };
+
+    pub static LOGGER: Logger = LoggerBuilder::const_default()
+        .level_filter(LOG_LEVEL)
+        .write(|s| debug_print!("{}", s))
+        .build();
+}
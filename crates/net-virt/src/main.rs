@@ -0,0 +1,300 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+use alloc::rc::Rc;
+use core::cell::RefCell;
+use core::ptr::NonNull;
+use eth_driver_interface::{virt, MTU};
+use log::{error, info, warn};
+use net_virt_core::{demux, Queues, RoundRobin};
+use sel4_bounce_buffer_allocator::{Basic, BounceBufferAllocator};
+use sel4_driver_interfaces::net::GetNetDeviceMeta;
+use sel4_externally_shared::{ExternallySharedRef, ExternallySharedRefExt};
+use sel4_microkit::{
+    memory_region_symbol, protection_domain, Channel, Handler, Infallible, MessageInfo,
+};
+use sel4_microkit_driver_adapters::net::client::Client as NetClient;
+use sel4_microkit_driver_adapters::net::driver::HandlerImpl as PortHandler;
+use sel4_microkit_message::MessageInfoExt;
+use sel4_shared_ring_buffer::{roles::Use, RawRingBuffer, RingBuffers};
+use sel4_shared_ring_buffer_smoltcp::DeviceImpl;
+use smoltcp::{
+    phy::{Device, DeviceCapabilities, RxToken, TxToken},
+    time::Instant,
+    wire::EthernetAddress,
+};
+
+mod config;
+mod port;
+
+use port::Port;
+
+// Shares the driver among several clients, each with its own rings, DMA region, channel and MAC
+// address (see `eth_driver_interface::virt`). To the driver, or the packet filter in front of
+// it, it is the one client, and to each client it looks like the driver.
+//
+// Frames from the driver go to the client with their destination address, and broadcast and
+// multicast frames to every client. Unicast frames for no client are dropped. Frames from the
+// clients go to the driver one from each client in turn, so a busy client can't starve the
+// others. Clients can't reach each other through the port, as the switch won't send frames
+// back out of the port they came in on. How frames are shared out is in `net_virt_core`.
+
+#[protection_domain(
+    heap_size = 4*1024*1024,
+)]
+fn init() -> Virtualiser {
+    config::log::LOGGER.set().unwrap();
+    let Ok(port_mac) = NetClient::new(config::channels::DRIVER).get_mac_address() else {
+        error!("Driver unavailable, the clients have no network");
+        return Virtualiser::NoDriver;
+    };
+    let port_mac = port_mac.0;
+    let notify_driver: fn() = || config::channels::DRIVER.notify();
+
+    let driver = {
+        let dma_region = unsafe {
+            ExternallySharedRef::<'static, _>::new(
+                memory_region_symbol!(driver_dma_vaddr: *mut [u8], n = config::sizes::DRIVER_DMA),
+            )
+        };
+
+        let bounce_buffer_allocator =
+            BounceBufferAllocator::new(Basic::new(dma_region.as_ptr().len()), 1);
+
+        DeviceImpl::new(
+            Default::default(),
+            dma_region,
+            bounce_buffer_allocator,
+            RingBuffers::from_ptrs_using_default_initialization_strategy_for_role(
+                unsafe { ExternallySharedRef::new(memory_region_symbol!(driver_rx_free: *mut _)) },
+                unsafe { ExternallySharedRef::new(memory_region_symbol!(driver_rx_used: *mut _)) },
+                notify_driver,
+            ),
+            RingBuffers::from_ptrs_using_default_initialization_strategy_for_role(
+                unsafe { ExternallySharedRef::new(memory_region_symbol!(driver_tx_free: *mut _)) },
+                unsafe { ExternallySharedRef::new(memory_region_symbol!(driver_tx_used: *mut _)) },
+                notify_driver,
+            ),
+            128,
+            MTU,
+            {
+                let mut caps = DeviceCapabilities::default();
+                caps.max_transmission_unit = MTU;
+                caps
+            },
+        )
+        .unwrap()
+    };
+
+    let clients = [Client::new(
+        0,
+        port_mac,
+        ClientRegions {
+            dma: memory_region_symbol!(client0_dma_vaddr: *mut [u8], n = config::sizes::CLIENT_DMA),
+            rx_free: memory_region_symbol!(client0_rx_free: *mut _),
+            rx_used: memory_region_symbol!(client0_rx_used: *mut _),
+            tx_free: memory_region_symbol!(client0_tx_free: *mut _),
+            tx_used: memory_region_symbol!(client0_tx_used: *mut _),
+        },
+        || config::channels::CLIENTS[0].notify(),
+    )];
+
+    info!("Initialized Network Virtualiser");
+    Virtualiser::Running(HandlerImpl {
+        driver,
+        clients,
+        tx_order: RoundRobin::default(),
+    })
+}
+
+enum Virtualiser {
+    Running(HandlerImpl),
+    /// The driver failed to initialize, so the clients' calls fail as well
+    NoDriver,
+}
+
+struct ClientRegions {
+    dma: NonNull<[u8]>,
+    rx_free: NonNull<RawRingBuffer>,
+    rx_used: NonNull<RawRingBuffer>,
+    tx_free: NonNull<RawRingBuffer>,
+    tx_used: NonNull<RawRingBuffer>,
+}
+
+struct Client {
+    index: usize,
+    channel: Channel,
+    mac: [u8; 6],
+    queues: Rc<RefCell<Queues>>,
+    handler: PortHandler<Port>,
+    // As last logged
+    rx_dropped: u64,
+    tx_spoofed: u64,
+}
+
+impl Client {
+    fn new(index: usize, port_mac: [u8; 6], regions: ClientRegions, notify: fn()) -> Self {
+        let channel = config::channels::CLIENTS[index];
+        let mac = virt::client_mac(port_mac, index);
+        let queues = Rc::new(RefCell::new(Queues::new(config::queues::RX_LEN)));
+        let handler = PortHandler::new(
+            Port::new(mac, queues.clone()),
+            unsafe { ExternallySharedRef::new(regions.dma) },
+            RingBuffers::<'_, Use, fn()>::from_ptrs_using_default_initialization_strategy_for_role(
+                unsafe { ExternallySharedRef::new(regions.rx_free) },
+                unsafe { ExternallySharedRef::new(regions.rx_used) },
+                notify,
+            ),
+            RingBuffers::<'_, Use, fn()>::from_ptrs_using_default_initialization_strategy_for_role(
+                unsafe { ExternallySharedRef::new(regions.tx_free) },
+                unsafe { ExternallySharedRef::new(regions.tx_used) },
+                notify,
+            ),
+            // Ports are only ever notified on their client's channel, see `port`
+            config::channels::DRIVER,
+            channel,
+        );
+        info!("Client {index}: MAC address {}", EthernetAddress(mac));
+        Self {
+            index,
+            channel,
+            mac,
+            queues,
+            handler,
+            rx_dropped: 0,
+            tx_spoofed: 0,
+        }
+    }
+
+    fn log_drops(&mut self) {
+        let queues = self.queues.borrow();
+        if queues.rx_dropped != self.rx_dropped {
+            warn!(
+                "Client {}: RX queue full, {} frames dropped so far",
+                self.index, queues.rx_dropped
+            );
+            self.rx_dropped = queues.rx_dropped;
+        }
+        if queues.tx_spoofed != self.tx_spoofed {
+            warn!(
+                "Client {}: {} frames not from its MAC address dropped so far",
+                self.index, queues.tx_spoofed
+            );
+            self.tx_spoofed = queues.tx_spoofed;
+        }
+    }
+}
+
+struct HandlerImpl {
+    driver: DeviceImpl<Basic>,
+    clients: [Client; config::channels::CLIENTS.len()],
+    tx_order: RoundRobin,
+}
+
+impl HandlerImpl {
+    fn forward(&mut self) -> Result<(), Infallible> {
+        loop {
+            self.driver.poll();
+            let received = self.receive();
+            // Hands the clients what was received, and takes what they have to send
+            for client in &mut self.clients {
+                client.handler.notified(client.channel)?;
+            }
+            let sent = self.transmit();
+            if !received && !sent {
+                break;
+            }
+        }
+        for client in &mut self.clients {
+            client.log_drops();
+        }
+        Ok(())
+    }
+
+    fn receive(&mut self) -> bool {
+        let clients = self
+            .clients
+            .each_ref()
+            .map(|client| (client.mac, &*client.queues));
+        let mut received = false;
+        while let Some((rx, _tx)) = self.driver.receive(Instant::ZERO) {
+            rx.consume(|frame| demux(&clients, frame));
+            received = true;
+        }
+        received
+    }
+
+    fn transmit(&mut self) -> bool {
+        let clients = self.clients.each_ref().map(|client| &*client.queues);
+        let driver = &mut self.driver;
+        self.tx_order.transmit(&clients, |frame| {
+            let Some(tx) = driver.transmit(Instant::ZERO) else {
+                return false;
+            };
+            tx.consume(frame.len(), |buf| buf.copy_from_slice(frame));
+            true
+        })
+    }
+}
+
+impl Handler for HandlerImpl {
+    type Error = Infallible;
+
+    fn notified(&mut self, channel: Channel) -> Result<(), Self::Error> {
+        if channel == config::channels::DRIVER
+            || self.clients.iter().any(|client| client.channel == channel)
+        {
+            self.forward()?;
+        }
+        Ok(())
+    }
+
+    fn protected(
+        &mut self,
+        channel: Channel,
+        msg_info: MessageInfo,
+    ) -> Result<MessageInfo, Self::Error> {
+        // The clients asking for their MAC addresses
+        match self
+            .clients
+            .iter_mut()
+            .find(|client| client.channel == channel)
+        {
+            Some(client) => client.handler.protected(channel, msg_info),
+            None => {
+                warn!("Call on unknown channel: {channel:?}");
+                Ok(MessageInfo::send_unspecified_error())
+            }
+        }
+    }
+}
+
+impl Handler for Virtualiser {
+    type Error = Infallible;
+
+    fn notified(&mut self, channel: Channel) -> Result<(), Self::Error> {
+        match self {
+            Self::Running(handler) => handler.notified(channel),
+            Self::NoDriver => Ok(()),
+        }
+    }
+
+    fn protected(
+        &mut self,
+        channel: Channel,
+        msg_info: MessageInfo,
+    ) -> Result<MessageInfo, Self::Error> {
+        match self {
+            Self::Running(handler) => handler.protected(channel, msg_info),
+            Self::NoDriver => Ok(MessageInfo::send_unspecified_error()),
+        }
+    }
+}
@@ -0,0 +1,110 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+use crate::config;
+use alloc::rc::Rc;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+use core::convert::Infallible;
+use eth_driver_interface::MTU;
+use net_virt_core::Queues;
+use sel4_driver_interfaces::net::{GetNetDeviceMeta, MacAddress};
+use sel4_driver_interfaces::HandleInterrupt;
+use smoltcp::{
+    phy::{self, Device, DeviceCapabilities, Medium},
+    time::Instant,
+};
+
+// A client's side of the virtualiser, as a device for the adapters' `HandlerImpl`, which moves
+// frames between it and the client's rings just as it does for the driver. The handler owns the
+// device, so the queues are shared with `NetVirt`, which fills `rx` with the client's frames
+// from the driver and sends what the client leaves in `tx`.
+
+pub struct Port {
+    mac: [u8; 6],
+    queues: Rc<RefCell<Queues>>,
+}
+
+impl Port {
+    pub fn new(mac: [u8; 6], queues: Rc<RefCell<Queues>>) -> Self {
+        Self { mac, queues }
+    }
+}
+
+pub struct PortRxToken(Vec<u8>);
+
+pub struct PortTxToken<'a> {
+    mac: [u8; 6],
+    queues: &'a RefCell<Queues>,
+}
+
+impl phy::RxToken for PortRxToken {
+    fn consume<R, F: FnOnce(&mut [u8]) -> R>(mut self, f: F) -> R {
+        f(&mut self.0)
+    }
+}
+
+impl phy::TxToken for PortTxToken<'_> {
+    fn consume<R, F: FnOnce(&mut [u8]) -> R>(self, len: usize, f: F) -> R {
+        let mut frame = alloc::vec![0; len];
+        let result = f(&mut frame);
+        let mut queues = self.queues.borrow_mut();
+        // Clients are kept apart by their addresses, so they may only send from their own
+        if frame.get(6..12) == Some(&self.mac[..]) {
+            queues.tx.push_back(frame);
+        } else {
+            queues.tx_spoofed += 1;
+        }
+        result
+    }
+}
+
+impl Device for Port {
+    type RxToken<'a> = PortRxToken;
+    type TxToken<'a> = PortTxToken<'a>;
+
+    fn receive(&mut self, _timestamp: Instant) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
+        let frame = self.queues.borrow_mut().rx.pop_front()?;
+        Some((
+            PortRxToken(frame),
+            PortTxToken {
+                mac: self.mac,
+                queues: &self.queues,
+            },
+        ))
+    }
+
+    fn transmit(&mut self, _timestamp: Instant) -> Option<Self::TxToken<'_>> {
+        // Leave the rest in the client's ring until these have had their turn
+        if self.queues.borrow().tx.len() >= config::queues::TX_LEN {
+            return None;
+        }
+        Some(PortTxToken {
+            mac: self.mac,
+            queues: &self.queues,
+        })
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        let mut caps = DeviceCapabilities::default();
+        caps.medium = Medium::Ethernet;
+        caps.max_transmission_unit = MTU;
+        caps
+    }
+}
+
+// The handler only calls this when notified on the device channel, which it never is for a port
+impl HandleInterrupt for Port {
+    fn handle_interrupt(&mut self) {}
+}
+
+impl GetNetDeviceMeta for Port {
+    type Error = Infallible;
+
+    fn get_mac_address(&mut self) -> Result<MacAddress, Self::Error> {
+        Ok(MacAddress(self.mac))
+    }
+}
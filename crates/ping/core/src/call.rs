@@ -0,0 +1,60 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+use core::ops::Deref;
+
+// The arguments of a protected call to ping, copied out of the message registers before anything
+// else is done with the call. Whatever ping calls in turn while handling it, the timer PD for
+// the time included, replies through the same registers.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Args<const N: usize> {
+    words: [u64; N],
+    count: usize,
+}
+
+impl<const N: usize> Args<N> {
+    /// The first `count` words of `mrs`, or as many of them as there are, up to `N`
+    pub fn copy(mrs: &[u64], count: usize) -> Self {
+        let count = count.min(N).min(mrs.len());
+        let mut words = [0; N];
+        words[..count].copy_from_slice(&mrs[..count]);
+        Self { words, count }
+    }
+}
+
+impl<const N: usize> Deref for Args<N> {
+    type Target = [u64];
+
+    fn deref(&self) -> &[u64] {
+        &self.words[..self.count]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn copy() {
+        let mrs = [1, 2, 3, 4, 5];
+        assert_eq!(*Args::<4>::copy(&mrs, 3), [1, 2, 3]);
+        // More than the call takes, or than there are registers
+        assert_eq!(*Args::<4>::copy(&mrs, 9), [1, 2, 3, 4]);
+        assert_eq!(*Args::<8>::copy(&mrs, 9), [1, 2, 3, 4, 5]);
+        assert!(Args::<4>::copy(&mrs, 0).is_empty());
+    }
+
+    #[test]
+    fn registers_overwritten_after_the_copy() {
+        let mut mrs = [0x10, 0x20, 0x30];
+        let args = Args::<4>::copy(&mrs, 2);
+        // As a call to the timer PD for the time would
+        mrs = [0x1234_5678, 0, 0];
+        assert_eq!(*args, [0x10, 0x20]);
+        assert_eq!(mrs[0], 0x1234_5678);
+    }
+}
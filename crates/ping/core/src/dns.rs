@@ -0,0 +1,322 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+use alloc::vec::Vec;
+use core::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+// Just enough DNS (RFC 1035) for a stub resolver: a query for the A or AAAA records of a name,
+// and the addresses in the reply along with the TTL they can be cached for. smoltcp's DNS socket
+// doesn't pass on the records' TTLs, so ping sends these over UDP itself.
+
+pub const PORT: u16 = 53;
+/// The resolver's UDP port, in the ephemeral range
+pub const LOCAL_PORT: u16 = 50_053;
+/// The largest message over UDP, without EDNS
+pub const MAX_LEN: usize = 512;
+
+const HEADER_LEN: usize = 12;
+const CLASS_IN: u16 = 1;
+const TYPE_A: u16 = 1;
+const TYPE_CNAME: u16 = 5;
+const TYPE_AAAA: u16 = 28;
+
+// Recursion desired
+const FLAGS_QUERY: u16 = 1 << 8;
+const FLAG_RESPONSE: u16 = 1 << 15;
+const FLAG_TRUNCATED: u16 = 1 << 9;
+const RCODE_MASK: u16 = 0xF;
+const RCODE_NAME_ERROR: u16 = 3;
+
+// Names longer than this don't fit in 255 bytes once encoded
+const MAX_NAME_LEN: usize = 253;
+const MAX_LABEL_LEN: usize = 63;
+// A length byte with the top two bits set starts a compression pointer, two bytes long
+const POINTER: u8 = 0xC0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordType {
+    A,
+    Aaaa,
+}
+
+impl RecordType {
+    fn code(self) -> u16 {
+        match self {
+            Self::A => TYPE_A,
+            Self::Aaaa => TYPE_AAAA,
+        }
+    }
+}
+
+/// A query for the `record_type` records of `name`, or `None` if `name` isn't a valid domain
+/// name. The server echoes `id` back in its reply.
+pub fn query(id: u16, name: &str, record_type: RecordType) -> Option<Vec<u8>> {
+    let name = name.strip_suffix('.').unwrap_or(name);
+    if name.is_empty() || name.len() > MAX_NAME_LEN {
+        return None;
+    }
+    let mut packet = Vec::with_capacity(HEADER_LEN + name.len() + 6);
+    for word in [id, FLAGS_QUERY, 1, 0, 0, 0] {
+        packet.extend_from_slice(&word.to_be_bytes());
+    }
+    for label in name.split('.') {
+        if label.is_empty() || label.len() > MAX_LABEL_LEN {
+            return None;
+        }
+        packet.push(label.len() as u8);
+        packet.extend_from_slice(label.as_bytes());
+    }
+    packet.push(0);
+    packet.extend_from_slice(&record_type.code().to_be_bytes());
+    packet.extend_from_slice(&CLASS_IN.to_be_bytes());
+    Some(packet)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Reply {
+    /// The addresses in the answer, which may have none, and the smallest TTL in seconds among
+    /// them and the aliases they were found through. The TTL is 0 without any addresses.
+    Answer { addrs: Vec<IpAddr>, ttl: u32 },
+    /// The name doesn't exist
+    NameError,
+    /// The server failed or refused to answer, or the answer didn't fit
+    Failed,
+}
+
+/// The reply to the query for `record_type` records with `id`, or `None` if `packet` isn't one
+pub fn parse_reply(packet: &[u8], id: u16, record_type: RecordType) -> Option<Reply> {
+    let mut reader = Reader { packet, pos: 0 };
+    let [reply_id, flags, questions, answers, _, _] = [(); 6].map(|_| reader.u16());
+    if reply_id? != id || flags? & FLAG_RESPONSE == 0 {
+        return None;
+    }
+    let flags = flags?;
+    match flags & RCODE_MASK {
+        0 => {}
+        RCODE_NAME_ERROR => return Some(Reply::NameError),
+        _ => return Some(Reply::Failed),
+    }
+    // The rest would have to be asked for over TCP
+    if flags & FLAG_TRUNCATED != 0 {
+        return Some(Reply::Failed);
+    }
+
+    for _ in 0..questions? {
+        reader.skip_name()?;
+        // Type and class
+        reader.bytes(4)?;
+    }
+    let mut addrs = Vec::new();
+    let mut ttl = u32::MAX;
+    for _ in 0..answers? {
+        reader.skip_name()?;
+        let (rtype, class, record_ttl) = (reader.u16()?, reader.u16()?, reader.u32()?);
+        let len = reader.u16()?;
+        let data = reader.bytes(len as usize)?;
+        if class != CLASS_IN {
+            continue;
+        }
+        let addr = match (rtype, record_type) {
+            (TYPE_A, RecordType::A) => {
+                Some(IpAddr::V4(Ipv4Addr::from(<[u8; 4]>::try_from(data).ok()?)))
+            }
+            (TYPE_AAAA, RecordType::Aaaa) => {
+                Some(IpAddr::V6(Ipv6Addr::from(<[u8; 16]>::try_from(data).ok()?)))
+            }
+            (TYPE_CNAME, _) => None,
+            _ => continue,
+        };
+        // RFC 2181 section 8: a TTL with the top bit set is taken as 0
+        ttl = ttl.min(if record_ttl > i32::MAX as u32 {
+            0
+        } else {
+            record_ttl
+        });
+        addrs.extend(addr);
+    }
+    if addrs.is_empty() {
+        ttl = 0;
+    }
+    Some(Reply::Answer { addrs, ttl })
+}
+
+struct Reader<'a> {
+    packet: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        let bytes = self.packet.get(self.pos..self.pos.checked_add(len)?)?;
+        self.pos += len;
+        Some(bytes)
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        Some(u16::from_be_bytes(self.bytes(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_be_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    // Names aren't compared, so pointers don't need following
+    fn skip_name(&mut self) -> Option<()> {
+        loop {
+            let len = self.bytes(1)?[0];
+            match len {
+                0 => return Some(()),
+                _ if len & POINTER == POINTER => {
+                    self.bytes(1)?;
+                    return Some(());
+                }
+                _ if len as usize > MAX_LABEL_LEN => return None,
+                _ => {
+                    self.bytes(len as usize)?;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    // A reply to `query(0x1234, "host.example", RecordType::A)`, with `answers` given as their
+    // type, TTL and data
+    fn reply(rcode: u16, answers: &[(u16, u32, &[u8])]) -> Vec<u8> {
+        let mut packet = query(0x1234, "host.example", RecordType::A).unwrap();
+        packet[2..4].copy_from_slice(&(FLAG_RESPONSE | FLAGS_QUERY | rcode).to_be_bytes());
+        packet[6..8].copy_from_slice(&(answers.len() as u16).to_be_bytes());
+        for &(rtype, ttl, data) in answers {
+            // Pointing back at the name in the question
+            packet.extend_from_slice(&[POINTER, HEADER_LEN as u8]);
+            packet.extend_from_slice(&rtype.to_be_bytes());
+            packet.extend_from_slice(&CLASS_IN.to_be_bytes());
+            packet.extend_from_slice(&ttl.to_be_bytes());
+            packet.extend_from_slice(&(data.len() as u16).to_be_bytes());
+            packet.extend_from_slice(data);
+        }
+        packet
+    }
+
+    #[test]
+    fn query_layout() {
+        assert_eq!(
+            query(0xABCD, "host.example.", RecordType::Aaaa).unwrap(),
+            [
+                &[0xAB, 0xCD, 0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0][..],
+                b"\x04host\x07example\x00",
+                &[0, 28, 0, 1],
+            ]
+            .concat()
+        );
+    }
+
+    #[test]
+    fn query_invalid_names() {
+        let long_label = "a".repeat(64);
+        let long_name = ["a"; 128].join(".");
+        for name in ["", ".", "host..example", &long_label, &long_name] {
+            assert_eq!(query(1, name, RecordType::A), None, "{name}");
+        }
+    }
+
+    #[test]
+    fn answer_with_smallest_ttl() {
+        let packet = reply(
+            0,
+            &[
+                (TYPE_CNAME, 60, b"\x05alias\x00"),
+                (TYPE_A, 300, &[192, 0, 2, 1]),
+                (TYPE_A, 120, &[192, 0, 2, 2]),
+                // Not asked for
+                (TYPE_AAAA, 10, &[0; 16]),
+            ],
+        );
+        assert_eq!(
+            parse_reply(&packet, 0x1234, RecordType::A),
+            Some(Reply::Answer {
+                addrs: vec![
+                    IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1)),
+                    IpAddr::V4(Ipv4Addr::new(192, 0, 2, 2)),
+                ],
+                ttl: 60,
+            })
+        );
+    }
+
+    #[test]
+    fn answer_aaaa() {
+        let addr = Ipv6Addr::new(0x2001, 0xDB8, 0, 0, 0, 0, 0, 1);
+        let packet = reply(0, &[(TYPE_AAAA, 3600, &addr.octets())]);
+        assert_eq!(
+            parse_reply(&packet, 0x1234, RecordType::Aaaa),
+            Some(Reply::Answer {
+                addrs: vec![IpAddr::V6(addr)],
+                ttl: 3600,
+            })
+        );
+    }
+
+    #[test]
+    fn ttl_with_top_bit_set() {
+        let packet = reply(0, &[(TYPE_A, 0x8000_0000, &[192, 0, 2, 1])]);
+        let Some(Reply::Answer { ttl, .. }) = parse_reply(&packet, 0x1234, RecordType::A) else {
+            panic!();
+        };
+        assert_eq!(ttl, 0);
+    }
+
+    #[test]
+    fn no_addresses() {
+        let packet = reply(0, &[(TYPE_CNAME, 60, b"\x05alias\x00")]);
+        assert_eq!(
+            parse_reply(&packet, 0x1234, RecordType::A),
+            Some(Reply::Answer {
+                addrs: vec![],
+                ttl: 0
+            })
+        );
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            parse_reply(&reply(3, &[]), 0x1234, RecordType::A),
+            Some(Reply::NameError)
+        );
+        assert_eq!(
+            parse_reply(&reply(2, &[]), 0x1234, RecordType::A),
+            Some(Reply::Failed)
+        );
+        let mut truncated = reply(0, &[]);
+        truncated[2] |= (FLAG_TRUNCATED >> 8) as u8;
+        assert_eq!(
+            parse_reply(&truncated, 0x1234, RecordType::A),
+            Some(Reply::Failed)
+        );
+    }
+
+    #[test]
+    fn not_a_reply() {
+        let packet = reply(0, &[(TYPE_A, 300, &[192, 0, 2, 1])]);
+        // Another query's
+        assert_eq!(parse_reply(&packet, 0x4321, RecordType::A), None);
+        // A query rather than a reply
+        let query = query(0x1234, "host.example", RecordType::A).unwrap();
+        assert_eq!(parse_reply(&query, 0x1234, RecordType::A), None);
+        // Cut short, or with a bad address length
+        assert_eq!(
+            parse_reply(&packet[..packet.len() - 1], 0x1234, RecordType::A),
+            None
+        );
+        let bad_len = reply(0, &[(TYPE_A, 300, &[192, 0, 2])]);
+        assert_eq!(parse_reply(&bad_len, 0x1234, RecordType::A), None);
+    }
+}
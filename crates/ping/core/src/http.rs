@@ -0,0 +1,176 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+use alloc::format;
+use alloc::string::String;
+
+// Just enough HTTP/1.1 to answer `GET` requests for a few JSON resources. Each connection gets
+// one response and is then closed, request bodies are ignored.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resource {
+    Status,
+    Stats,
+}
+
+impl Resource {
+    fn from_path(path: &str) -> Option<Self> {
+        match path {
+            "/status" => Some(Self::Status),
+            "/stats" => Some(Self::Stats),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Parsed<'a> {
+    Incomplete,
+    Malformed,
+    Request { method: &'a str, path: &'a str },
+}
+
+/// Parse the request received so far, up to the end of its headers
+pub fn parse_request(request: &[u8]) -> Parsed<'_> {
+    let Some(end) = request.windows(4).position(|w| w == b"\r\n\r\n") else {
+        return Parsed::Incomplete;
+    };
+    let Ok(head) = core::str::from_utf8(&request[..end]) else {
+        return Parsed::Malformed;
+    };
+    let request_line = head.split("\r\n").next().unwrap_or_default();
+    let mut parts = request_line.split(' ');
+    match (parts.next(), parts.next(), parts.next(), parts.next()) {
+        (Some(method), Some(target), Some(version), None) if version.starts_with("HTTP/1.") => {
+            // Queries aren't used by any resource
+            let path = target.split('?').next().unwrap_or_default();
+            Parsed::Request { method, path }
+        }
+        _ => Parsed::Malformed,
+    }
+}
+
+/// The response to a well-formed request. `render` gives the JSON body for a resource.
+pub fn answer(method: &str, path: &str, render: impl FnOnce(Resource) -> String) -> String {
+    match (method, Resource::from_path(path)) {
+        (_, None) => error(404, "Not Found"),
+        ("GET", Some(resource)) => respond(200, "OK", "", &render(resource)),
+        (_, Some(_)) => respond(
+            405,
+            "Method Not Allowed",
+            "Allow: GET\r\n",
+            "{\"error\":\"Method Not Allowed\"}",
+        ),
+    }
+}
+
+pub fn error(code: u16, reason: &str) -> String {
+    respond(code, reason, "", &format!("{{\"error\":\"{reason}\"}}"))
+}
+
+fn respond(code: u16, reason: &str, headers: &str, body: &str) -> String {
+    format!(
+        "HTTP/1.1 {code} {reason}\r\n\
+         Content-Type: application/json\r\n\
+         Content-Length: {}\r\n\
+         Connection: close\r\n\
+         {headers}\r\n\
+         {body}",
+        body.len()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_get() {
+        assert_eq!(
+            parse_request(b"GET /status HTTP/1.1\r\nHost: ping\r\n\r\n"),
+            Parsed::Request {
+                method: "GET",
+                path: "/status"
+            }
+        );
+        assert_eq!(
+            parse_request(b"HEAD /stats?verbose=1 HTTP/1.0\r\n\r\nbody"),
+            Parsed::Request {
+                method: "HEAD",
+                path: "/stats"
+            }
+        );
+    }
+
+    #[test]
+    fn parse_incomplete() {
+        assert_eq!(parse_request(b""), Parsed::Incomplete);
+        assert_eq!(
+            parse_request(b"GET /status HTTP/1.1\r\nHost: ping\r\n"),
+            Parsed::Incomplete
+        );
+    }
+
+    #[test]
+    fn parse_malformed() {
+        for request in [
+            &b"GET /status\r\n\r\n"[..],
+            b"GET /status HTTP/2\r\n\r\n",
+            b"GET  /status HTTP/1.1\r\n\r\n",
+            b"GET /status HTTP/1.1 extra\r\n\r\n",
+            b"GET /\xff HTTP/1.1\r\n\r\n",
+        ] {
+            assert_eq!(parse_request(request), Parsed::Malformed);
+        }
+    }
+
+    #[test]
+    fn resources() {
+        assert_eq!(Resource::from_path("/status"), Some(Resource::Status));
+        assert_eq!(Resource::from_path("/stats"), Some(Resource::Stats));
+        assert_eq!(Resource::from_path("/"), None);
+        assert_eq!(Resource::from_path("/status/"), None);
+    }
+
+    #[test]
+    fn answer_get() {
+        let response = answer("GET", "/stats", |resource| format!("{resource:?}"));
+        assert_eq!(
+            response,
+            "HTTP/1.1 200 OK\r\n\
+             Content-Type: application/json\r\n\
+             Content-Length: 5\r\n\
+             Connection: close\r\n\
+             \r\n\
+             Stats"
+        );
+    }
+
+    #[test]
+    fn answer_errors() {
+        let render = |_: Resource| -> String { unreachable!() };
+        assert_eq!(answer("GET", "/", render), error(404, "Not Found"));
+        assert_eq!(
+            answer("POST", "/status", render),
+            "HTTP/1.1 405 Method Not Allowed\r\n\
+             Content-Type: application/json\r\n\
+             Content-Length: 30\r\n\
+             Connection: close\r\n\
+             Allow: GET\r\n\
+             \r\n\
+             {\"error\":\"Method Not Allowed\"}"
+        );
+        assert_eq!(
+            error(404, "Not Found"),
+            "HTTP/1.1 404 Not Found\r\n\
+             Content-Type: application/json\r\n\
+             Content-Length: 21\r\n\
+             Connection: close\r\n\
+             \r\n\
+             {\"error\":\"Not Found\"}"
+        );
+    }
+}
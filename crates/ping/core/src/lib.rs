@@ -0,0 +1,20 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+#![no_std]
+
+// The parts of ping with no dependency on seL4 or on smoltcp's sockets, so that their tests
+// run on the host.
+
+extern crate alloc;
+
+pub mod call;
+pub mod dns;
+pub mod http;
+pub mod password;
+pub mod ports;
+pub mod settings;
+pub mod sntp;
@@ -0,0 +1,57 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+use alloc::string::String;
+use core::fmt;
+
+/// Shown as `Password(..)`, so it stays out of the logs
+#[derive(Clone, PartialEq, Eq)]
+pub struct Password(String);
+
+impl Password {
+    pub fn new(password: &str) -> Self {
+        Self(password.into())
+    }
+
+    /// Takes as long whatever the attempt has in common with the password
+    pub fn matches(&self, attempt: &str) -> bool {
+        let (password, attempt) = (self.0.as_bytes(), attempt.as_bytes());
+        password.len() == attempt.len()
+            && password
+                .iter()
+                .zip(attempt)
+                .fold(0, |diff, (a, b)| diff | (a ^ b))
+                == 0
+    }
+}
+
+impl fmt::Debug for Password {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Password(..)")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::format;
+
+    #[test]
+    fn matches() {
+        let password = Password::new("open sesame");
+        assert!(password.matches("open sesame"));
+        assert!(!password.matches("open sesam"));
+        assert!(!password.matches("open sesame!"));
+        assert!(!password.matches("Open sesame"));
+        assert!(!password.matches(""));
+    }
+
+    #[test]
+    fn hidden_from_debug() {
+        let password = Password::new("open sesame");
+        assert_eq!(format!("{password:?}"), "Password(..)");
+    }
+}
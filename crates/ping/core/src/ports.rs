@@ -0,0 +1,127 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+use core::ops::RangeInclusive;
+
+// Which of a socket client's ports its sockets may take, for ping's socket server. A UDP socket
+// bound to a port, or a TCP socket listening on it, keeps it to itself. A TCP connection only
+// keeps its port from being picked for another, as connections are told apart by their remote
+// ends, so a client can listen again on the port a listening socket has just been connected on.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PortUse {
+    /// A bound UDP socket or a listening TCP socket
+    Bound,
+    /// A TCP socket that has left LISTEN, or was never in it
+    Connected,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PortError {
+    /// Not one of the client's ports
+    NotAllowed,
+    InUse,
+}
+
+pub struct Ports {
+    range: RangeInclusive<u16>,
+    // Where to start looking for a free port
+    next: u16,
+}
+
+impl Ports {
+    pub fn new(range: RangeInclusive<u16>) -> Self {
+        Self {
+            next: *range.start(),
+            range,
+        }
+    }
+
+    /// `port` for `BIND` or `LISTEN`, given the ports the client's sockets have
+    pub fn check(&self, port: u16, in_use: &[(u16, PortUse)]) -> Result<u16, PortError> {
+        if !self.range.contains(&port) {
+            return Err(PortError::NotAllowed);
+        }
+        if in_use.contains(&(port, PortUse::Bound)) {
+            return Err(PortError::InUse);
+        }
+        Ok(port)
+    }
+
+    /// A port for `CONNECT`, or for `SEND` on an unbound UDP socket: the next of the client's
+    /// that none of its sockets has
+    pub fn pick(&mut self, in_use: &[(u16, PortUse)]) -> Result<u16, PortError> {
+        let len = (self.range.end() - self.range.start()) as usize + 1;
+        let start = self.next;
+        let port = self
+            .range
+            .clone()
+            .skip_while(|&port| port < start)
+            .chain(self.range.clone())
+            .take(len)
+            .find(|&port| in_use.iter().all(|&(used, _)| used != port))
+            .ok_or(PortError::InUse)?;
+        self.next = port.checked_add(1).unwrap_or(*self.range.start());
+        Ok(port)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check() {
+        let ports = Ports::new(7..=9);
+        assert_eq!(ports.check(7, &[]), Ok(7));
+        assert_eq!(ports.check(9, &[(7, PortUse::Bound)]), Ok(9));
+        assert_eq!(ports.check(6, &[]), Err(PortError::NotAllowed));
+        assert_eq!(ports.check(10, &[]), Err(PortError::NotAllowed));
+        assert_eq!(
+            ports.check(7, &[(7, PortUse::Bound)]),
+            Err(PortError::InUse)
+        );
+    }
+
+    #[test]
+    fn listen_again_after_a_peer_connects() {
+        let ports = Ports::new(7..=7);
+        // LISTEN on socket 0
+        let mut in_use = [(ports.check(7, &[]).unwrap(), PortUse::Bound)];
+        // Another listener has to wait
+        assert_eq!(ports.check(7, &in_use), Err(PortError::InUse));
+        // A peer connects, taking socket 0 out of LISTEN, so socket 1 can listen in its place
+        in_use[0].1 = PortUse::Connected;
+        assert_eq!(ports.check(7, &in_use), Ok(7));
+    }
+
+    #[test]
+    fn pick_goes_round() {
+        let mut ports = Ports::new(7..=9);
+        assert_eq!(ports.pick(&[]), Ok(7));
+        assert_eq!(ports.pick(&[]), Ok(8));
+        // Connections keep their ports from being picked too
+        assert_eq!(ports.pick(&[(9, PortUse::Connected)]), Ok(7));
+        assert_eq!(ports.pick(&[]), Ok(8));
+        assert_eq!(ports.pick(&[(9, PortUse::Bound)]), Ok(7));
+    }
+
+    #[test]
+    fn pick_when_all_are_taken() {
+        let mut ports = Ports::new(7..=8);
+        let in_use = [(7, PortUse::Bound), (8, PortUse::Connected)];
+        assert_eq!(ports.pick(&in_use), Err(PortError::InUse));
+        assert_eq!(ports.pick(&in_use[..1]), Ok(8));
+    }
+
+    #[test]
+    fn pick_at_the_top_of_the_range() {
+        let mut ports = Ports::new(65534..=65535);
+        assert_eq!(ports.pick(&[]), Ok(65534));
+        assert_eq!(ports.pick(&[]), Ok(65535));
+        assert_eq!(ports.pick(&[]), Ok(65534));
+    }
+}
@@ -0,0 +1,308 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+use crate::password::Password;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use log::{info, warn};
+use smoltcp::wire::{Ipv4Address, Ipv4Cidr};
+
+// The network settings `ping` reads at boot from the `.net_config` section of its image, as
+// key=value text:
+//
+//   # Comments and blank lines are ignored
+//   version=1
+//   dhcp=false
+//   address=192.168.60.146/24
+//   gateway=192.168.60.158
+//   dns=192.168.60.158 1.1.1.1
+//   sntp=pool.ntp.org
+//   syslog=192.168.60.10
+//   shell_password=correct horse
+//   crc32=0123abcd
+//
+// `version` comes first and `crc32` last, holding the CRC-32 (as used by zlib) of everything
+// before that line. `support/net-config.py` appends it. An empty `syslog` turns remote logging
+// off, and an empty `shell_password` the shell. Text that doesn't check out is ignored as a
+// whole.
+
+const VERSION: u32 = 1;
+
+/// The static IPv4 configuration, used when DHCP is off or no server answers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StaticIpv4 {
+    pub cidr: Ipv4Cidr,
+    pub gateway: Ipv4Address,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Settings {
+    pub dhcp: bool,
+    pub static_ipv4: StaticIpv4,
+    /// Used when DHCP doesn't provide any
+    pub dns_servers: Vec<Ipv4Address>,
+    pub sntp_server: String,
+    pub syslog_collector: Option<String>,
+    pub shell_password: Option<Password>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseError {
+    NotText,
+    NoChecksum,
+    BadChecksum { expected: u32, actual: u32 },
+    UnsupportedVersion,
+    BadLine { line: usize, reason: &'static str },
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotText => write!(f, "not UTF-8 text"),
+            Self::NoChecksum => write!(f, "no crc32 line at the end"),
+            Self::BadChecksum { expected, actual } => {
+                write!(f, "crc32 is {actual:08x}, expected {expected:08x}")
+            }
+            Self::UnsupportedVersion => write!(f, "doesn't start with a supported version"),
+            Self::BadLine { line, reason } => write!(f, "line {line}: {reason}"),
+        }
+    }
+}
+
+impl Settings {
+    /// The settings in `region`, up to its first NUL byte, or `defaults` if there are none or
+    /// they are invalid
+    pub fn load(region: &[u8], defaults: Self) -> Self {
+        let len = region.iter().position(|&b| b == 0).unwrap_or(region.len());
+        if len == 0 {
+            info!("No network configuration, using the built-in defaults");
+            return defaults;
+        }
+        match Self::parse(&region[..len], defaults.clone()) {
+            Ok(settings) => {
+                info!("Loaded network configuration: {settings:?}");
+                settings
+            }
+            Err(err) => {
+                warn!("Ignoring network configuration, {err}");
+                defaults
+            }
+        }
+    }
+
+    /// Anything `text` leaves out keeps its value from `defaults`
+    pub fn parse(text: &[u8], defaults: Self) -> Result<Self, ParseError> {
+        let mut settings = defaults;
+        for (line, text) in lines(text, VERSION)? {
+            let bad = |reason| ParseError::BadLine { line, reason };
+            let (key, value) = text.split_once('=').ok_or(bad("expected key=value"))?;
+            let (key, value) = (key.trim(), value.trim());
+            match key {
+                "dhcp" => {
+                    settings.dhcp = match value {
+                        "true" | "yes" | "1" => true,
+                        "false" | "no" | "0" => false,
+                        _ => return Err(bad("expected true or false")),
+                    }
+                }
+                "address" => {
+                    settings.static_ipv4.cidr = value
+                        .parse()
+                        .map_err(|_| bad("expected an IPv4 address/prefix"))?
+                }
+                "gateway" => {
+                    settings.static_ipv4.gateway =
+                        value.parse().map_err(|_| bad("expected an IPv4 address"))?
+                }
+                "dns" => {
+                    settings.dns_servers = value
+                        .split_whitespace()
+                        .map(str::parse)
+                        .collect::<Result<_, _>>()
+                        .map_err(|_| bad("expected IPv4 addresses separated by spaces"))?
+                }
+                "sntp" if value.is_empty() => return Err(bad("expected a name or address")),
+                "sntp" => settings.sntp_server = value.into(),
+                "syslog" => settings.syslog_collector = (!value.is_empty()).then(|| value.into()),
+                "shell_password" => {
+                    settings.shell_password = (!value.is_empty()).then(|| Password::new(value))
+                }
+                _ => warn!("Network configuration line {line}: unknown setting {key}"),
+            }
+        }
+        Ok(settings)
+    }
+}
+
+/// The numbered lines of checksummed text in this format, after the `version` line and before
+/// the `crc32` one, without comments or blank lines. net-filter's rule table is in it too.
+pub fn lines(text: &[u8], version: u32) -> Result<impl Iterator<Item = (usize, &str)>, ParseError> {
+    let text = core::str::from_utf8(text).map_err(|_| ParseError::NotText)?;
+    let text = text.trim_end();
+    let (body, last) = text
+        .rfind('\n')
+        .map(|i| text.split_at(i + 1))
+        .ok_or(ParseError::NoChecksum)?;
+    let expected = last
+        .trim()
+        .strip_prefix("crc32=")
+        .and_then(|crc| u32::from_str_radix(crc, 16).ok())
+        .ok_or(ParseError::NoChecksum)?;
+    let actual = crc32(body.as_bytes());
+    if actual != expected {
+        return Err(ParseError::BadChecksum { expected, actual });
+    }
+
+    let mut lines = body
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'));
+    let found = lines
+        .next()
+        .and_then(|(_, line)| line.strip_prefix("version="))
+        .and_then(|found| found.parse::<u32>().ok());
+    if found != Some(version) {
+        return Err(ParseError::UnsupportedVersion);
+    }
+    Ok(lines)
+}
+
+/// CRC-32/ISO-HDLC, as computed by zlib and `cksum -a crc32b`
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xEDB8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::format;
+    use alloc::vec;
+
+    fn defaults() -> Settings {
+        Settings {
+            dhcp: true,
+            static_ipv4: StaticIpv4 {
+                cidr: Ipv4Cidr::new(Ipv4Address::new(192, 168, 60, 146), 24),
+                gateway: Ipv4Address::new(192, 168, 60, 158),
+            },
+            dns_servers: vec![Ipv4Address::new(192, 168, 60, 158)],
+            sntp_server: "pool.ntp.org".into(),
+            syslog_collector: Some("192.168.60.10".into()),
+            shell_password: None,
+        }
+    }
+
+    fn with_crc(body: &str) -> String {
+        format!("{body}crc32={:08x}\n", crc32(body.as_bytes()))
+    }
+
+    #[test]
+    fn crc32_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn parse_all_settings() {
+        let text = with_crc(
+            "# A comment\n\
+             version=1\n\
+             \n\
+             dhcp=false\n\
+             address=10.0.0.2/8\n\
+             gateway = 10.0.0.1\n\
+             dns=10.0.0.1 1.1.1.1\n\
+             sntp=time.example.com\n\
+             syslog=10.0.0.10\n\
+             shell_password=open sesame\n\
+             unknown=ignored\n",
+        );
+        let settings = Settings::parse(text.as_bytes(), defaults()).unwrap();
+        assert!(!settings.dhcp);
+        assert_eq!(
+            settings.static_ipv4,
+            StaticIpv4 {
+                cidr: Ipv4Cidr::new(Ipv4Address::new(10, 0, 0, 2), 8),
+                gateway: Ipv4Address::new(10, 0, 0, 1),
+            }
+        );
+        assert_eq!(
+            settings.dns_servers,
+            [Ipv4Address::new(10, 0, 0, 1), Ipv4Address::new(1, 1, 1, 1)]
+        );
+        assert_eq!(settings.sntp_server, "time.example.com");
+        assert_eq!(settings.syslog_collector.as_deref(), Some("10.0.0.10"));
+        assert_eq!(settings.shell_password, Some(Password::new("open sesame")));
+    }
+
+    #[test]
+    fn parse_keeps_defaults() {
+        let text = with_crc("version=1\nsyslog=\n");
+        assert_eq!(
+            Settings::parse(text.as_bytes(), defaults()),
+            Ok(Settings {
+                syslog_collector: None,
+                ..defaults()
+            })
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        let parse = |text: &str| Settings::parse(text.as_bytes(), defaults());
+        assert_eq!(parse("version=1\n"), Err(ParseError::NoChecksum));
+        assert_eq!(parse("version=1\ncrc32=xyz\n"), Err(ParseError::NoChecksum));
+        assert!(matches!(
+            parse("version=1\ncrc32=00000000\n"),
+            Err(ParseError::BadChecksum { expected: 0, .. })
+        ));
+        assert_eq!(
+            parse(&with_crc("version=2\n")),
+            Err(ParseError::UnsupportedVersion)
+        );
+        assert_eq!(
+            parse(&with_crc("dhcp=true\nversion=1\n")),
+            Err(ParseError::UnsupportedVersion)
+        );
+        assert_eq!(
+            parse(&with_crc("version=1\n\ndhcp=maybe\n")),
+            Err(ParseError::BadLine {
+                line: 3,
+                reason: "expected true or false"
+            })
+        );
+        assert!(matches!(
+            parse(&with_crc("version=1\ndns=1.1.1.1 localhost\n")),
+            Err(ParseError::BadLine { line: 2, .. })
+        ));
+        assert!(matches!(
+            parse(&with_crc("version=1\nsntp\n")),
+            Err(ParseError::BadLine { line: 2, .. })
+        ));
+        assert_eq!(
+            Settings::parse(b"version=1\n\xff\ncrc32=0\n", defaults()),
+            Err(ParseError::NotText)
+        );
+    }
+
+    #[test]
+    fn load_falls_back_to_defaults() {
+        assert_eq!(Settings::load(&[0; 64], defaults()), defaults());
+        assert_eq!(Settings::load(b"garbage\0", defaults()), defaults());
+
+        let mut region = with_crc("version=1\ndhcp=false\n").into_bytes();
+        region.resize(4096, 0);
+        assert!(!Settings::load(&region, defaults()).dhcp);
+    }
+}
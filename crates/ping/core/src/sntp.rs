@@ -0,0 +1,141 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+// SNTP (RFC 4330) packets, for the client in ping's `sntp` module.
+//
+// NTP timestamps wrap in 2036. Those that would be before 1970 are taken to be from after the
+// wrap, which lasts until 2106.
+
+pub const NTP_PORT: u16 = 123;
+pub const LOCAL_PORT: u16 = 50_123;
+pub const PACKET_LEN: usize = 48;
+
+const LEAP_UNSYNCHRONIZED: u8 = 3;
+const VERSION: u8 = 4;
+const MODE_CLIENT: u8 = 3;
+const MODE_SERVER: u8 = 4;
+
+// From 1900, the start of NTP era 0, to 1970
+const UNIX_EPOCH_NTP_SECS: u64 = 2_208_988_800;
+const NTP_ERA_SECS: u64 = 1 << 32;
+
+fn ntp_to_unix_us(ntp: u64) -> u64 {
+    let secs = ntp >> 32;
+    let secs = match secs.checked_sub(UNIX_EPOCH_NTP_SECS) {
+        Some(secs) => secs,
+        // Era 1
+        None => secs + NTP_ERA_SECS - UNIX_EPOCH_NTP_SECS,
+    };
+    let frac_us = ((ntp & 0xFFFF_FFFF) * 1_000_000) >> 32;
+    secs * 1_000_000 + frac_us
+}
+
+/// A client request. The server echoes `cookie` back as the originate timestamp.
+pub fn request(cookie: u64) -> [u8; PACKET_LEN] {
+    let mut packet = [0; PACKET_LEN];
+    packet[0] = VERSION << 3 | MODE_CLIENT;
+    packet[40..48].copy_from_slice(&cookie.to_be_bytes());
+    packet
+}
+
+/// The server's receive and transmit timestamps, in microseconds since the Unix epoch
+pub fn parse_reply(packet: &[u8], cookie: u64) -> Option<(u64, u64)> {
+    let packet: &[u8; PACKET_LEN] = packet.get(..PACKET_LEN)?.try_into().ok()?;
+    let timestamp =
+        |offset: usize| u64::from_be_bytes(packet[offset..offset + 8].try_into().unwrap());
+    let leap = packet[0] >> 6;
+    let mode = packet[0] & 0x7;
+    let stratum = packet[1];
+    // Stratum 0 is a kiss-o'-death, telling the client to back off
+    if mode != MODE_SERVER || leap == LEAP_UNSYNCHRONIZED || stratum == 0 {
+        return None;
+    }
+    if timestamp(24) != cookie {
+        return None;
+    }
+    Some((ntp_to_unix_us(timestamp(32)), ntp_to_unix_us(timestamp(40))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COOKIE: u64 = 0x0123_4567_89ab_cdef;
+
+    fn ntp(unix_secs: u64, frac: u32) -> u64 {
+        ((unix_secs + UNIX_EPOCH_NTP_SECS) % NTP_ERA_SECS) << 32 | frac as u64
+    }
+
+    fn reply(receive: u64, transmit: u64) -> [u8; PACKET_LEN] {
+        let mut packet = [0; PACKET_LEN];
+        packet[0] = VERSION << 3 | MODE_SERVER;
+        packet[1] = 2;
+        packet[24..32].copy_from_slice(&COOKIE.to_be_bytes());
+        packet[32..40].copy_from_slice(&receive.to_be_bytes());
+        packet[40..48].copy_from_slice(&transmit.to_be_bytes());
+        packet
+    }
+
+    #[test]
+    fn ntp_to_unix() {
+        assert_eq!(ntp_to_unix_us(ntp(0, 0)), 0);
+        assert_eq!(ntp_to_unix_us(ntp(1, 0x8000_0000)), 1_500_000);
+        assert_eq!(
+            ntp_to_unix_us(ntp(1_700_000_000, 0xFFFF_FFFF)),
+            1_700_000_000_999_999
+        );
+    }
+
+    #[test]
+    fn ntp_to_unix_after_the_wrap() {
+        // 2036-02-07T06:28:16Z, the start of era 1
+        let wrap = NTP_ERA_SECS - UNIX_EPOCH_NTP_SECS;
+        assert_eq!(ntp(wrap, 0), 0);
+        assert_eq!(ntp_to_unix_us(ntp(wrap, 0)), wrap * 1_000_000);
+        assert_eq!(ntp_to_unix_us(ntp(wrap - 1, 0)), (wrap - 1) * 1_000_000);
+        assert_eq!(ntp_to_unix_us(ntp(4_000_000_000, 0)), 4_000_000_000_000_000);
+    }
+
+    #[test]
+    fn request_layout() {
+        let packet = request(COOKIE);
+        assert_eq!(packet[0], 0x23);
+        assert!(packet[1..40].iter().all(|&b| b == 0));
+        assert_eq!(packet[40..], COOKIE.to_be_bytes());
+    }
+
+    #[test]
+    fn parse_valid_reply() {
+        let packet = reply(ntp(1_700_000_000, 0), ntp(1_700_000_001, 0));
+        assert_eq!(
+            parse_reply(&packet, COOKIE),
+            Some((1_700_000_000_000_000, 1_700_000_001_000_000))
+        );
+        // Anything after the header is ignored
+        let mut longer = packet.to_vec();
+        longer.extend_from_slice(&[0; 20]);
+        assert!(parse_reply(&longer, COOKIE).is_some());
+    }
+
+    #[test]
+    fn parse_rejects_replies() {
+        let packet = reply(ntp(1_700_000_000, 0), ntp(1_700_000_001, 0));
+        assert_eq!(parse_reply(&packet[..PACKET_LEN - 1], COOKIE), None);
+        assert_eq!(parse_reply(&packet, COOKIE + 1), None);
+
+        let mut client = packet;
+        client[0] = VERSION << 3 | MODE_CLIENT;
+        assert_eq!(parse_reply(&client, COOKIE), None);
+
+        let mut unsynchronized = packet;
+        unsynchronized[0] |= LEAP_UNSYNCHRONIZED << 6;
+        assert_eq!(parse_reply(&unsynchronized, COOKIE), None);
+
+        let mut kiss_of_death = packet;
+        kiss_of_death[1] = 0;
+        assert_eq!(parse_reply(&kiss_of_death, COOKIE), None);
+    }
+}
@@ -0,0 +1,338 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+#![no_std]
+
+use core::ptr;
+use sel4_microkit::{with_msg_regs, with_msg_regs_mut, Channel, MessageInfo};
+
+// TCP and UDP sockets on `ping`'s network stack, for other PDs. Each client has its own channel
+// and data region. Operations are protected calls on the channel: the message label selects the
+// operation, message register 0 is the socket (apart from `OPEN` and `RESOLVE`), and replies
+// are labelled `OK` or with an `Error`. Payloads are passed in the data region, from its start.
+//
+// `ping` notifies the client on the same channel when any of its sockets' `SocketStatus` has
+// changed. Clients may only bind and listen on the ports `ping` has assigned them, and their
+// connections are made from those ports too.
+//
+// A listening TCP socket becomes the connection once a peer connects, so the client opens
+// another one to accept the next.
+
+/// Message register 0 is a `Protocol`. Replies with the new socket in message register 0.
+pub const OPEN: u64 = 0;
+/// UDP only. Message register 1 is the local port.
+pub const BIND: u64 = 1;
+/// TCP only. Message register 1 is the local port.
+pub const LISTEN: u64 = 2;
+/// TCP only. Message registers 1 to 4 are the remote `Endpoint`.
+pub const CONNECT: u64 = 3;
+/// Message register 1 is the length of the payload.
+///
+/// For UDP, message registers 2 to 5 are the destination `Endpoint`, and an unbound socket is
+/// bound to one of the client's ports. Replies with how much was taken in message register 0,
+/// which for TCP can be less than all of it.
+pub const SEND: u64 = 4;
+/// Message register 1 is the most to receive.
+///
+/// Replies with the length of the payload in message register 0, and for UDP the sender's
+/// `Endpoint` in message registers 1 to 4. For TCP, a length of 0 means the peer has closed its
+/// side and everything has been received.
+pub const RECV: u64 = 5;
+/// The socket can't be used afterwards. TCP connections are closed gracefully.
+pub const CLOSE: u64 = 6;
+/// Replies with a `SocketStatus` in message register 0
+pub const STATUS: u64 = 7;
+/// Looks up the name in the data region, message register 0 being its length. Replies with the
+/// number of addresses in message register 0, at most `MAX_ADDRS`, followed by each as an
+/// `Addr`.
+///
+/// Fails with `WouldBlock` while the lookup is in progress, and `ping` notifies the client once
+/// it is worth calling again.
+pub const RESOLVE: u64 = 8;
+
+/// The most addresses `RESOLVE` replies with
+pub const MAX_ADDRS: usize = 4;
+
+pub const OK: u64 = 0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// Unknown operation or socket, or the wrong protocol or state for it
+    Invalid = 1,
+    /// The port isn't one of the client's
+    Permission = 2,
+    /// The client already has as many sockets as it is allowed
+    NoSockets = 3,
+    /// Nothing to receive, or no room to send
+    WouldBlock = 4,
+    /// The TCP socket has no connection
+    NotConnected = 5,
+    /// Another of the client's sockets has the port
+    AddrInUse = 6,
+    /// `ping` has no network, as the driver failed to initialize
+    NetDown = 7,
+    /// The name has no addresses, or its lookup failed
+    NotFound = 8,
+}
+
+impl Error {
+    pub fn label(self) -> u64 {
+        self as u64
+    }
+
+    pub fn from_label(label: u64) -> Self {
+        match label {
+            2 => Self::Permission,
+            3 => Self::NoSockets,
+            4 => Self::WouldBlock,
+            5 => Self::NotConnected,
+            6 => Self::AddrInUse,
+            7 => Self::NetDown,
+            8 => Self::NotFound,
+            _ => Self::Invalid,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    Tcp = 0,
+    Udp = 1,
+}
+
+impl Protocol {
+    pub fn from_word(word: u64) -> Option<Self> {
+        match word {
+            0 => Some(Self::Tcp),
+            1 => Some(Self::Udp),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Addr {
+    V4([u8; 4]),
+    V6([u8; 16]),
+}
+
+impl Addr {
+    pub const WORDS: usize = 3;
+
+    /// The IP version, then the address as a big-endian 128-bit number split in two
+    pub fn to_words(&self) -> [u64; Self::WORDS] {
+        let (version, addr) = match *self {
+            Self::V4(addr) => (4, u32::from_be_bytes(addr) as u128),
+            Self::V6(addr) => (6, u128::from_be_bytes(addr)),
+        };
+        [version, (addr >> 64) as u64, addr as u64]
+    }
+
+    pub fn from_words(words: &[u64]) -> Option<Self> {
+        let [version, high, low]: [u64; Self::WORDS] = words.get(..Self::WORDS)?.try_into().ok()?;
+        let addr = (high as u128) << 64 | low as u128;
+        match version {
+            4 => Some(Self::V4(u32::try_from(addr).ok()?.to_be_bytes())),
+            6 => Some(Self::V6(addr.to_be_bytes())),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Endpoint {
+    pub addr: Addr,
+    pub port: u16,
+}
+
+impl Endpoint {
+    pub const WORDS: usize = Addr::WORDS + 1;
+
+    /// The `Addr`, then the port
+    pub fn to_words(&self) -> [u64; Self::WORDS] {
+        let [version, high, low] = self.addr.to_words();
+        [version, high, low, self.port as u64]
+    }
+
+    pub fn from_words(words: &[u64]) -> Option<Self> {
+        let port = *words.get(Addr::WORDS..Self::WORDS)?.first()?;
+        Some(Self {
+            addr: Addr::from_words(words)?,
+            port: port.try_into().ok()?,
+        })
+    }
+}
+
+/// What a socket is ready for, as returned by `STATUS`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SocketStatus {
+    /// TCP: a connection is established. UDP: bound.
+    pub active: bool,
+    pub can_recv: bool,
+    pub can_send: bool,
+    /// TCP: the peer has closed its side, or the connection has gone
+    pub remote_closed: bool,
+}
+
+impl SocketStatus {
+    const ACTIVE: u64 = 1 << 0;
+    const CAN_RECV: u64 = 1 << 1;
+    const CAN_SEND: u64 = 1 << 2;
+    const REMOTE_CLOSED: u64 = 1 << 3;
+
+    pub fn to_word(&self) -> u64 {
+        [
+            (self.active, Self::ACTIVE),
+            (self.can_recv, Self::CAN_RECV),
+            (self.can_send, Self::CAN_SEND),
+            (self.remote_closed, Self::REMOTE_CLOSED),
+        ]
+        .into_iter()
+        .filter(|(set, _)| *set)
+        .fold(0, |word, (_, bit)| word | bit)
+    }
+
+    pub fn from_word(word: u64) -> Self {
+        Self {
+            active: word & Self::ACTIVE != 0,
+            can_recv: word & Self::CAN_RECV != 0,
+            can_send: word & Self::CAN_SEND != 0,
+            remote_closed: word & Self::REMOTE_CLOSED != 0,
+        }
+    }
+}
+
+/// A socket, numbered per client
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Socket(pub u64);
+
+/// Calls to `ping` over `channel`, with payloads in the data region
+pub struct Client {
+    channel: Channel,
+    data: *mut u8,
+    data_len: usize,
+}
+
+impl Client {
+    /// # Safety
+    ///
+    /// `data` must be the client's data region, valid for `data_len` bytes and shared with
+    /// nothing but `ping` for as long as the `Client` is used.
+    pub unsafe fn new(channel: Channel, data: *mut u8, data_len: usize) -> Self {
+        Self {
+            channel,
+            data,
+            data_len,
+        }
+    }
+
+    pub fn channel(&self) -> Channel {
+        self.channel
+    }
+
+    fn call(&self, label: u64, args: &[u64]) -> Result<(), Error> {
+        with_msg_regs_mut(|mrs| mrs[..args.len()].copy_from_slice(args));
+        let reply = self.channel.pp_call(MessageInfo::new(label, args.len()));
+        match reply.label() {
+            OK => Ok(()),
+            label => Err(Error::from_label(label)),
+        }
+    }
+
+    pub fn open(&self, protocol: Protocol) -> Result<Socket, Error> {
+        self.call(OPEN, &[protocol as u64])?;
+        Ok(Socket(with_msg_regs(|mrs| mrs[0])))
+    }
+
+    pub fn bind(&self, socket: Socket, port: u16) -> Result<(), Error> {
+        self.call(BIND, &[socket.0, port as u64])
+    }
+
+    pub fn listen(&self, socket: Socket, port: u16) -> Result<(), Error> {
+        self.call(LISTEN, &[socket.0, port as u64])
+    }
+
+    pub fn connect(&self, socket: Socket, remote: Endpoint) -> Result<(), Error> {
+        let [a, b, c, d] = remote.to_words();
+        self.call(CONNECT, &[socket.0, a, b, c, d])
+    }
+
+    /// Returns how much was taken, at most the size of the data region
+    pub fn send(&self, socket: Socket, payload: &[u8]) -> Result<usize, Error> {
+        let len = self.copy_in(payload);
+        self.call(SEND, &[socket.0, len as u64])?;
+        Ok(with_msg_regs(|mrs| mrs[0]) as usize)
+    }
+
+    pub fn send_to(&self, socket: Socket, payload: &[u8], remote: Endpoint) -> Result<(), Error> {
+        if payload.len() > self.data_len {
+            return Err(Error::Invalid);
+        }
+        let len = self.copy_in(payload);
+        let [a, b, c, d] = remote.to_words();
+        self.call(SEND, &[socket.0, len as u64, a, b, c, d])
+    }
+
+    pub fn recv(&self, socket: Socket, buf: &mut [u8]) -> Result<usize, Error> {
+        let len = self.recv_call(socket, buf.len())?;
+        self.copy_out(&mut buf[..len]);
+        Ok(len)
+    }
+
+    pub fn recv_from(&self, socket: Socket, buf: &mut [u8]) -> Result<(usize, Endpoint), Error> {
+        let len = self.recv_call(socket, buf.len())?;
+        let remote = with_msg_regs(|mrs| Endpoint::from_words(&mrs[1..])).ok_or(Error::Invalid)?;
+        self.copy_out(&mut buf[..len]);
+        Ok((len, remote))
+    }
+
+    pub fn close(&self, socket: Socket) -> Result<(), Error> {
+        self.call(CLOSE, &[socket.0])
+    }
+
+    pub fn status(&self, socket: Socket) -> Result<SocketStatus, Error> {
+        self.call(STATUS, &[socket.0])?;
+        Ok(SocketStatus::from_word(with_msg_regs(|mrs| mrs[0])))
+    }
+
+    /// Fills `addrs` with the name's addresses, returning how many it filled. Fails with
+    /// `WouldBlock` until the lookup is done.
+    pub fn resolve(&self, name: &str, addrs: &mut [Addr]) -> Result<usize, Error> {
+        if name.len() > self.data_len {
+            return Err(Error::Invalid);
+        }
+        let len = self.copy_in(name.as_bytes());
+        self.call(RESOLVE, &[len as u64])?;
+        with_msg_regs(|mrs| {
+            let count = (mrs[0] as usize).min(MAX_ADDRS);
+            let mut found = 0;
+            for (addr, words) in addrs
+                .iter_mut()
+                .zip(mrs[1..].chunks(Addr::WORDS).take(count))
+            {
+                *addr = Addr::from_words(words).ok_or(Error::Invalid)?;
+                found += 1;
+            }
+            Ok(found)
+        })
+    }
+
+    fn recv_call(&self, socket: Socket, max_len: usize) -> Result<usize, Error> {
+        self.call(RECV, &[socket.0, max_len.min(self.data_len) as u64])?;
+        Ok(with_msg_regs(|mrs| mrs[0]) as usize)
+    }
+
+    fn copy_in(&self, payload: &[u8]) -> usize {
+        let len = payload.len().min(self.data_len);
+        unsafe { ptr::copy_nonoverlapping(payload.as_ptr(), self.data, len) };
+        len
+    }
+
+    fn copy_out(&self, buf: &mut [u8]) {
+        let len = buf.len().min(self.data_len);
+        unsafe { ptr::copy_nonoverlapping(self.data, buf.as_mut_ptr(), len) };
+    }
+}
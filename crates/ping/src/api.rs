@@ -0,0 +1,224 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+use crate::config;
+use crate::pinger::Summary;
+use crate::services::{Counters, UdpService};
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::{self, Write};
+use eth_driver_interface::{control, LogRecord, Status};
+use log::warn;
+use net_filter_interface::HITS;
+use sel4_microkit::{with_msg_regs, MessageInfo};
+use smoltcp::{
+    iface::Interface,
+    time::Instant,
+    wire::{EthernetAddress, IpAddress, IpCidr},
+};
+
+// The JSON served over HTTP. Fields the driver couldn't provide are null.
+
+/// Shows a MAC address colon separated, rather than with smoltcp's dashes
+pub struct Mac(pub EthernetAddress);
+
+impl fmt::Display for Mac {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let b = self.0.as_bytes();
+        write!(
+            f,
+            "{:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}",
+            b[0], b[1], b[2], b[3], b[4], b[5]
+        )
+    }
+}
+
+/// The route table's destinations and routers. smoltcp only lets the table be read through
+/// `update`, which leaves it as it is here.
+pub fn routes(iface: &mut Interface) -> Vec<(IpCidr, IpAddress)> {
+    let mut routes = Vec::new();
+    iface.routes_mut().update(|table| {
+        routes.extend(table.iter().map(|route| (route.cidr, route.via_router)));
+    });
+    routes
+}
+
+/// The router of the default route, IPv4 first
+pub fn default_router(iface: &mut Interface) -> Option<IpAddress> {
+    routes(iface)
+        .into_iter()
+        .filter(|(cidr, _)| cidr.prefix_len() == 0)
+        .min_by_key(|(cidr, _)| matches!(cidr, IpCidr::Ipv6(_)))
+        .map(|(_, router)| router)
+}
+
+/// Ask the driver for its link status and counters over the control channel
+pub fn driver_status() -> Option<Status> {
+    let reply = config::channels::NET_CONTROL.pp_call(MessageInfo::new(control::STATUS, 0));
+    if reply.label() != control::OK || reply.count() < Status::WORDS {
+        warn!("Driver status request failed");
+        return None;
+    }
+    Some(with_msg_regs(|mrs| {
+        Status::from_words(mrs[..Status::WORDS].try_into().unwrap())
+    }))
+}
+
+/// Take the oldest of the driver's log records over the control channel
+pub fn driver_log() -> Option<LogRecord> {
+    let reply = config::channels::NET_CONTROL.pp_call(MessageInfo::new(control::LOG, 0));
+    if reply.label() != control::OK || reply.count() == 0 {
+        return None;
+    }
+    with_msg_regs(|mrs| LogRecord::from_words(&mrs[..reply.count()]))
+}
+
+/// How many received frames each of the packet filter's rules matched, and how many got its
+/// default policy
+pub struct FilterHits {
+    pub rules: Vec<u64>,
+    pub default: u64,
+}
+
+/// Ask the packet filter for its hit counters over its stats channel
+pub fn filter_hits() -> Option<FilterHits> {
+    let reply = config::channels::NET_FILTER.pp_call(MessageInfo::new(HITS, 0));
+    let hits = with_msg_regs(|mrs| {
+        let (rules, default) = net_filter_interface::split_hits(&mrs[..reply.count()])?;
+        Some(FilterHits {
+            rules: rules.into(),
+            default,
+        })
+    });
+    if reply.label() != net_filter_interface::OK || hits.is_none() {
+        warn!("Packet filter hits request failed");
+        return None;
+    }
+    hits
+}
+
+/// `GET /status`
+pub fn status(
+    now: Instant,
+    iface: &Interface,
+    mac: EthernetAddress,
+    driver: Option<&Status>,
+    uplink: Option<&Summary>,
+) -> String {
+    let mut json = String::new();
+    write!(json, "{{\"mac\":\"{}\"", Mac(mac)).unwrap();
+
+    let addrs = |version: fn(&IpCidr) -> bool| {
+        let mut list = String::new();
+        for (i, addr) in iface
+            .ip_addrs()
+            .iter()
+            .filter(|addr| version(addr))
+            .enumerate()
+        {
+            let sep = if i == 0 { "" } else { "," };
+            write!(list, "{sep}\"{addr}\"").unwrap();
+        }
+        list
+    };
+    write!(
+        json,
+        ",\"ipv4\":[{}],\"ipv6\":[{}]",
+        addrs(|addr| matches!(addr, IpCidr::Ipv4(_))),
+        addrs(|addr| matches!(addr, IpCidr::Ipv6(_)))
+    )
+    .unwrap();
+
+    match driver {
+        Some(status) => write!(
+            json,
+            ",\"link\":{{\"speed_mbps\":{},\"duplex\":\"{}\",\"running\":{}}}",
+            status.speed_mbps,
+            if status.full_duplex { "full" } else { "half" },
+            status.running
+        ),
+        None => write!(json, ",\"link\":null"),
+    }
+    .unwrap();
+    match uplink {
+        Some(uplink) => {
+            write!(
+                json,
+                ",\"uplink\":{{\"target\":\"{}\",\"transmitted\":{},\"received\":{},\"rtt_avg_us\":",
+                uplink.target, uplink.transmitted, uplink.received
+            )
+            .unwrap();
+            match uplink.rtt {
+                Some(rtt) => write!(json, "{}}}", rtt.avg_us),
+                None => write!(json, "null}}"),
+            }
+        }
+        None => write!(json, ",\"uplink\":null"),
+    }
+    .unwrap();
+    write!(json, ",\"uptime_s\":{}}}", now.secs()).unwrap();
+    json
+}
+
+/// `GET /stats`
+pub fn stats(
+    driver: Option<&Status>,
+    filter: Option<&FilterHits>,
+    tcp_echo_connections: usize,
+    udp_services: &[UdpService],
+    http_requests: u64,
+    syslog_sent: u64,
+) -> String {
+    let mut json = String::new();
+    match driver {
+        Some(status) => write!(
+            json,
+            "{{\"driver\":{{\"rx_frames\":{},\"rx_bytes\":{},\"rx_errors\":{},\
+             \"tx_frames\":{},\"tx_bytes\":{},\"tx_dropped\":{}}}",
+            status.rx_frames,
+            status.rx_bytes,
+            status.rx_errors,
+            status.tx_frames,
+            status.tx_bytes,
+            status.tx_dropped
+        ),
+        None => write!(json, "{{\"driver\":null"),
+    }
+    .unwrap();
+
+    match filter {
+        Some(hits) => {
+            json.push_str(",\"filter\":{\"rules\":[");
+            for (i, rule) in hits.rules.iter().enumerate() {
+                write!(json, "{}{rule}", if i > 0 { "," } else { "" }).unwrap();
+            }
+            write!(json, "],\"default\":{}}}", hits.default).unwrap();
+        }
+        None => json.push_str(",\"filter\":null"),
+    }
+
+    write!(
+        json,
+        ",\"tcp_echo\":{{\"connections\":{tcp_echo_connections}}}"
+    )
+    .unwrap();
+    for service in udp_services {
+        let Counters {
+            packets,
+            bytes,
+            dropped,
+        } = service.counters();
+        write!(
+            json,
+            ",\"udp_{}\":{{\"packets\":{packets},\"bytes\":{bytes},\"dropped\":{dropped}}}",
+            service.mode().name()
+        )
+        .unwrap();
+    }
+    write!(json, ",\"http\":{{\"requests\":{http_requests}}}").unwrap();
+    write!(json, ",\"syslog\":{{\"sent\":{syslog_sent}}}}}").unwrap();
+    json
+}
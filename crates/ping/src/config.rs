@@ -4,36 +4,171 @@
 // SPDX-License-Identifier: BSD-2-Clause
 //
 
+// The `network`, `sntp` and `syslog` defaults can be overridden per board from the
+// `.net_config` section of the image, see `settings`
+
 pub mod network {
-    use smoltcp::wire::{IpAddress, Ipv4Address};
+    use smoltcp::time::Duration;
+    use smoltcp::wire::{Ipv4Address, Ipv4Cidr};
+
+    /// Get the address from a DHCP server rather than using the static configuration below
+    pub const DHCP: bool = true;
+    /// How long to wait for a DHCP server before using the static configuration
+    pub const DHCP_TIMEOUT: Duration = Duration::from_secs(10);
 
-    pub const IP: IpAddress = IpAddress::v4(192, 168, 60, 146);
+    pub const STATIC_CIDR: Ipv4Cidr = Ipv4Cidr::new(Ipv4Address::new(192, 168, 60, 146), 24);
     pub const GATEWAY: Ipv4Address = Ipv4Address::new(192, 168, 60, 158);
+
+    /// Used when DHCP doesn't provide any
+    pub const DNS_SERVERS: &[Ipv4Address] = &[GATEWAY];
+    /// The longest answers are cached, however long their records' TTLs
+    pub const DNS_MAX_CACHE_TTL: Duration = Duration::from_secs(300);
+    /// How long names that failed to resolve are cached
+    pub const DNS_NEGATIVE_TTL: Duration = Duration::from_secs(30);
+}
+
+pub mod services {
+    use smoltcp::time::Duration;
+
+    pub const TCP_ECHO_PORT: u16 = 7;
+    /// Number of clients the TCP echo service can serve at once
+    pub const TCP_ECHO_CONNECTIONS: usize = 4;
+
+    /// Idle connections are probed this often
+    pub const TCP_KEEP_ALIVE: Duration = Duration::from_secs(30);
+    /// Connections are dropped when the peer hasn't answered for this long
+    pub const TCP_TIMEOUT: Duration = Duration::from_secs(90);
+
+    pub const UDP_ECHO_PORT: u16 = 7;
+    pub const UDP_DISCARD_PORT: u16 = 9;
+    /// Payload bytes each UDP socket can buffer in each direction, which also bounds the
+    /// largest datagram
+    pub const UDP_BUF_SIZE: usize = 16 * 1024;
+    /// Datagrams each UDP socket can buffer in each direction
+    pub const UDP_PACKETS: usize = 16;
+
+    pub const HTTP_PORT: u16 = 80;
+    /// Number of HTTP requests that can be in progress at once
+    pub const HTTP_CONNECTIONS: usize = 2;
+
+    pub const SHELL_PORT: u16 = 23;
+    /// The shell is off without a password. Usually set per board in `NET_CONFIG` instead.
+    pub const SHELL_PASSWORD: Option<&str> = None;
+    /// Echo requests the shell's `ping` sends when not given a count
+    pub const SHELL_PING_COUNT: u16 = 4;
+
+    /// How often the services' counters are logged
+    pub const STATS_INTERVAL: Duration = Duration::from_secs(60);
+}
+
+pub mod uplink {
+    use smoltcp::time::Duration;
+
+    /// A name or an address to ping, or `None` for the default router
+    pub const TARGET: Option<&str> = None;
+    /// How often the target is pinged
+    pub const INTERVAL: Duration = Duration::from_secs(60);
+    /// Echo requests sent each time
+    pub const COUNT: u16 = 3;
+}
+
+pub mod sntp {
+    use smoltcp::time::Duration;
+
+    /// A name or an address
+    pub const SERVER: &str = "pool.ntp.org";
+    /// How often to ask the server for the time once it has answered
+    pub const INTERVAL: Duration = Duration::from_secs(600);
+    /// How long to wait before asking again after a failure
+    pub const RETRY_INTERVAL: Duration = Duration::from_secs(30);
+    /// How long to wait for an answer
+    pub const TIMEOUT: Duration = Duration::from_secs(5);
+}
+
+pub mod syslog {
+    use smoltcp::time::Duration;
+
+    /// A name or an address, or `None` to only log to the serial console
+    pub const COLLECTOR: Option<&str> = None;
+    /// local0
+    pub const FACILITY: u8 = 16;
+    /// APP-NAME for the records passed on from the driver
+    pub const DRIVER_APP_NAME: &str = "eth_driver";
+    /// Records held while the collector can't be reached, after which the oldest are dropped
+    pub const QUEUE_LEN: usize = 128;
+    /// Messages sent a second, once a burst of `BURST` has been used up
+    pub const RATE: u32 = 20;
+    pub const BURST: u32 = 40;
+    /// How long to wait before resolving the collector again after a failure
+    pub const RETRY_INTERVAL: Duration = Duration::from_secs(30);
+}
+
+pub mod socket_server {
+    use core::ops::RangeInclusive;
+    use sel4_microkit::Channel;
+
+    /// A PD using sockets on `ping`'s stack, see `ping_interface`
+    pub struct ClientConfig {
+        pub channel: Channel,
+        /// The ports it may bind and listen on, and make connections from. They should be
+        /// clear of `ping`'s own services and of each other client's. `net_filter` only lets
+        /// UDP replies in to 49152 and above.
+        pub ports: RangeInclusive<u16>,
+    }
+
+    /// In the order of their data regions in `main`
+    pub const CLIENTS: [ClientConfig; 1] = [ClientConfig {
+        channel: Channel::new(3),
+        ports: 13..=13,
+    }];
+
+    /// Sockets each client can have open at once
+    pub const MAX_SOCKETS: usize = 4;
+    pub const TCP_BUF_SIZE: usize = 8 * 1024;
+    pub const UDP_BUF_SIZE: usize = 8 * 1024;
+    pub const UDP_PACKETS: usize = 8;
 }
 
 pub mod channels {
     use sel4_microkit::Channel;
 
+    /// The network virtualiser, which looks to `ping` just like the driver, as does the packet
+    /// filter in front of it
     pub const NET_DEV: Channel = Channel::new(0);
+    /// See `eth_driver_interface::control`
+    pub const NET_CONTROL: Channel = Channel::new(1);
+    pub const TIMER: Channel = Channel::new(2);
+    /// See `net_filter_interface`
+    pub const NET_FILTER: Channel = Channel::new(4);
 }
 
 pub mod sizes {
+    #[cfg(not(feature = "jumbo"))]
     pub const NET_CLIENT_DMA: usize = 0x20_0000;
+    #[cfg(feature = "jumbo")]
+    pub const NET_CLIENT_DMA: usize = 0x40_0000;
+    /// The size of the `.net_config` section, which support/net-config.py reads from the image
+    pub const NET_CONFIG: usize = 0x1000;
+    /// Each socket client's data region
+    pub const SOCKET_CLIENT_DATA: usize = 0x1_0000;
 }
 
 pub mod log {
     use sel4_logging::{LevelFilter, Logger, LoggerBuilder};
     use sel4_microkit::debug_print;
 
-    const LOG_LEVEL: LevelFilter = {
+    /// The level at boot, the shell's `log` command can change it
+    pub const LOG_LEVEL: LevelFilter = {
         // LevelFilter::Trace
         // LevelFilter::Debug
         LevelFilter::Info
         // LevelFilter::Warn
     };
 
+    // Filtering is left to `log::max_level`, which can be changed at runtime. This only writes
+    // to the serial console, `syslog::LOGGER` passes records on to it.
     pub static LOGGER: Logger = LoggerBuilder::const_default()
-        .level_filter(LOG_LEVEL)
+        .level_filter(LevelFilter::Trace)
         .write(|s| debug_print!("{}", s))
         .build();
 }
@@ -0,0 +1,101 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+use crate::config;
+use alloc::vec::Vec;
+use log::{info, warn};
+use ping_core::settings::StaticIpv4;
+use smoltcp::{
+    iface::{Interface, SocketHandle, SocketSet},
+    socket::dhcpv4,
+    time::Instant,
+    wire::{IpCidr, Ipv4Address, Ipv4Cidr},
+};
+
+// Gets the interface's IPv4 address, default route and DNS servers from a DHCP server. The
+// socket renews the lease by itself. Until a server answers, and after a lease is lost, the
+// static configuration is used once `DHCP_TIMEOUT` has passed.
+
+pub struct Dhcp {
+    handle: SocketHandle,
+    // When to give up waiting for a lease and use the static configuration
+    fallback_at: Option<Instant>,
+    static_ipv4: StaticIpv4,
+    dns_servers: Vec<Ipv4Address>,
+}
+
+impl Dhcp {
+    pub fn new(sockets: &mut SocketSet<'_>, now: Instant, static_ipv4: StaticIpv4) -> Self {
+        Self {
+            handle: sockets.add(dhcpv4::Socket::new()),
+            fallback_at: Some(now + config::network::DHCP_TIMEOUT),
+            static_ipv4,
+            dns_servers: Vec::new(),
+        }
+    }
+
+    pub fn poll(&mut self, now: Instant, iface: &mut Interface, sockets: &mut SocketSet<'_>) {
+        match sockets.get_mut::<dhcpv4::Socket>(self.handle).poll() {
+            Some(dhcpv4::Event::Configured(lease)) => {
+                info!(
+                    "DHCP lease from {}: {}, router: {:?}",
+                    lease.server.address, lease.address, lease.router
+                );
+                set_ipv4_config(iface, Some(lease.address), lease.router);
+                self.dns_servers = lease.dns_servers.iter().copied().collect();
+                self.fallback_at = None;
+            }
+            Some(dhcpv4::Event::Deconfigured) => {
+                warn!("DHCP lease lost");
+                set_ipv4_config(iface, None, None);
+                self.dns_servers.clear();
+                self.fallback_at = Some(now + config::network::DHCP_TIMEOUT);
+            }
+            None => {}
+        }
+
+        if self.fallback_at.is_some_and(|at| at <= now) {
+            warn!(
+                "No DHCP server answered, using static address {}",
+                self.static_ipv4.cidr
+            );
+            set_static_config(iface, self.static_ipv4);
+            self.fallback_at = None;
+        }
+    }
+
+    /// When `poll` next needs to be called, on top of what the sockets need
+    pub fn poll_at(&self) -> Option<Instant> {
+        self.fallback_at
+    }
+
+    /// From the current lease, empty without one
+    pub fn dns_servers(&self) -> &[Ipv4Address] {
+        &self.dns_servers
+    }
+}
+
+pub fn set_static_config(iface: &mut Interface, static_ipv4: StaticIpv4) {
+    set_ipv4_config(iface, Some(static_ipv4.cidr), Some(static_ipv4.gateway));
+}
+
+// Replace the interface's IPv4 address and default route, leaving anything else alone
+fn set_ipv4_config(iface: &mut Interface, cidr: Option<Ipv4Cidr>, router: Option<Ipv4Address>) {
+    iface.update_ip_addrs(|addrs| {
+        addrs.retain(|addr| !matches!(addr, IpCidr::Ipv4(_)));
+        if let Some(cidr) = cidr {
+            addrs.push(IpCidr::Ipv4(cidr)).unwrap();
+        }
+    });
+    match router {
+        Some(router) => {
+            iface.routes_mut().add_default_ipv4_route(router).unwrap();
+        }
+        None => {
+            iface.routes_mut().remove_default_ipv4_route();
+        }
+    }
+}
@@ -0,0 +1,282 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+use crate::config;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::net::IpAddr;
+use log::{debug, info, warn};
+use ping_core::dns::{self, RecordType, Reply};
+use smoltcp::{
+    iface::{SocketHandle, SocketSet},
+    socket::udp,
+    time::{Duration, Instant},
+    wire::{IpAddress, IpEndpoint, Ipv4Address, Ipv6Address},
+};
+
+// Stub resolver over UDP, asking for A and AAAA records together, with the messages themselves
+// in `ping_core::dns`. Servers come from the DHCP lease when there is one, and from the
+// configured defaults otherwise.
+//
+// Answers are cached for the smallest TTL among their records, but no longer than
+// `DNS_MAX_CACHE_TTL`, and failures for `DNS_NEGATIVE_TTL`.
+
+const MAX_SERVERS: usize = 2;
+// Names being resolved at once
+const MAX_QUERIES: usize = 4;
+const CACHE_SIZE: usize = 16;
+// Unanswered queries are sent again this often, to each server in turn
+const RETRY: Duration = Duration::from_secs(1);
+const ATTEMPTS: usize = 4;
+// Long enough for whoever asked to pick up an answer with a TTL of 0
+const MIN_CACHE_TTL: Duration = Duration::from_secs(1);
+
+const RECORD_TYPES: [RecordType; 2] = [RecordType::A, RecordType::Aaaa];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Lookup {
+    /// Ask again once the stack has been polled
+    Pending,
+    Resolved(Vec<IpAddress>),
+    /// No server answered, or the name doesn't exist
+    Failed,
+}
+
+struct CacheEntry {
+    name: String,
+    // Empty for names that failed to resolve
+    addrs: Vec<IpAddress>,
+    expires: Instant,
+}
+
+struct Query {
+    name: String,
+    // The id of the query for each of `RECORD_TYPES`, cleared once it is answered
+    ids: [Option<u16>; 2],
+    addrs: Vec<IpAddress>,
+    // The smallest TTL of the answers with addresses
+    ttl: Option<Duration>,
+    attempts: usize,
+    // When the queries still unanswered are sent next
+    send_at: Instant,
+}
+
+impl Query {
+    fn done(&self) -> bool {
+        self.ids.iter().all(Option::is_none)
+    }
+
+    fn take_reply(&mut self, packet: &[u8]) {
+        for (id, record_type) in self.ids.iter_mut().zip(RECORD_TYPES) {
+            let Some(reply) = id.and_then(|id| dns::parse_reply(packet, id, record_type)) else {
+                continue;
+            };
+            *id = None;
+            match reply {
+                Reply::Answer { addrs, ttl } if !addrs.is_empty() => {
+                    self.addrs.extend(addrs.into_iter().map(from_ip_addr));
+                    let ttl = Duration::from_secs(ttl.into());
+                    self.ttl = Some(self.ttl.map_or(ttl, |old| old.min(ttl)));
+                }
+                Reply::Answer { .. } => {}
+                Reply::NameError => debug!("{} doesn't exist", self.name),
+                Reply::Failed => debug!("Server failed to resolve {}", self.name),
+            }
+        }
+    }
+}
+
+pub struct Resolver {
+    handle: SocketHandle,
+    servers: Vec<IpAddress>,
+    // Used when there are no others
+    default_servers: Vec<IpAddress>,
+    cache: Vec<CacheEntry>,
+    queries: Vec<Query>,
+    // Like the SNTP client's cookies, only needs to tell this resolver's queries apart
+    next_id: u16,
+}
+
+impl Resolver {
+    pub fn new(sockets: &mut SocketSet<'_>, default_servers: &[Ipv4Address], now: Instant) -> Self {
+        let packets = MAX_QUERIES * RECORD_TYPES.len();
+        let buffer = || {
+            udp::PacketBuffer::new(
+                vec![udp::PacketMetadata::EMPTY; packets],
+                vec![0; packets * dns::MAX_LEN],
+            )
+        };
+        let mut socket = udp::Socket::new(buffer(), buffer());
+        socket.bind(dns::LOCAL_PORT).unwrap();
+        let mut this = Self {
+            handle: sockets.add(socket),
+            servers: Vec::new(),
+            default_servers: default_servers
+                .iter()
+                .map(|&server| server.into())
+                .collect(),
+            cache: Vec::new(),
+            queries: Vec::new(),
+            next_id: now.total_micros() as u16,
+        };
+        this.set_servers(&[]);
+        this
+    }
+
+    /// Use `servers`, or the default ones if there are none
+    pub fn set_servers(&mut self, servers: &[IpAddress]) {
+        let servers: Vec<IpAddress> = if servers.is_empty() {
+            self.default_servers
+                .iter()
+                .copied()
+                .take(MAX_SERVERS)
+                .collect()
+        } else {
+            servers.iter().copied().take(MAX_SERVERS).collect()
+        };
+        if servers != self.servers {
+            info!("DNS servers: {servers:?}");
+            self.servers = servers;
+        }
+    }
+
+    /// Addresses for `name`, IPv4 first. Also takes address literals.
+    pub fn resolve(&mut self, now: Instant, sockets: &mut SocketSet<'_>, name: &str) -> Lookup {
+        if let Ok(addr) = name.parse::<IpAddress>() {
+            return Lookup::Resolved([addr].into());
+        }
+        let name = name.trim_end_matches('.').to_ascii_lowercase();
+        if let Some(entry) = self
+            .cache
+            .iter()
+            .find(|entry| entry.name == name && entry.expires > now)
+        {
+            return if entry.addrs.is_empty() {
+                Lookup::Failed
+            } else {
+                Lookup::Resolved(entry.addrs.clone())
+            };
+        }
+        // Try again once other queries have finished
+        if self.queries.len() == MAX_QUERIES || self.queries.iter().any(|query| query.name == name)
+        {
+            return Lookup::Pending;
+        }
+        if dns::query(0, &name, RecordType::A).is_none() {
+            debug!("Can't resolve {name}: not a valid name");
+            return Lookup::Failed;
+        }
+
+        debug!("Resolving {name}");
+        let ids = RECORD_TYPES.map(|_| {
+            self.next_id = self.next_id.wrapping_add(1);
+            Some(self.next_id)
+        });
+        self.queries.push(Query {
+            name,
+            ids,
+            addrs: Vec::new(),
+            ttl: None,
+            attempts: 0,
+            send_at: now,
+        });
+        self.send(now, sockets);
+        Lookup::Pending
+    }
+
+    /// Pick up the answers to queries in progress
+    pub fn poll(&mut self, now: Instant, sockets: &mut SocketSet<'_>) {
+        let socket = sockets.get_mut::<udp::Socket>(self.handle);
+        while let Ok((packet, meta)) = socket.recv() {
+            if meta.endpoint.port != dns::PORT || !self.servers.contains(&meta.endpoint.addr) {
+                continue;
+            }
+            for query in &mut self.queries {
+                query.take_reply(packet);
+            }
+        }
+        self.send(now, sockets);
+
+        let (done, pending) = self.queries.drain(..).partition::<Vec<_>, _>(Query::done);
+        self.queries = pending;
+        for mut query in done {
+            query
+                .addrs
+                .sort_by_key(|addr| matches!(addr, IpAddress::Ipv6(_)));
+            let ttl = match query.ttl {
+                Some(ttl) => {
+                    debug!("Resolved {}: {:?} for {ttl}", query.name, query.addrs);
+                    ttl.clamp(MIN_CACHE_TTL, config::network::DNS_MAX_CACHE_TTL)
+                }
+                None => {
+                    warn!("Failed to resolve {}", query.name);
+                    config::network::DNS_NEGATIVE_TTL
+                }
+            };
+            self.insert(CacheEntry {
+                name: query.name,
+                addrs: query.addrs,
+                expires: now + ttl,
+            });
+        }
+    }
+
+    /// When unanswered queries are next sent again
+    pub fn poll_at(&self) -> Option<Instant> {
+        self.queries
+            .iter()
+            .filter(|query| !query.done())
+            .map(|query| query.send_at)
+            .min()
+    }
+
+    // Send the queries that are due, or give up on them after the last attempt
+    fn send(&mut self, now: Instant, sockets: &mut SocketSet<'_>) {
+        let socket = sockets.get_mut::<udp::Socket>(self.handle);
+        for query in &mut self.queries {
+            if query.done() || query.send_at > now {
+                continue;
+            }
+            if query.attempts == ATTEMPTS || self.servers.is_empty() {
+                query.ids = [None; 2];
+                continue;
+            }
+            let server = self.servers[query.attempts % self.servers.len()];
+            for (id, record_type) in query.ids.iter().zip(RECORD_TYPES) {
+                let Some(id) = *id else {
+                    continue;
+                };
+                // The name was checked in `resolve`
+                let packet = dns::query(id, &query.name, record_type).unwrap();
+                if let Err(err) = socket.send_slice(&packet, IpEndpoint::new(server, dns::PORT)) {
+                    debug!("Failed to send DNS query to {server}: {err:?}");
+                }
+            }
+            query.attempts += 1;
+            query.send_at = now + RETRY;
+        }
+    }
+
+    fn insert(&mut self, entry: CacheEntry) {
+        self.cache.retain(|old| old.name != entry.name);
+        if self.cache.len() == CACHE_SIZE {
+            // Make room by forgetting the entry closest to expiring
+            let oldest = (0..self.cache.len())
+                .min_by_key(|&i| self.cache[i].expires)
+                .unwrap();
+            self.cache.swap_remove(oldest);
+        }
+        self.cache.push(entry);
+    }
+}
+
+fn from_ip_addr(addr: IpAddr) -> IpAddress {
+    match addr {
+        IpAddr::V4(addr) => Ipv4Address(addr.octets()).into(),
+        IpAddr::V6(addr) => Ipv6Address(addr.octets()).into(),
+    }
+}
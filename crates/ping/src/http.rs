@@ -0,0 +1,126 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+use crate::config;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use log::{debug, info, warn};
+use ping_core::http::{answer, error, parse_request, Parsed};
+use smoltcp::{
+    iface::{SocketHandle, SocketSet},
+    socket::tcp,
+};
+
+pub use ping_core::http::Resource;
+
+// Serves `ping_core::http` over smoltcp's TCP sockets
+
+const RX_BUF_SIZE: usize = 1024;
+const TX_BUF_SIZE: usize = 4096;
+
+struct Connection {
+    handle: SocketHandle,
+    // The request so far, until the end of its headers
+    request: Vec<u8>,
+    responded: bool,
+}
+
+pub struct HttpServer {
+    connections: Vec<Connection>,
+    // Requests answered since boot, including errors
+    requests: u64,
+}
+
+impl HttpServer {
+    pub fn new(sockets: &mut SocketSet<'_>) -> Self {
+        let connections = (0..config::services::HTTP_CONNECTIONS)
+            .map(|_| {
+                let mut socket = tcp::Socket::new(
+                    tcp::SocketBuffer::new(vec![0; RX_BUF_SIZE]),
+                    tcp::SocketBuffer::new(vec![0; TX_BUF_SIZE]),
+                );
+                socket.set_keep_alive(Some(config::services::TCP_KEEP_ALIVE));
+                socket.set_timeout(Some(config::services::TCP_TIMEOUT));
+                Connection {
+                    handle: sockets.add(socket),
+                    request: Vec::new(),
+                    responded: false,
+                }
+            })
+            .collect();
+        info!("HTTP on port {}", config::services::HTTP_PORT);
+        Self {
+            connections,
+            requests: 0,
+        }
+    }
+
+    /// `render` gives the JSON body for a resource, given the number of requests so far
+    pub fn poll(
+        &mut self,
+        sockets: &mut SocketSet<'_>,
+        mut render: impl FnMut(Resource, u64) -> String,
+    ) {
+        for conn in &mut self.connections {
+            let socket = sockets.get_mut::<tcp::Socket>(conn.handle);
+            if !socket.is_open() {
+                socket.listen(config::services::HTTP_PORT).unwrap();
+                conn.request.clear();
+                conn.responded = false;
+                continue;
+            }
+
+            // Anything after the request is dropped, the connection is closing anyway
+            while socket.can_recv() {
+                let request = &mut conn.request;
+                socket
+                    .recv(|data| {
+                        let len = data.len().min(RX_BUF_SIZE - request.len());
+                        request.extend_from_slice(&data[..len]);
+                        (data.len(), ())
+                    })
+                    .unwrap();
+            }
+            // Still listening or connecting, or already closing
+            if conn.responded || !socket.may_send() {
+                continue;
+            }
+
+            let response = match parse_request(&conn.request) {
+                Parsed::Incomplete if conn.request.len() < RX_BUF_SIZE && socket.may_recv() => {
+                    continue;
+                }
+                // The peer closed its side without asking for anything
+                Parsed::Incomplete if conn.request.is_empty() => {
+                    socket.close();
+                    conn.responded = true;
+                    continue;
+                }
+                Parsed::Incomplete | Parsed::Malformed => error(400, "Bad Request"),
+                Parsed::Request { method, path } => {
+                    self.requests += 1;
+                    debug!("HTTP {method} {path} from {:?}", socket.remote_endpoint());
+                    answer(method, path, |resource| render(resource, self.requests))
+                }
+            };
+            // Nothing has been sent on the connection yet, so only a response bigger than the
+            // whole buffer is cut short
+            match socket.send_slice(response.as_bytes()) {
+                Ok(sent) if sent < response.len() => {
+                    warn!(
+                        "HTTP response cut short at {sent} of {} bytes",
+                        response.len()
+                    )
+                }
+                Ok(_) => {}
+                Err(err) => warn!("Failed to send HTTP response: {err:?}"),
+            }
+            socket.close();
+            conn.responded = true;
+        }
+    }
+}
@@ -9,8 +9,11 @@
 
 extern crate alloc;
 
+use alloc::boxed::Box;
 use alloc::vec;
-use log::{debug, info};
+use alloc::vec::Vec;
+use eth_driver_interface::MTU;
+use log::{error, info, warn};
 use sel4_bounce_buffer_allocator::{Basic, BounceBufferAllocator};
 use sel4_driver_interfaces::net::GetNetDeviceMeta;
 use sel4_externally_shared::{ExternallySharedRef, ExternallySharedRefExt};
@@ -21,21 +24,55 @@ use sel4_microkit_driver_adapters::net::client::Client as NetClient;
 use sel4_shared_ring_buffer::RingBuffers;
 use sel4_shared_ring_buffer_smoltcp::DeviceImpl;
 use smoltcp::{
-    iface::{Config, Interface, SocketHandle, SocketSet},
+    iface::{Config, Interface, SocketSet},
     phy::{Device, DeviceCapabilities, Medium},
-    socket::icmp,
     time::Instant,
-    wire::{EthernetAddress, HardwareAddress, IpCidr},
+    wire::{EthernetAddress, HardwareAddress, IpAddress},
 };
+use timer_interface::Timer;
 
+mod api;
 mod config;
+mod dhcp;
+mod dns;
+mod http;
+mod neighbors;
+mod pinger;
+mod server;
+mod services;
+mod settings;
+mod shell;
+mod slaac;
+mod sntp;
+mod syslog;
+mod uplink;
+
+use dhcp::Dhcp;
+use dns::Resolver;
+use http::{HttpServer, Resource};
+use neighbors::LearningDevice;
+use server::SocketServer;
+use services::{TcpEcho, UdpMode, UdpService};
+use shell::Shell;
+use slaac::Slaac;
+use sntp::Sntp;
+use syslog::Syslog;
+use uplink::Uplink;
 
 #[protection_domain(
     heap_size = 16*1024*1024,
 )]
-fn init() -> HandlerImpl<'static> {
-    config::log::LOGGER.set().unwrap();
+fn init() -> Ping<'static> {
+    log::set_logger(&syslog::LOGGER).unwrap();
+    log::set_max_level(config::log::LOG_LEVEL);
+    let timer = Timer::new(config::channels::TIMER);
+    let settings = settings::load();
     let mut net_client = NetClient::new(config::channels::NET_DEV);
+    let Ok(mac_address) = net_client.get_mac_address() else {
+        error!("Driver unavailable, there is no network");
+        return Ping::NoNetwork;
+    };
+    let mac_address = EthernetAddress(mac_address.0);
     let notify_net: fn() = || config::channels::NET_DEV.notify();
 
     let mut net_device = {
@@ -63,97 +100,290 @@ fn init() -> HandlerImpl<'static> {
                 notify_net,
             ),
             128,
-            1600,
+            MTU,
             {
-                // TODO: Should this be queried from the driver via a protection call?
+                // Shared with the driver through eth-driver-interface so the two always agree
                 let mut caps = DeviceCapabilities::default();
-                caps.max_transmission_unit = 1600;
+                caps.max_transmission_unit = MTU;
                 caps
             },
         )
         .unwrap()
     };
+    // For the shell's `neigh`
+    let mut net_device = LearningDevice::new(net_device);
 
     let net_config = {
         assert_eq!(net_device.capabilities().medium, Medium::Ethernet);
-        let mac_address = EthernetAddress(net_client.get_mac_address().unwrap().0);
         let hardware_addr = HardwareAddress::Ethernet(mac_address);
         let mut this = Config::new(hardware_addr);
         this.random_seed = 0;
         this
     };
 
-    // TODO: Need a timer driver?
-    // let mut iface = Interface::new(net_config, &mut net_device, Instant::now());
-    let iface = {
-        let mut iface = Interface::new(net_config, &mut net_device, Instant::ZERO);
-        iface.update_ip_addrs(|ip_addrs| {
-            ip_addrs.push(IpCidr::new(config::network::IP, 24)).unwrap();
-        });
-        iface
-            .routes_mut()
-            .add_default_ipv4_route(config::network::GATEWAY)
-            .unwrap();
+    let timestamp = now(&timer);
+    let mut iface = {
+        let mut iface = Interface::new(net_config, &mut net_device, timestamp);
+        if !settings.dhcp {
+            dhcp::set_static_config(&mut iface, settings.static_ipv4);
+        }
         iface
     };
 
-    let (sockets, icmp_handle) = {
-        let icmp_rx_buffer =
-            icmp::PacketBuffer::new(vec![icmp::PacketMetadata::EMPTY], vec![0; 256]);
-        let icmp_tx_buffer =
-            icmp::PacketBuffer::new(vec![icmp::PacketMetadata::EMPTY], vec![0; 256]);
-        let icmp_socket = icmp::Socket::new(icmp_rx_buffer, icmp_tx_buffer);
-        let mut sockets = SocketSet::new(vec![]);
-        let icmp_handle = sockets.add(icmp_socket);
-        (sockets, icmp_handle)
-    };
+    // smoltcp answers echo requests itself, the ICMP sockets are only for sending them
+    let mut sockets = SocketSet::new(vec![]);
 
-    info!("Initialized Ping Server: {}", config::network::IP);
-    HandlerImpl {
+    let dhcp = settings
+        .dhcp
+        .then(|| Dhcp::new(&mut sockets, timestamp, settings.static_ipv4));
+    let slaac = Slaac::new(&mut sockets, &mut iface, mac_address, timestamp);
+    let resolver = Resolver::new(&mut sockets, &settings.dns_servers, timestamp);
+    let sntp = Sntp::new(&mut sockets, timer, settings.sntp_server, timestamp);
+    let syslog = Syslog::new(&mut sockets, settings.syslog_collector, timestamp);
+    let tcp_echo = TcpEcho::new(&mut sockets);
+    let http = HttpServer::new(&mut sockets);
+    let shell = Shell::new(&mut sockets, settings.shell_password);
+    let uplink = Uplink::new(timestamp);
+    let socket_server = SocketServer::new([memory_region_symbol!(
+        socket_client0_data_vaddr: *mut [u8],
+        n = config::sizes::SOCKET_CLIENT_DATA
+    )]);
+    let udp_services = [
+        UdpService::new(&mut sockets, UdpMode::Echo, config::services::UDP_ECHO_PORT),
+        UdpService::new(
+            &mut sockets,
+            UdpMode::Discard,
+            config::services::UDP_DISCARD_PORT,
+        ),
+    ];
+
+    // Poll straight away, to start DHCP and solicit IPv6 routers
+    timer.set_timeout(0).unwrap();
+
+    info!("Initialized Ping Server");
+    Ping::Running(Box::new(HandlerImpl {
         net_driver_channel: config::channels::NET_DEV,
+        timer,
         net_device,
+        mac_address,
         iface,
         sockets,
-        icmp_handle,
-    }
+        dhcp,
+        slaac,
+        resolver,
+        sntp,
+        syslog,
+        tcp_echo,
+        udp_services,
+        http,
+        shell,
+        uplink,
+        socket_server,
+        next_stats: timestamp + config::services::STATS_INTERVAL,
+    }))
+}
+
+enum Ping<'a> {
+    Running(Box<HandlerImpl<'a>>),
+    /// The driver failed to initialize. Socket clients' calls fail with `NetDown`.
+    NoNetwork,
+}
+
+fn now(timer: &Timer) -> Instant {
+    Instant::from_micros(timer.now_us().unwrap() as i64)
 }
 
 struct HandlerImpl<'a> {
     net_driver_channel: sel4_microkit::Channel,
-    net_device: DeviceImpl<Basic>,
+    timer: Timer,
+    net_device: LearningDevice<DeviceImpl<Basic>>,
+    mac_address: EthernetAddress,
     iface: Interface,
     sockets: SocketSet<'a>,
-    icmp_handle: SocketHandle,
+    dhcp: Option<Dhcp>,
+    slaac: Slaac,
+    resolver: Resolver,
+    sntp: Sntp,
+    syslog: Syslog,
+    tcp_echo: TcpEcho,
+    udp_services: [UdpService; 2],
+    http: HttpServer,
+    shell: Shell,
+    uplink: Uplink,
+    socket_server: SocketServer,
+    next_stats: Instant,
+}
+
+impl HandlerImpl<'_> {
+    fn poll(&mut self) {
+        let timestamp = now(&self.timer);
+        self.net_device.inner_mut().poll();
+        self.iface
+            .poll(timestamp, &mut self.net_device, &mut self.sockets);
+        if let Some(dhcp) = &mut self.dhcp {
+            dhcp.poll(timestamp, &mut self.iface, &mut self.sockets);
+            let servers: Vec<IpAddress> = dhcp
+                .dns_servers()
+                .iter()
+                .map(|&server| server.into())
+                .collect();
+            self.resolver.set_servers(&servers);
+        }
+        self.resolver.poll(timestamp, &mut self.sockets);
+        self.sntp
+            .poll(timestamp, &mut self.sockets, &mut self.resolver);
+        self.syslog.poll(
+            timestamp,
+            &mut self.iface,
+            &mut self.sockets,
+            &mut self.resolver,
+        );
+        self.slaac
+            .poll(timestamp, &mut self.iface, &mut self.sockets);
+        self.uplink.poll(
+            timestamp,
+            &mut self.iface,
+            &mut self.sockets,
+            &mut self.resolver,
+        );
+        self.tcp_echo.poll(&mut self.sockets);
+        for service in &mut self.udp_services {
+            service.poll(&mut self.sockets);
+        }
+        let tcp_echo_connections = self.tcp_echo.connections(&self.sockets);
+        let syslog_sent = self.syslog.sent();
+        self.http.poll(&mut self.sockets, |resource, http_requests| {
+            let driver = api::driver_status();
+            match resource {
+                Resource::Status => api::status(
+                    timestamp,
+                    &self.iface,
+                    self.mac_address,
+                    driver.as_ref(),
+                    self.uplink.last(),
+                ),
+                Resource::Stats => api::stats(
+                    driver.as_ref(),
+                    api::filter_hits().as_ref(),
+                    tcp_echo_connections,
+                    &self.udp_services,
+                    http_requests,
+                    syslog_sent,
+                ),
+            }
+        });
+        self.shell.poll(
+            &mut self.sockets,
+            shell::Context {
+                now: timestamp,
+                iface: &mut self.iface,
+                mac: self.mac_address,
+                neighbors: self.net_device.neighbors(),
+                resolver: &mut self.resolver,
+                sntp: &self.sntp,
+            },
+        );
+        if self.next_stats <= timestamp {
+            for service in &self.udp_services {
+                info!("UDP {:?}: {}", service.mode(), service.counters());
+            }
+            self.next_stats = timestamp + config::services::STATS_INTERVAL;
+        }
+        // Send whatever the services queued
+        self.iface
+            .poll(timestamp, &mut self.net_device, &mut self.sockets);
+        self.sntp.dispatched(timestamp, &self.sockets);
+        self.socket_server
+            .poll(timestamp, &mut self.sockets, &mut self.resolver);
+
+        // Come back when smoltcp next has something to do, such as a retransmit
+        let delay = self.iface.poll_delay(timestamp, &self.sockets);
+        let poll_at = [
+            self.dhcp.as_ref().and_then(Dhcp::poll_at),
+            self.slaac.poll_at(),
+            self.resolver.poll_at(),
+            self.shell.poll_at(),
+            self.sntp.poll_at(),
+            self.uplink.poll_at(),
+            self.syslog.poll_at(),
+            Some(self.next_stats),
+        ]
+        .into_iter()
+        .flatten()
+        .min();
+        match delay
+            .into_iter()
+            .chain(poll_at.map(|at| at - timestamp))
+            .min()
+        {
+            Some(delay) => self.timer.set_timeout(delay.total_micros()).unwrap(),
+            None => self.timer.cancel_timeout().unwrap(),
+        }
+    }
 }
 
 impl Handler for HandlerImpl<'_> {
     type Error = Infallible;
 
     fn notified(&mut self, channel: Channel) -> Result<(), Self::Error> {
-        if channel == self.net_driver_channel {
-            // Can the socket close? Should this be done in init or here?
-            {
-                let socket = self.sockets.get_mut::<icmp::Socket>(self.icmp_handle);
-                if !socket.is_open() {
-                    let ident = 0xb;
-                    debug!("Bind icmp socket: {ident}");
-                    socket.bind(icmp::Endpoint::Ident(ident)).unwrap();
-                }
+        if channel == config::channels::NET_CONTROL {
+            // The driver has log records for syslog
+            while let Some(record) = api::driver_log() {
+                syslog::LOGGER.forward(config::syslog::DRIVER_APP_NAME, &record);
             }
-            let timestamp = Instant::ZERO;
-            self.net_device.poll();
-            self.iface
-                .poll(timestamp, &mut self.net_device, &mut self.sockets);
+        }
+        if channel == self.net_driver_channel
+            || channel == self.timer.channel()
+            || channel == config::channels::NET_CONTROL
+        {
+            self.poll();
         }
         Ok(())
     }
 
     fn protected(
         &mut self,
-        _channel: Channel,
-        _msg_info: MessageInfo,
+        channel: Channel,
+        msg_info: MessageInfo,
     ) -> Result<MessageInfo, Self::Error> {
-        debug!("Shouldn't be in protected");
-        unreachable!()
+        let label = msg_info.label();
+        let Some(reply) = self.socket_server.call(
+            channel,
+            msg_info,
+            || now(&self.timer),
+            &mut self.iface,
+            &mut self.sockets,
+            &mut self.resolver,
+        ) else {
+            warn!("Call from unknown channel: {}", channel.index());
+            return Ok(MessageInfo::new(ping_interface::Error::Invalid.label(), 0));
+        };
+        // Send what the call queued, and let the client know what changed
+        if label != ping_interface::STATUS {
+            self.poll();
+        }
+        Ok(server::reply(reply))
+    }
+}
+
+impl Handler for Ping<'_> {
+    type Error = Infallible;
+
+    fn notified(&mut self, channel: Channel) -> Result<(), Self::Error> {
+        match self {
+            Self::Running(handler) => handler.notified(channel),
+            Self::NoNetwork => Ok(()),
+        }
+    }
+
+    fn protected(
+        &mut self,
+        channel: Channel,
+        msg_info: MessageInfo,
+    ) -> Result<MessageInfo, Self::Error> {
+        match self {
+            Self::Running(handler) => handler.protected(channel, msg_info),
+            Self::NoNetwork => Ok(MessageInfo::new(ping_interface::Error::NetDown.label(), 0)),
+        }
     }
 }
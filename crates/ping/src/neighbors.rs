@@ -0,0 +1,191 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+use alloc::vec::Vec;
+use smoltcp::{
+    phy::{self, Device, DeviceCapabilities},
+    time::{Duration, Instant},
+    wire::{
+        ArpPacket, ArpRepr, EthernetAddress, EthernetFrame, EthernetProtocol, Icmpv6Packet,
+        Icmpv6Repr, IpAddress, IpProtocol, Ipv6Address, Ipv6Packet, Ipv6Repr, NdiscRepr,
+        RawHardwareAddress,
+    },
+};
+
+// smoltcp keeps its neighbor cache to itself, so for showing it this keeps a copy, learnt from
+// the same ARP and neighbor discovery packets with the same entry lifetime. It can disagree
+// with smoltcp's when either cache is full.
+
+const CAPACITY: usize = 8;
+const ENTRY_LIFETIME: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Neighbor {
+    pub ip: IpAddress,
+    pub mac: EthernetAddress,
+    pub expires: Instant,
+}
+
+#[derive(Debug, Default)]
+pub struct Neighbors {
+    entries: Vec<Neighbor>,
+}
+
+impl Neighbors {
+    /// Entries that haven't expired at `now`
+    pub fn current(&self, now: Instant) -> impl Iterator<Item = &Neighbor> {
+        self.entries.iter().filter(move |entry| entry.expires > now)
+    }
+
+    fn fill(&mut self, now: Instant, ip: IpAddress, mac: EthernetAddress) {
+        if !mac.is_unicast() || ip.is_unspecified() {
+            return;
+        }
+        self.entries
+            .retain(|entry| entry.expires > now && entry.ip != ip);
+        if self.entries.len() == CAPACITY {
+            // Make room by forgetting the entry closest to expiring
+            let oldest = (0..self.entries.len())
+                .min_by_key(|&i| self.entries[i].expires)
+                .unwrap();
+            self.entries.swap_remove(oldest);
+        }
+        self.entries.push(Neighbor {
+            ip,
+            mac,
+            expires: now + ENTRY_LIFETIME,
+        });
+    }
+
+    fn learn(&mut self, now: Instant, frame: &[u8]) {
+        let Ok(frame) = EthernetFrame::new_checked(frame) else {
+            return;
+        };
+        match frame.ethertype() {
+            EthernetProtocol::Arp => {
+                if let Ok(ArpRepr::EthernetIpv4 {
+                    source_hardware_addr,
+                    source_protocol_addr,
+                    ..
+                }) = ArpPacket::new_checked(frame.payload())
+                    .and_then(|packet| ArpRepr::parse(&packet))
+                {
+                    self.fill(now, source_protocol_addr.into(), source_hardware_addr);
+                }
+            }
+            EthernetProtocol::Ipv6 => {
+                if let Some((ip, lladdr)) = ndisc_neighbor(frame.payload()) {
+                    if lladdr.len() == 6 {
+                        self.fill(
+                            now,
+                            ip.into(),
+                            EthernetAddress::from_bytes(lladdr.as_bytes()),
+                        );
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+// The address and link-layer address a neighbor solicitation or advertisement is about
+fn ndisc_neighbor(packet: &[u8]) -> Option<(Ipv6Address, RawHardwareAddress)> {
+    let packet = Ipv6Packet::new_checked(packet).ok()?;
+    let ipv6_repr = Ipv6Repr::parse(&packet).ok()?;
+    if ipv6_repr.next_header != IpProtocol::Icmpv6 {
+        return None;
+    }
+    let icmp_packet = Icmpv6Packet::new_checked(packet.payload()).ok()?;
+    let icmp_repr = Icmpv6Repr::parse(
+        &ipv6_repr.src_addr.into(),
+        &ipv6_repr.dst_addr.into(),
+        &icmp_packet,
+        &Default::default(),
+    )
+    .ok()?;
+    match icmp_repr {
+        Icmpv6Repr::Ndisc(NdiscRepr::NeighborSolicit {
+            lladdr: Some(lladdr),
+            ..
+        }) => Some((ipv6_repr.src_addr, lladdr)),
+        Icmpv6Repr::Ndisc(NdiscRepr::NeighborAdvert {
+            target_addr,
+            lladdr: Some(lladdr),
+            ..
+        }) => Some((target_addr, lladdr)),
+        _ => None,
+    }
+}
+
+/// Passes everything through to `inner`, learning neighbors from received frames on the way
+pub struct LearningDevice<D> {
+    inner: D,
+    neighbors: Neighbors,
+}
+
+impl<D> LearningDevice<D> {
+    pub fn new(inner: D) -> Self {
+        Self {
+            inner,
+            neighbors: Neighbors::default(),
+        }
+    }
+
+    pub fn inner_mut(&mut self) -> &mut D {
+        &mut self.inner
+    }
+
+    pub fn neighbors(&self) -> &Neighbors {
+        &self.neighbors
+    }
+}
+
+impl<D: Device> Device for LearningDevice<D> {
+    type RxToken<'a> = LearningRxToken<'a, D::RxToken<'a>> where Self: 'a;
+    type TxToken<'a> = D::TxToken<'a> where Self: 'a;
+
+    fn receive(&mut self, timestamp: Instant) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
+        let (rx, tx) = self.inner.receive(timestamp)?;
+        let rx = LearningRxToken {
+            inner: rx,
+            neighbors: &mut self.neighbors,
+            now: timestamp,
+        };
+        Some((rx, tx))
+    }
+
+    fn transmit(&mut self, timestamp: Instant) -> Option<Self::TxToken<'_>> {
+        self.inner.transmit(timestamp)
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        self.inner.capabilities()
+    }
+}
+
+pub struct LearningRxToken<'a, T> {
+    inner: T,
+    neighbors: &'a mut Neighbors,
+    now: Instant,
+}
+
+impl<T: phy::RxToken> phy::RxToken for LearningRxToken<'_, T> {
+    fn consume<R, F>(self, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        let Self {
+            inner,
+            neighbors,
+            now,
+        } = self;
+        inner.consume(|frame| {
+            neighbors.learn(now, frame);
+            f(frame)
+        })
+    }
+}
@@ -0,0 +1,348 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt::{self, Write};
+use smoltcp::{
+    iface::{Interface, SocketHandle, SocketSet},
+    phy::ChecksumCapabilities,
+    socket::icmp,
+    time::{Duration, Instant},
+    wire::{Icmpv4Packet, Icmpv4Repr, Icmpv6Packet, Icmpv6Repr, IpAddress, IpCidr, Ipv6Address},
+};
+
+// Sends ICMP echo requests to one host, a second apart, and reports the replies and round-trip
+// statistics like the Unix `ping`. Each run gets its own socket, bound to an identifier of its
+// own so replies to earlier runs are ignored, and replies are matched to requests by sequence
+// number.
+
+const INTERVAL: Duration = Duration::from_secs(1);
+/// How long to wait for a reply to the last request
+const TIMEOUT: Duration = Duration::from_secs(2);
+const PAYLOAD_LEN: usize = 56;
+
+struct Request {
+    sent_at: Instant,
+    replied: bool,
+}
+
+/// Round-trip times of the replies so far, in microseconds
+#[derive(Debug, Clone, Copy, Default)]
+struct Rtts {
+    count: u64,
+    min: u64,
+    max: u64,
+    sum: u64,
+    sum_of_squares: u128,
+}
+
+impl Rtts {
+    fn add(&mut self, rtt: u64) {
+        if self.count == 0 || rtt < self.min {
+            self.min = rtt;
+        }
+        self.max = self.max.max(rtt);
+        self.count += 1;
+        self.sum += rtt;
+        self.sum_of_squares += rtt as u128 * rtt as u128;
+    }
+
+    fn avg(&self) -> u64 {
+        self.sum / self.count
+    }
+
+    // Standard deviation, which the Unix `ping` calls mdev
+    fn mdev(&self) -> u64 {
+        let avg = self.avg() as u128;
+        let variance = (self.sum_of_squares / self.count as u128).saturating_sub(avg * avg);
+        isqrt(variance) as u64
+    }
+}
+
+fn isqrt(n: u128) -> u128 {
+    if n < 2 {
+        return n;
+    }
+    // Newton's method from above
+    let mut x = n;
+    let mut y = x.div_ceil(2);
+    while y < x {
+        x = y;
+        y = (x + n / x) / 2;
+    }
+    x
+}
+
+/// Shows microseconds as milliseconds
+pub struct Millis(pub u64);
+
+impl fmt::Display for Millis {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{:03}", self.0 / 1000, self.0 % 1000)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RttSummary {
+    pub min_us: u64,
+    pub avg_us: u64,
+    pub max_us: u64,
+    pub mdev_us: u64,
+}
+
+/// The outcome of a run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Summary {
+    pub target: IpAddress,
+    pub transmitted: u16,
+    pub received: u16,
+    pub duplicates: u16,
+    /// Only when there were replies
+    pub rtt: Option<RttSummary>,
+}
+
+impl Summary {
+    /// In percent, rounded down
+    pub fn loss(&self) -> u16 {
+        match self.transmitted {
+            0 => 0,
+            transmitted => ((transmitted - self.received) as u32 * 100 / transmitted as u32) as u16,
+        }
+    }
+}
+
+/// Shows the packet counts and loss
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} packets transmitted, {} received",
+            self.transmitted, self.received
+        )?;
+        if self.duplicates > 0 {
+            write!(f, ", +{} duplicates", self.duplicates)?;
+        }
+        write!(f, ", {}% packet loss", self.loss())
+    }
+}
+
+pub struct Pinger {
+    handle: SocketHandle,
+    target: IpAddress,
+    ident: u16,
+    count: u16,
+    // By sequence number
+    requests: Vec<Request>,
+    rtts: Rtts,
+    duplicates: u16,
+    next_send: Instant,
+}
+
+impl Pinger {
+    pub fn new(
+        sockets: &mut SocketSet<'_>,
+        target: IpAddress,
+        count: u16,
+        ident: u16,
+        now: Instant,
+        out: &mut String,
+    ) -> Self {
+        let mut socket = icmp::Socket::new(
+            icmp::PacketBuffer::new(vec![icmp::PacketMetadata::EMPTY; 4], vec![0; 1024]),
+            icmp::PacketBuffer::new(vec![icmp::PacketMetadata::EMPTY; 4], vec![0; 1024]),
+        );
+        socket.bind(icmp::Endpoint::Ident(ident)).unwrap();
+        writeln!(out, "PING {target}: {PAYLOAD_LEN} data bytes").unwrap();
+        Self {
+            handle: sockets.add(socket),
+            target,
+            ident,
+            count,
+            requests: Vec::new(),
+            rtts: Rtts::default(),
+            duplicates: 0,
+            next_send: now,
+        }
+    }
+
+    /// Returns whether the run has finished
+    pub fn poll(
+        &mut self,
+        now: Instant,
+        iface: &Interface,
+        sockets: &mut SocketSet<'_>,
+        out: &mut String,
+    ) -> bool {
+        let socket = sockets.get_mut::<icmp::Socket>(self.handle);
+        while let Ok((payload, from)) = socket.recv() {
+            if let Some((seq_no, len)) = self.parse_reply(payload) {
+                if let Some(request) = self.requests.get_mut(seq_no as usize) {
+                    let rtt = (now - request.sent_at).total_micros();
+                    let duplicate = request.replied;
+                    if duplicate {
+                        self.duplicates += 1;
+                    } else {
+                        request.replied = true;
+                        self.rtts.add(rtt);
+                    }
+                    writeln!(
+                        out,
+                        "{len} bytes from {from}: icmp_seq={seq_no} time={} ms{}",
+                        Millis(rtt),
+                        if duplicate { " (DUP!)" } else { "" }
+                    )
+                    .unwrap();
+                }
+            }
+        }
+
+        let sent = self.requests.len() as u16;
+        if sent < self.count && self.next_send <= now && socket.can_send() {
+            match self.send(iface, socket, sent) {
+                Ok(()) => {
+                    self.requests.push(Request {
+                        sent_at: now,
+                        replied: false,
+                    });
+                    self.next_send = now + INTERVAL;
+                }
+                Err(err) => {
+                    writeln!(out, "{err}").unwrap();
+                    return true;
+                }
+            }
+        }
+        self.requests.len() as u16 == self.count
+            && self
+                .requests
+                .last()
+                .is_some_and(|last| last.sent_at + TIMEOUT <= now)
+    }
+
+    /// When `poll` next needs to be called, on top of what the sockets need
+    pub fn poll_at(&self) -> Instant {
+        match self.requests.last() {
+            Some(last) if self.requests.len() as u16 == self.count => last.sent_at + TIMEOUT,
+            _ => self.next_send,
+        }
+    }
+
+    /// Close the socket and summarize the run
+    pub fn finish(self, sockets: &mut SocketSet<'_>, out: &mut String) -> Summary {
+        sockets.remove(self.handle);
+        let summary = Summary {
+            target: self.target,
+            transmitted: self.requests.len() as u16,
+            received: self.rtts.count as u16,
+            duplicates: self.duplicates,
+            rtt: (self.rtts.count > 0).then(|| RttSummary {
+                min_us: self.rtts.min,
+                avg_us: self.rtts.avg(),
+                max_us: self.rtts.max,
+                mdev_us: self.rtts.mdev(),
+            }),
+        };
+        writeln!(out, "--- {} ping statistics ---", self.target).unwrap();
+        writeln!(out, "{summary}").unwrap();
+        if let Some(rtt) = summary.rtt {
+            writeln!(
+                out,
+                "rtt min/avg/max/mdev = {}/{}/{}/{} ms",
+                Millis(rtt.min_us),
+                Millis(rtt.avg_us),
+                Millis(rtt.max_us),
+                Millis(rtt.mdev_us)
+            )
+            .unwrap();
+        }
+        summary
+    }
+
+    fn send(
+        &self,
+        iface: &Interface,
+        socket: &mut icmp::Socket<'_>,
+        seq_no: u16,
+    ) -> Result<(), &'static str> {
+        let data = [0xA5; PAYLOAD_LEN];
+        let caps = ChecksumCapabilities::default();
+        match self.target {
+            IpAddress::Ipv4(_) => {
+                let repr = Icmpv4Repr::EchoRequest {
+                    ident: self.ident,
+                    seq_no,
+                    data: &data,
+                };
+                let buf = socket
+                    .send(repr.buffer_len(), self.target)
+                    .map_err(|_| "Failed to send echo request")?;
+                repr.emit(&mut Icmpv4Packet::new_unchecked(buf), &caps);
+            }
+            IpAddress::Ipv6(target) => {
+                // The checksum covers the source address, which smoltcp only picks later
+                let src = source_ipv6(iface, &target).ok_or("No IPv6 address to send from")?;
+                let repr = Icmpv6Repr::EchoRequest {
+                    ident: self.ident,
+                    seq_no,
+                    data: &data,
+                };
+                let buf = socket
+                    .send(repr.buffer_len(), self.target)
+                    .map_err(|_| "Failed to send echo request")?;
+                repr.emit(
+                    &src.into(),
+                    &self.target,
+                    &mut Icmpv6Packet::new_unchecked(buf),
+                    &caps,
+                );
+            }
+        }
+        Ok(())
+    }
+
+    // The sequence number and length of an echo reply to this run. smoltcp has already
+    // checked the checksum.
+    fn parse_reply(&self, payload: &[u8]) -> Option<(u16, usize)> {
+        let caps = ChecksumCapabilities::ignored();
+        let (ident, seq_no) = match self.target {
+            IpAddress::Ipv4(_) => {
+                let packet = Icmpv4Packet::new_checked(payload).ok()?;
+                match Icmpv4Repr::parse(&packet, &caps).ok()? {
+                    Icmpv4Repr::EchoReply { ident, seq_no, .. } => (ident, seq_no),
+                    _ => return None,
+                }
+            }
+            IpAddress::Ipv6(_) => {
+                let packet = Icmpv6Packet::new_checked(payload).ok()?;
+                let unspecified = IpAddress::Ipv6(Ipv6Address::UNSPECIFIED);
+                match Icmpv6Repr::parse(&unspecified, &unspecified, &packet, &caps).ok()? {
+                    Icmpv6Repr::EchoReply { ident, seq_no, .. } => (ident, seq_no),
+                    _ => return None,
+                }
+            }
+        };
+        (ident == self.ident).then_some((seq_no, payload.len()))
+    }
+}
+
+// An address of the same scope as `target`, or any if there isn't one
+fn source_ipv6(iface: &Interface, target: &Ipv6Address) -> Option<Ipv6Address> {
+    let addrs: Vec<_> = iface
+        .ip_addrs()
+        .iter()
+        .filter_map(|cidr| match cidr {
+            IpCidr::Ipv6(cidr) => Some(cidr.address()),
+            _ => None,
+        })
+        .collect();
+    addrs
+        .iter()
+        .find(|addr| addr.is_link_local() == target.is_link_local())
+        .or(addrs.first())
+        .copied()
+}
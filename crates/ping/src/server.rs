@@ -0,0 +1,512 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+use crate::config;
+use crate::config::socket_server::{ClientConfig, CLIENTS};
+use crate::dns::{Lookup, Resolver};
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ptr::{self, NonNull};
+use log::{debug, info};
+use ping_core::call::Args;
+use ping_core::ports::{PortError, PortUse, Ports};
+use ping_interface::{self as api, Addr, Endpoint, Error, Protocol, SocketStatus};
+use sel4_microkit::{with_msg_regs, with_msg_regs_mut, Channel, MessageInfo};
+use smoltcp::{
+    iface::{Interface, SocketHandle, SocketSet},
+    socket::{tcp, udp},
+    time::Instant,
+    wire::{IpAddress, IpEndpoint, Ipv4Address, Ipv6Address},
+};
+
+// Sockets on this stack for other PDs, see `ping_interface`. Each client has a fixed number of
+// socket slots, numbered from 0, and the ports in its `ClientConfig`. Names are looked up with
+// the stack's `Resolver`, and a client whose lookup is pending is notified once it has finished.
+
+// The most message registers any call takes
+const MAX_ARGS: usize = 2 + Endpoint::WORDS;
+
+type Reply = Result<Vec<u64>, Error>;
+
+struct Slot {
+    handle: SocketHandle,
+    protocol: Protocol,
+    // Set by `BIND`, `LISTEN` and `CONNECT`, or by `SEND` for an unbound UDP socket
+    port: Option<u16>,
+    // As last notified
+    status: SocketStatus,
+}
+
+struct Client {
+    config: &'static ClientConfig,
+    data: NonNull<[u8]>,
+    slots: [Option<Slot>; config::socket_server::MAX_SOCKETS],
+    ports: Ports,
+    // The name `RESOLVE` is waiting for
+    resolving: Option<String>,
+}
+
+pub struct SocketServer {
+    clients: Vec<Client>,
+    // Closed by their clients, and removed once the connection has gone
+    closing: Vec<SocketHandle>,
+}
+
+impl SocketServer {
+    /// `data` has each client's data region, in the order of `CLIENTS`
+    pub fn new(data: [NonNull<[u8]>; CLIENTS.len()]) -> Self {
+        let clients = CLIENTS
+            .iter()
+            .zip(data)
+            .map(|(config, data)| {
+                info!(
+                    "Socket client on channel {}, ports {:?}",
+                    config.channel.index(),
+                    config.ports
+                );
+                Client {
+                    config,
+                    data,
+                    slots: Default::default(),
+                    ports: Ports::new(config.ports.clone()),
+                    resolving: None,
+                }
+            })
+            .collect();
+        Self {
+            clients,
+            closing: Vec::new(),
+        }
+    }
+
+    /// Carry out a protected call, or return `None` if `channel` isn't a client's. The reply's
+    /// message registers are only written by `reply`, so the stack can be polled in between.
+    /// `now` is only asked for the time once the arguments have been copied out of the message
+    /// registers, as it may overwrite them.
+    pub fn call(
+        &mut self,
+        channel: Channel,
+        msg_info: MessageInfo,
+        now: impl FnOnce() -> Instant,
+        iface: &mut Interface,
+        sockets: &mut SocketSet<'_>,
+        resolver: &mut Resolver,
+    ) -> Option<Reply> {
+        let client = self
+            .clients
+            .iter_mut()
+            .find(|client| client.config.channel == channel)?;
+        let args = with_msg_regs(|mrs| Args::<MAX_ARGS>::copy(mrs, msg_info.count()));
+        let args = &*args;
+
+        let reply = match msg_info.label() {
+            api::OPEN => client.open(sockets, args),
+            api::BIND => client.bind(sockets, args),
+            api::LISTEN => client.listen(sockets, args),
+            api::CONNECT => client.connect(iface, sockets, args),
+            api::SEND => client.send(sockets, args),
+            api::RECV => client.recv(sockets, args),
+            api::CLOSE => client.close(sockets, args).map(|closing| {
+                self.closing.extend(closing);
+                Vec::new()
+            }),
+            api::STATUS => client
+                .slot(args)
+                .map(|slot| vec![status(sockets, slot).to_word()]),
+            api::RESOLVE => client.resolve(now(), sockets, resolver, args),
+            _ => Err(Error::Invalid),
+        };
+        if let Err(err) = &reply {
+            debug!(
+                "Socket call {} from channel {} failed: {err:?}",
+                msg_info.label(),
+                channel.index()
+            );
+        }
+        Some(reply)
+    }
+
+    /// Free the sockets whose connections have gone, and tell clients about changes to theirs
+    /// and about finished lookups
+    pub fn poll(&mut self, now: Instant, sockets: &mut SocketSet<'_>, resolver: &mut Resolver) {
+        self.closing.retain(|&handle| {
+            let socket = sockets.get::<tcp::Socket>(handle);
+            let done = matches!(socket.state(), tcp::State::Closed | tcp::State::TimeWait);
+            if done {
+                sockets.remove(handle);
+            }
+            !done
+        });
+
+        for client in &mut self.clients {
+            let mut changed = false;
+            for slot in client.slots.iter_mut().flatten() {
+                let status = status(sockets, slot);
+                changed |= status != slot.status;
+                slot.status = status;
+            }
+            if let Some(name) = &client.resolving {
+                if resolver.resolve(now, sockets, name) != Lookup::Pending {
+                    client.resolving = None;
+                    changed = true;
+                }
+            }
+            if changed {
+                client.config.channel.notify();
+            }
+        }
+    }
+}
+
+/// Labels the reply and fills in its message registers
+pub fn reply(reply: Reply) -> MessageInfo {
+    match reply {
+        Ok(words) => {
+            with_msg_regs_mut(|mrs| mrs[..words.len()].copy_from_slice(&words));
+            MessageInfo::new(api::OK, words.len())
+        }
+        Err(err) => MessageInfo::new(err.label(), 0),
+    }
+}
+
+impl Client {
+    fn slot(&self, args: &[u64]) -> Result<&Slot, Error> {
+        let index = *args.first().ok_or(Error::Invalid)?;
+        self.slots
+            .get(index as usize)
+            .and_then(Option::as_ref)
+            .ok_or(Error::Invalid)
+    }
+
+    fn slot_mut(&mut self, args: &[u64], protocol: Protocol) -> Result<&mut Slot, Error> {
+        let index = *args.first().ok_or(Error::Invalid)?;
+        self.slots
+            .get_mut(index as usize)
+            .and_then(Option::as_mut)
+            .filter(|slot| slot.protocol == protocol)
+            .ok_or(Error::Invalid)
+    }
+
+    fn data(&mut self) -> &mut [u8] {
+        // Shared with the client, which only touches it between calls
+        unsafe { self.data.as_mut() }
+    }
+
+    // The ports the client's sockets have, see `ping_core::ports`
+    fn ports_in_use(&self, sockets: &SocketSet<'_>) -> Vec<(u16, PortUse)> {
+        self.slots
+            .iter()
+            .flatten()
+            .filter_map(|slot| {
+                let port = slot.port?;
+                let bound = match slot.protocol {
+                    Protocol::Tcp => {
+                        sockets.get::<tcp::Socket>(slot.handle).state() == tcp::State::Listen
+                    }
+                    Protocol::Udp => true,
+                };
+                Some((
+                    port,
+                    if bound {
+                        PortUse::Bound
+                    } else {
+                        PortUse::Connected
+                    },
+                ))
+            })
+            .collect()
+    }
+
+    // `port` for `BIND` or `LISTEN`
+    fn check_port(&self, sockets: &SocketSet<'_>, port: u64) -> Result<u16, Error> {
+        let port = u16::try_from(port).map_err(|_| Error::Invalid)?;
+        self.ports
+            .check(port, &self.ports_in_use(sockets))
+            .map_err(from_port_error)
+    }
+
+    // A port for `CONNECT`, or `SEND` on an unbound UDP socket
+    fn free_port(&mut self, sockets: &SocketSet<'_>) -> Result<u16, Error> {
+        let in_use = self.ports_in_use(sockets);
+        self.ports.pick(&in_use).map_err(from_port_error)
+    }
+
+    fn open(&mut self, sockets: &mut SocketSet<'_>, args: &[u64]) -> Reply {
+        let protocol = args
+            .first()
+            .and_then(|&word| Protocol::from_word(word))
+            .ok_or(Error::Invalid)?;
+        let index = self
+            .slots
+            .iter()
+            .position(Option::is_none)
+            .ok_or(Error::NoSockets)?;
+        let handle = match protocol {
+            Protocol::Tcp => {
+                let mut socket = tcp::Socket::new(
+                    tcp::SocketBuffer::new(vec![0; config::socket_server::TCP_BUF_SIZE]),
+                    tcp::SocketBuffer::new(vec![0; config::socket_server::TCP_BUF_SIZE]),
+                );
+                socket.set_keep_alive(Some(config::services::TCP_KEEP_ALIVE));
+                socket.set_timeout(Some(config::services::TCP_TIMEOUT));
+                sockets.add(socket)
+            }
+            Protocol::Udp => {
+                let buffer = || {
+                    udp::PacketBuffer::new(
+                        vec![udp::PacketMetadata::EMPTY; config::socket_server::UDP_PACKETS],
+                        vec![0; config::socket_server::UDP_BUF_SIZE],
+                    )
+                };
+                sockets.add(udp::Socket::new(buffer(), buffer()))
+            }
+        };
+        self.slots[index] = Some(Slot {
+            handle,
+            protocol,
+            port: None,
+            status: SocketStatus::default(),
+        });
+        Ok(vec![index as u64])
+    }
+
+    fn bind(&mut self, sockets: &mut SocketSet<'_>, args: &[u64]) -> Reply {
+        let port = self.check_port(sockets, *args.get(1).ok_or(Error::Invalid)?)?;
+        let slot = self.slot_mut(args, Protocol::Udp)?;
+        sockets
+            .get_mut::<udp::Socket>(slot.handle)
+            .bind(port)
+            .map_err(|_| Error::Invalid)?;
+        slot.port = Some(port);
+        Ok(Vec::new())
+    }
+
+    fn listen(&mut self, sockets: &mut SocketSet<'_>, args: &[u64]) -> Reply {
+        let port = self.check_port(sockets, *args.get(1).ok_or(Error::Invalid)?)?;
+        let slot = self.slot_mut(args, Protocol::Tcp)?;
+        sockets
+            .get_mut::<tcp::Socket>(slot.handle)
+            .listen(port)
+            .map_err(|_| Error::Invalid)?;
+        slot.port = Some(port);
+        Ok(Vec::new())
+    }
+
+    fn connect(
+        &mut self,
+        iface: &mut Interface,
+        sockets: &mut SocketSet<'_>,
+        args: &[u64],
+    ) -> Reply {
+        let remote = args
+            .get(1..)
+            .and_then(Endpoint::from_words)
+            .ok_or(Error::Invalid)?;
+        if self.slot_mut(args, Protocol::Tcp)?.port.is_some() {
+            return Err(Error::Invalid);
+        }
+        let port = self.free_port(sockets)?;
+        let slot = self.slot_mut(args, Protocol::Tcp)?;
+        sockets
+            .get_mut::<tcp::Socket>(slot.handle)
+            .connect(iface.context(), to_smoltcp(remote), port)
+            .map_err(|_| Error::Invalid)?;
+        slot.port = Some(port);
+        Ok(Vec::new())
+    }
+
+    fn send(&mut self, sockets: &mut SocketSet<'_>, args: &[u64]) -> Reply {
+        let len = *args.get(1).ok_or(Error::Invalid)? as usize;
+        if len > self.data().len() {
+            return Err(Error::Invalid);
+        }
+        match self.slot(args)?.protocol {
+            Protocol::Tcp => {
+                let handle = self.slot(args)?.handle;
+                let data = &self.data()[..len];
+                let socket = sockets.get_mut::<tcp::Socket>(handle);
+                if !socket.may_send() {
+                    return Err(Error::NotConnected);
+                }
+                match socket.send_slice(data) {
+                    Ok(0) if len > 0 => Err(Error::WouldBlock),
+                    Ok(sent) => Ok(vec![sent as u64]),
+                    Err(_) => Err(Error::NotConnected),
+                }
+            }
+            Protocol::Udp => {
+                let remote = args
+                    .get(2..)
+                    .and_then(Endpoint::from_words)
+                    .ok_or(Error::Invalid)?;
+                let handle = self.slot(args)?.handle;
+                if self.slot(args)?.port.is_none() {
+                    let port = self.free_port(sockets)?;
+                    sockets
+                        .get_mut::<udp::Socket>(handle)
+                        .bind(port)
+                        .map_err(|_| Error::Invalid)?;
+                    self.slot_mut(args, Protocol::Udp)?.port = Some(port);
+                }
+                let data = &self.data()[..len];
+                let socket = sockets.get_mut::<udp::Socket>(handle);
+                match socket.send_slice(data, to_smoltcp(remote)) {
+                    Ok(()) => Ok(vec![len as u64]),
+                    Err(udp::SendError::BufferFull) => Err(Error::WouldBlock),
+                    Err(udp::SendError::Unaddressable) => Err(Error::Invalid),
+                }
+            }
+        }
+    }
+
+    fn recv(&mut self, sockets: &mut SocketSet<'_>, args: &[u64]) -> Reply {
+        let max_len = (*args.get(1).ok_or(Error::Invalid)? as usize).min(self.data().len());
+        let slot = self.slot(args)?;
+        let handle = slot.handle;
+        match slot.protocol {
+            Protocol::Tcp => {
+                let started = slot.port.is_some();
+                let data = &mut self.data()[..max_len];
+                let socket = sockets.get_mut::<tcp::Socket>(handle);
+                if socket.can_recv() {
+                    let len = socket.recv_slice(data).map_err(|_| Error::NotConnected)?;
+                    return Ok(vec![len as u64]);
+                }
+                match socket.state() {
+                    _ if !started => Err(Error::NotConnected),
+                    tcp::State::Listen
+                    | tcp::State::SynSent
+                    | tcp::State::SynReceived
+                    | tcp::State::Established
+                    | tcp::State::FinWait1
+                    | tcp::State::FinWait2 => Err(Error::WouldBlock),
+                    // The peer has finished, or the connection has gone
+                    _ => Ok(vec![0]),
+                }
+            }
+            Protocol::Udp => {
+                let data = self.data();
+                let socket = sockets.get_mut::<udp::Socket>(handle);
+                let (payload, meta) = socket.recv().map_err(|_| Error::WouldBlock)?;
+                // Truncated to fit, like a BSD socket
+                let len = payload.len().min(max_len);
+                unsafe { ptr::copy_nonoverlapping(payload.as_ptr(), data.as_mut_ptr(), len) };
+                let mut reply = vec![len as u64];
+                reply.extend(from_smoltcp(meta.endpoint).to_words());
+                Ok(reply)
+            }
+        }
+    }
+
+    fn resolve(
+        &mut self,
+        now: Instant,
+        sockets: &mut SocketSet<'_>,
+        resolver: &mut Resolver,
+        args: &[u64],
+    ) -> Reply {
+        let len = *args.first().ok_or(Error::Invalid)? as usize;
+        let name = self.data().get(..len).ok_or(Error::Invalid)?;
+        let name = String::from(core::str::from_utf8(name).map_err(|_| Error::Invalid)?);
+        self.resolving = None;
+        match resolver.resolve(now, sockets, &name) {
+            Lookup::Pending => {
+                self.resolving = Some(name);
+                Err(Error::WouldBlock)
+            }
+            Lookup::Resolved(addrs) => {
+                let addrs = &addrs[..addrs.len().min(api::MAX_ADDRS)];
+                let mut reply = vec![addrs.len() as u64];
+                for &addr in addrs {
+                    reply.extend(from_smoltcp_addr(addr).to_words());
+                }
+                Ok(reply)
+            }
+            Lookup::Failed => Err(Error::NotFound),
+        }
+    }
+
+    // Returns the socket to remove once its connection has closed
+    fn close(
+        &mut self,
+        sockets: &mut SocketSet<'_>,
+        args: &[u64],
+    ) -> Result<Option<SocketHandle>, Error> {
+        self.slot(args)?;
+        let slot = self.slots[args[0] as usize].take().unwrap();
+        match slot.protocol {
+            Protocol::Tcp => {
+                sockets.get_mut::<tcp::Socket>(slot.handle).close();
+                Ok(Some(slot.handle))
+            }
+            Protocol::Udp => {
+                sockets.remove(slot.handle);
+                Ok(None)
+            }
+        }
+    }
+}
+
+fn status(sockets: &SocketSet<'_>, slot: &Slot) -> SocketStatus {
+    match slot.protocol {
+        Protocol::Tcp => {
+            let socket = sockets.get::<tcp::Socket>(slot.handle);
+            SocketStatus {
+                active: socket.may_send(),
+                can_recv: socket.can_recv(),
+                can_send: socket.can_send(),
+                remote_closed: slot.port.is_some()
+                    && matches!(
+                        socket.state(),
+                        tcp::State::CloseWait
+                            | tcp::State::LastAck
+                            | tcp::State::Closing
+                            | tcp::State::TimeWait
+                            | tcp::State::Closed
+                    ),
+            }
+        }
+        Protocol::Udp => {
+            let socket = sockets.get::<udp::Socket>(slot.handle);
+            SocketStatus {
+                active: socket.is_open(),
+                can_recv: socket.can_recv(),
+                can_send: socket.can_send(),
+                remote_closed: false,
+            }
+        }
+    }
+}
+
+fn from_port_error(err: PortError) -> Error {
+    match err {
+        PortError::NotAllowed => Error::Permission,
+        PortError::InUse => Error::AddrInUse,
+    }
+}
+
+fn to_smoltcp(endpoint: Endpoint) -> IpEndpoint {
+    let addr = match endpoint.addr {
+        Addr::V4(addr) => IpAddress::Ipv4(Ipv4Address::from_bytes(&addr)),
+        Addr::V6(addr) => IpAddress::Ipv6(Ipv6Address::from_bytes(&addr)),
+    };
+    IpEndpoint::new(addr, endpoint.port)
+}
+
+fn from_smoltcp_addr(addr: IpAddress) -> Addr {
+    match addr {
+        IpAddress::Ipv4(addr) => Addr::V4(addr.0),
+        IpAddress::Ipv6(addr) => Addr::V6(addr.0),
+    }
+}
+
+fn from_smoltcp(endpoint: IpEndpoint) -> Endpoint {
+    Endpoint {
+        addr: from_smoltcp_addr(endpoint.addr),
+        port: endpoint.port,
+    }
+}
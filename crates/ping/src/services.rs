@@ -0,0 +1,161 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+use crate::config;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+use log::{debug, info};
+use smoltcp::{
+    iface::{SocketHandle, SocketSet},
+    socket::{tcp, udp},
+};
+
+// Echo (RFC 862) and discard (RFC 863) services.
+//
+// Each TCP echo socket serves one connection at a time and listens again once the connection
+// has closed, so up to `TCP_ECHO_CONNECTIONS` clients can be connected.
+
+const BUF_SIZE: usize = 2048;
+
+pub struct TcpEcho {
+    handles: Vec<SocketHandle>,
+}
+
+impl TcpEcho {
+    pub fn new(sockets: &mut SocketSet<'_>) -> Self {
+        let handles = (0..config::services::TCP_ECHO_CONNECTIONS)
+            .map(|_| {
+                let mut socket = tcp::Socket::new(
+                    tcp::SocketBuffer::new(vec![0; BUF_SIZE]),
+                    tcp::SocketBuffer::new(vec![0; BUF_SIZE]),
+                );
+                // Don't hold on to peers that went away without closing
+                socket.set_keep_alive(Some(config::services::TCP_KEEP_ALIVE));
+                socket.set_timeout(Some(config::services::TCP_TIMEOUT));
+                sockets.add(socket)
+            })
+            .collect();
+        info!("TCP echo on port {}", config::services::TCP_ECHO_PORT);
+        Self { handles }
+    }
+
+    pub fn poll(&mut self, sockets: &mut SocketSet<'_>) {
+        for handle in &self.handles {
+            let socket = sockets.get_mut::<tcp::Socket>(*handle);
+            if !socket.is_open() {
+                socket.listen(config::services::TCP_ECHO_PORT).unwrap();
+                continue;
+            }
+
+            // Only take what can be sent straight back, the rest waits in the receive buffer
+            while socket.can_recv() && socket.can_send() {
+                let mut buf = [0; BUF_SIZE];
+                let space = socket.send_capacity() - socket.send_queue();
+                let len = socket.recv_slice(&mut buf[..space]).unwrap();
+                socket.send_slice(&buf[..len]).unwrap();
+            }
+
+            // The peer has finished sending, close once everything has been echoed
+            if !socket.may_recv() && socket.may_send() && socket.send_queue() == 0 {
+                debug!(
+                    "TCP echo: closing connection from {:?}",
+                    socket.remote_endpoint()
+                );
+                socket.close();
+            }
+        }
+    }
+
+    /// Clients currently connected
+    pub fn connections(&self, sockets: &SocketSet<'_>) -> usize {
+        self.handles
+            .iter()
+            .filter(|handle| sockets.get::<tcp::Socket>(**handle).is_active())
+            .count()
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Counters {
+    pub packets: u64,
+    pub bytes: u64,
+    /// Echo replies that didn't fit in the transmit buffer
+    pub dropped: u64,
+}
+
+impl fmt::Display for Counters {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "packets: {}, bytes: {}, dropped: {}",
+            self.packets, self.bytes, self.dropped
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UdpMode {
+    Echo,
+    Discard,
+}
+
+impl UdpMode {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Echo => "echo",
+            Self::Discard => "discard",
+        }
+    }
+}
+
+pub struct UdpService {
+    handle: SocketHandle,
+    mode: UdpMode,
+    // Big enough for any datagram the socket can hold
+    scratch: Vec<u8>,
+    counters: Counters,
+}
+
+impl UdpService {
+    pub fn new(sockets: &mut SocketSet<'_>, mode: UdpMode, port: u16) -> Self {
+        let buffer = || {
+            udp::PacketBuffer::new(
+                vec![udp::PacketMetadata::EMPTY; config::services::UDP_PACKETS],
+                vec![0; config::services::UDP_BUF_SIZE],
+            )
+        };
+        let mut socket = udp::Socket::new(buffer(), buffer());
+        socket.bind(port).unwrap();
+        info!("UDP {mode:?} on port {port}");
+        Self {
+            handle: sockets.add(socket),
+            mode,
+            scratch: vec![0; config::services::UDP_BUF_SIZE],
+            counters: Counters::default(),
+        }
+    }
+
+    pub fn poll(&mut self, sockets: &mut SocketSet<'_>) {
+        let socket = sockets.get_mut::<udp::Socket>(self.handle);
+        while let Ok((len, meta)) = socket.recv_slice(&mut self.scratch) {
+            self.counters.packets += 1;
+            self.counters.bytes += len as u64;
+            if self.mode == UdpMode::Echo && socket.send_slice(&self.scratch[..len], meta).is_err()
+            {
+                self.counters.dropped += 1;
+            }
+        }
+    }
+
+    pub fn mode(&self) -> UdpMode {
+        self.mode
+    }
+
+    pub fn counters(&self) -> Counters {
+        self.counters
+    }
+}
@@ -0,0 +1,46 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+use crate::config;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::ptr;
+use ping_core::password::Password;
+use ping_core::settings::{Settings, StaticIpv4};
+
+// Network settings read at boot from the `.net_config` section of `ping`'s image, which the
+// Makefile fills in from the board's `NET_CONFIG` file, so the crates are built once for many
+// boards. The section holds the text described in `ping_core::settings` up to the first NUL
+// byte. Anything it leaves out keeps its default from `config`.
+
+// All NUL, and so the defaults, unless the Makefile packs a configuration into it
+#[used]
+#[link_section = ".net_config"]
+static PACKED: [u8; config::sizes::NET_CONFIG] = [0; config::sizes::NET_CONFIG];
+
+/// The settings packed into the image, or the defaults if there are none or they are invalid
+pub fn load() -> Settings {
+    // Volatile so the compiler can't assume the section still holds what it was built with
+    let packed: Vec<u8> = PACKED
+        .iter()
+        .map(|byte| unsafe { ptr::read_volatile(byte) })
+        .collect();
+    Settings::load(&packed, defaults())
+}
+
+fn defaults() -> Settings {
+    Settings {
+        dhcp: config::network::DHCP,
+        static_ipv4: StaticIpv4 {
+            cidr: config::network::STATIC_CIDR,
+            gateway: config::network::GATEWAY,
+        },
+        dns_servers: config::network::DNS_SERVERS.into(),
+        sntp_server: config::sntp::SERVER.into(),
+        syslog_collector: config::syslog::COLLECTOR.map(String::from),
+        shell_password: config::services::SHELL_PASSWORD.map(Password::new),
+    }
+}
@@ -0,0 +1,415 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+use crate::api;
+use crate::config;
+use crate::dns::{Lookup, Resolver};
+use crate::neighbors::Neighbors;
+use crate::pinger::Pinger;
+use crate::sntp::Sntp;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt::Write;
+use log::{info, warn, LevelFilter};
+use ping_core::password::Password;
+use smoltcp::{
+    iface::{Interface, SocketHandle, SocketSet},
+    socket::{tcp, Socket},
+    time::Instant,
+    wire::{EthernetAddress, IpCidr},
+};
+use timer_interface::Utc;
+
+// A line-oriented command shell for one client at a time, e.g. `nc <address> 23`. Commands run
+// to completion straight away, apart from those that wait on the network, which hold back
+// further input until they have finished.
+//
+// The client has to give the password first, and is disconnected after `MAX_ATTEMPTS` wrong
+// ones. Without a password the shell is off. Input beyond `MAX_INPUT` is left in the socket,
+// so TCP holds back a client typing ahead of a running command, and a client that doesn't read
+// its output is disconnected once `MAX_OUTPUT` has built up.
+
+const BUF_SIZE: usize = 4096;
+const MAX_LINE: usize = 256;
+const MAX_INPUT: usize = BUF_SIZE;
+const MAX_OUTPUT: usize = 4 * BUF_SIZE;
+const MAX_ATTEMPTS: u32 = 3;
+const PROMPT: &str = "> ";
+const PASSWORD_PROMPT: &str = "Password: ";
+
+const HELP: &str = "\
+help                  this list
+ifconfig              addresses, routes and link
+neigh                 ARP and IPv6 neighbor entries
+sockets               socket table
+date                  UTC date and time from SNTP
+log [level]           show or set the log level (off, error, warn, info, debug, trace)
+resolve <name>        look up a name's addresses
+ping <host> [count]   send ICMP echo requests
+quit                  close the session
+";
+
+/// What the commands can look at and change, besides the sockets
+pub struct Context<'a> {
+    pub now: Instant,
+    pub iface: &'a mut Interface,
+    pub mac: EthernetAddress,
+    pub neighbors: &'a Neighbors,
+    pub resolver: &'a mut Resolver,
+    pub sntp: &'a Sntp,
+}
+
+// A command waiting on the network
+enum Task {
+    Resolve {
+        name: String,
+        // Ping the first address with this count once resolved
+        ping: Option<u16>,
+    },
+    Ping(Pinger),
+}
+
+pub struct Shell {
+    handle: SocketHandle,
+    password: Option<Password>,
+    connected: bool,
+    authenticated: bool,
+    failed_attempts: u32,
+    // Received but not yet run
+    input: Vec<u8>,
+    // Waiting for room in the transmit buffer, with CRLF line endings
+    output: Vec<u8>,
+    task: Option<Task>,
+    next_ident: u16,
+    quitting: bool,
+}
+
+impl Shell {
+    pub fn new(sockets: &mut SocketSet<'_>, password: Option<Password>) -> Self {
+        let mut socket = tcp::Socket::new(
+            tcp::SocketBuffer::new(vec![0; BUF_SIZE]),
+            tcp::SocketBuffer::new(vec![0; BUF_SIZE]),
+        );
+        socket.set_keep_alive(Some(config::services::TCP_KEEP_ALIVE));
+        socket.set_timeout(Some(config::services::TCP_TIMEOUT));
+        match password {
+            Some(_) => info!("Shell on port {}", config::services::SHELL_PORT),
+            None => info!("Shell off, as no password is set"),
+        }
+        Self {
+            handle: sockets.add(socket),
+            password,
+            connected: false,
+            authenticated: false,
+            failed_attempts: 0,
+            input: Vec::new(),
+            output: Vec::new(),
+            task: None,
+            // Clear of the identifier the uplink check uses
+            next_ident: 0x100,
+            quitting: false,
+        }
+    }
+
+    pub fn poll(&mut self, sockets: &mut SocketSet<'_>, mut cx: Context<'_>) {
+        if self.password.is_none() {
+            return;
+        }
+        let socket = sockets.get_mut::<tcp::Socket>(self.handle);
+        if !socket.is_open() {
+            socket.listen(config::services::SHELL_PORT).unwrap();
+            self.reset(sockets);
+            return;
+        }
+        if !self.connected && socket.may_send() {
+            self.connected = true;
+            self.print(PASSWORD_PROMPT);
+        }
+        while socket.can_recv() && self.input.len() < MAX_INPUT {
+            let input = &mut self.input;
+            socket
+                .recv(|data| {
+                    let len = data.len().min(MAX_INPUT - input.len());
+                    input.extend_from_slice(&data[..len]);
+                    (len, ())
+                })
+                .unwrap();
+        }
+
+        let mut out = String::new();
+        loop {
+            if let Some(task) = self.task.take() {
+                self.task = self.poll_task(task, sockets, &mut cx, &mut out);
+                if self.task.is_none() {
+                    out.push_str(PROMPT);
+                }
+            }
+            if self.task.is_some() || self.quitting {
+                break;
+            }
+            let Some(end) = self.input.iter().position(|&b| b == b'\n') else {
+                if self.input.len() > MAX_LINE {
+                    self.input.clear();
+                    out.push_str("Line too long\n");
+                    out.push_str(self.prompt());
+                }
+                break;
+            };
+            let line: String = self
+                .input
+                .drain(..=end)
+                .filter(|b| b.is_ascii_graphic() || *b == b' ')
+                .map(char::from)
+                .collect();
+            if !self.authenticated {
+                self.log_in(line.trim(), &mut out);
+                continue;
+            }
+            self.run(line.trim(), &mut cx, sockets, &mut out);
+            if self.task.is_none() && !self.quitting {
+                out.push_str(PROMPT);
+            }
+        }
+        self.print(&out);
+
+        let socket = sockets.get_mut::<tcp::Socket>(self.handle);
+        if let Ok(sent) = socket.send_slice(&self.output) {
+            self.output.drain(..sent);
+        }
+        if self.output.len() > MAX_OUTPUT {
+            warn!(
+                "Shell client {:?} isn't reading its output, disconnecting",
+                socket.remote_endpoint()
+            );
+            socket.abort();
+            return;
+        }
+        // Close once everything has gone out, if either end is done
+        if (self.quitting || !socket.may_recv()) && self.output.is_empty() {
+            socket.close();
+        }
+    }
+
+    /// When `poll` next needs to be called, on top of what the sockets need
+    pub fn poll_at(&self) -> Option<Instant> {
+        match &self.task {
+            Some(Task::Ping(pinger)) => Some(pinger.poll_at()),
+            // Answers come in as packets
+            _ => None,
+        }
+    }
+
+    fn reset(&mut self, sockets: &mut SocketSet<'_>) {
+        if let Some(Task::Ping(pinger)) = self.task.take() {
+            pinger.finish(sockets, &mut String::new());
+        }
+        self.connected = false;
+        self.authenticated = false;
+        self.failed_attempts = 0;
+        self.input.clear();
+        self.output.clear();
+        self.quitting = false;
+    }
+
+    fn prompt(&self) -> &'static str {
+        match self.authenticated {
+            true => PROMPT,
+            false => PASSWORD_PROMPT,
+        }
+    }
+
+    fn log_in(&mut self, attempt: &str, out: &mut String) {
+        let password = self.password.as_ref().unwrap();
+        if password.matches(attempt) {
+            self.authenticated = true;
+            out.push_str("Type 'help' for a list of commands\n");
+            out.push_str(PROMPT);
+            return;
+        }
+        self.failed_attempts += 1;
+        out.push_str("Wrong password\n");
+        if self.failed_attempts < MAX_ATTEMPTS {
+            out.push_str(PASSWORD_PROMPT);
+        } else {
+            warn!("Shell: too many wrong passwords, disconnecting");
+            self.quitting = true;
+        }
+    }
+
+    fn print(&mut self, text: &str) {
+        for line in text.split_inclusive('\n') {
+            match line.strip_suffix('\n') {
+                Some(line) => {
+                    self.output.extend_from_slice(line.as_bytes());
+                    self.output.extend_from_slice(b"\r\n");
+                }
+                None => self.output.extend_from_slice(line.as_bytes()),
+            }
+        }
+    }
+
+    // Returns the task to carry on with, if it hasn't finished
+    fn poll_task(
+        &mut self,
+        task: Task,
+        sockets: &mut SocketSet<'_>,
+        cx: &mut Context<'_>,
+        out: &mut String,
+    ) -> Option<Task> {
+        match task {
+            Task::Resolve { name, ping } => {
+                match cx.resolver.resolve(cx.now, sockets, &name) {
+                    Lookup::Pending => Some(Task::Resolve { name, ping }),
+                    Lookup::Failed => {
+                        writeln!(out, "Failed to resolve {name}").unwrap();
+                        None
+                    }
+                    Lookup::Resolved(addrs) => match ping {
+                        Some(count) => {
+                            let pinger =
+                                Pinger::new(sockets, addrs[0], count, self.next_ident, cx.now, out);
+                            self.next_ident = self.next_ident.wrapping_add(1).max(0x100);
+                            self.poll_task(Task::Ping(pinger), sockets, cx, out)
+                        }
+                        None => {
+                            for addr in addrs {
+                                writeln!(out, "{name} has address {addr}").unwrap();
+                            }
+                            None
+                        }
+                    },
+                }
+            }
+            Task::Ping(mut pinger) => {
+                if pinger.poll(cx.now, cx.iface, sockets, out) {
+                    pinger.finish(sockets, out);
+                    None
+                } else {
+                    Some(Task::Ping(pinger))
+                }
+            }
+        }
+    }
+
+    fn run(&mut self, line: &str, cx: &mut Context<'_>, sockets: &SocketSet<'_>, out: &mut String) {
+        let mut args = line.split_whitespace();
+        let Some(command) = args.next() else {
+            return;
+        };
+        let args: Vec<&str> = args.collect();
+        match (command, args.as_slice()) {
+            ("help", []) => out.push_str(HELP),
+            ("ifconfig", []) => ifconfig(cx, out),
+            ("neigh" | "arp", []) => neigh(cx, out),
+            ("sockets", []) => socket_table(sockets, out),
+            ("date", []) => match cx.sntp.utc_us(cx.now) {
+                Some(utc) => {
+                    writeln!(out, "{} (drift {}ppb)", Utc(utc), cx.sntp.drift_ppb()).unwrap()
+                }
+                None => writeln!(out, "Not synchronized with the SNTP server yet").unwrap(),
+            },
+            ("log", []) => writeln!(out, "Log level: {}", log::max_level()).unwrap(),
+            ("log", [level]) => match level.parse::<LevelFilter>() {
+                Ok(level) => {
+                    log::set_max_level(level);
+                    writeln!(out, "Log level: {level}").unwrap();
+                }
+                Err(_) => writeln!(out, "Unknown log level: {level}").unwrap(),
+            },
+            ("resolve", [name]) => {
+                self.task = Some(Task::Resolve {
+                    name: String::from(*name),
+                    ping: None,
+                })
+            }
+            ("ping", [host, rest @ ..]) if rest.len() <= 1 => {
+                let count = match rest.first().map(|count| count.parse::<u16>()) {
+                    None => config::services::SHELL_PING_COUNT,
+                    Some(Ok(count)) if count > 0 => count,
+                    Some(_) => {
+                        writeln!(out, "Count must be a number from 1 to {}", u16::MAX).unwrap();
+                        return;
+                    }
+                };
+                self.task = Some(Task::Resolve {
+                    name: String::from(*host),
+                    ping: Some(count),
+                });
+            }
+            ("quit" | "exit", []) => self.quitting = true,
+            _ => writeln!(out, "Unknown command, type 'help' for a list").unwrap(),
+        }
+    }
+}
+
+fn ifconfig(cx: &mut Context<'_>, out: &mut String) {
+    writeln!(out, "mac     {}", api::Mac(cx.mac)).unwrap();
+    match api::driver_status() {
+        Some(status) => writeln!(
+            out,
+            "link    {} Mbit/s {} duplex, {}",
+            status.speed_mbps,
+            if status.full_duplex { "full" } else { "half" },
+            if status.running { "running" } else { "stopped" }
+        ),
+        None => writeln!(out, "link    unknown"),
+    }
+    .unwrap();
+    for cidr in cx.iface.ip_addrs() {
+        let family = match cidr {
+            IpCidr::Ipv4(_) => "inet ",
+            IpCidr::Ipv6(_) => "inet6",
+        };
+        writeln!(out, "{family}   {cidr}").unwrap();
+    }
+    for (cidr, router) in api::routes(cx.iface) {
+        writeln!(out, "route   {cidr} via {router}").unwrap();
+    }
+}
+
+fn neigh(cx: &Context<'_>, out: &mut String) {
+    for neighbor in cx.neighbors.current(cx.now) {
+        writeln!(
+            out,
+            "{:<40} {}  expires in {}s",
+            alloc::format!("{}", neighbor.ip),
+            api::Mac(neighbor.mac),
+            (neighbor.expires - cx.now).secs()
+        )
+        .unwrap();
+    }
+}
+
+fn socket_table(sockets: &SocketSet<'_>, out: &mut String) {
+    for (handle, socket) in sockets.iter() {
+        // smoltcp's Display impls ignore padding
+        write!(out, "{:<4} ", alloc::format!("{handle}")).unwrap();
+        match socket {
+            Socket::Tcp(socket) => {
+                write!(out, "tcp     {:<12}", alloc::format!("{}", socket.state())).unwrap();
+                match (socket.local_endpoint(), socket.remote_endpoint()) {
+                    (Some(local), Some(remote)) => writeln!(out, " {local} <-> {remote}"),
+                    _ => writeln!(out),
+                }
+            }
+            Socket::Udp(socket) => writeln!(out, "udp     {}", socket.endpoint()),
+            Socket::Icmp(socket) => writeln!(
+                out,
+                "icmp    {}",
+                if socket.is_open() { "bound" } else { "unbound" }
+            ),
+            Socket::Raw(socket) => writeln!(
+                out,
+                "raw     {} {}",
+                socket.ip_version(),
+                socket.ip_protocol()
+            ),
+            Socket::Dhcpv4(_) => writeln!(out, "dhcpv4"),
+        }
+        .unwrap();
+    }
+}
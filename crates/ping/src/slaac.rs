@@ -0,0 +1,254 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+use alloc::vec;
+use log::{debug, info, warn};
+use smoltcp::{
+    iface::{Interface, SocketHandle, SocketSet},
+    phy::ChecksumCapabilities,
+    socket::raw,
+    time::{Duration, Instant},
+    wire::{
+        EthernetAddress, Icmpv6Packet, Icmpv6Repr, IpCidr, IpProtocol, IpVersion, Ipv6Address,
+        Ipv6Cidr, Ipv6Packet, Ipv6Repr, NdiscPrefixInfoFlags, NdiscPrefixInformation, NdiscRepr,
+        RawHardwareAddress,
+    },
+};
+
+// IPv6 stateless address autoconfiguration (RFC 4862). smoltcp does neighbor discovery and
+// answers echo requests itself, but ignores router advertisements, so they are picked up here
+// with a raw socket.
+//
+// Interface identifiers come from the MAC, so duplicate address detection is skipped. Only one
+// prefix is tracked, as smoltcp only parses the first prefix of an advertisement.
+
+const PREFIX_LEN: u8 = 64;
+const HOP_LIMIT: u8 = 255;
+
+const MAX_RTR_SOLICITATIONS: u8 = 3;
+const RTR_SOLICITATION_INTERVAL: Duration = Duration::from_secs(4);
+
+// Modified EUI-64
+fn address(prefix: &Ipv6Address, mac: &EthernetAddress) -> Ipv6Address {
+    let mac = mac.as_bytes();
+    let mut addr = [0; 16];
+    addr[..8].copy_from_slice(&prefix.as_bytes()[..8]);
+    addr[8..].copy_from_slice(&[
+        mac[0] ^ 0x02,
+        mac[1],
+        mac[2],
+        0xFF,
+        0xFE,
+        mac[3],
+        mac[4],
+        mac[5],
+    ]);
+    Ipv6Address::from_bytes(&addr)
+}
+
+struct RouterAdvert {
+    router: Ipv6Address,
+    router_lifetime: Duration,
+    prefix_info: Option<NdiscPrefixInformation>,
+}
+
+impl RouterAdvert {
+    fn parse(packet: &[u8]) -> Option<Self> {
+        let packet = Ipv6Packet::new_checked(packet).ok()?;
+        let ipv6_repr = Ipv6Repr::parse(&packet).ok()?;
+        let icmp_packet = Icmpv6Packet::new_checked(packet.payload()).ok()?;
+        let icmp_repr = Icmpv6Repr::parse(
+            &ipv6_repr.src_addr.into(),
+            &ipv6_repr.dst_addr.into(),
+            &icmp_packet,
+            &ChecksumCapabilities::default(),
+        )
+        .ok()?;
+        match icmp_repr {
+            // Advertisements from off the link are forged
+            Icmpv6Repr::Ndisc(NdiscRepr::RouterAdvert {
+                router_lifetime,
+                prefix_info,
+                ..
+            }) if ipv6_repr.hop_limit == HOP_LIMIT => Some(Self {
+                router: ipv6_repr.src_addr,
+                router_lifetime,
+                prefix_info,
+            }),
+            _ => None,
+        }
+    }
+}
+
+pub struct Slaac {
+    handle: SocketHandle,
+    mac: EthernetAddress,
+    link_local: Ipv6Address,
+    // With when they expire
+    global: Option<(Ipv6Cidr, Instant)>,
+    router: Option<(Ipv6Address, Instant)>,
+    solicitations_left: u8,
+    next_solicitation: Option<Instant>,
+}
+
+impl Slaac {
+    pub fn new(
+        sockets: &mut SocketSet<'_>,
+        iface: &mut Interface,
+        mac: EthernetAddress,
+        now: Instant,
+    ) -> Self {
+        let link_local = address(&Ipv6Address::new(0xFE80, 0, 0, 0, 0, 0, 0, 0), &mac);
+        info!("IPv6 link-local address: {link_local}");
+        iface.update_ip_addrs(|addrs| {
+            addrs
+                .push(IpCidr::Ipv6(Ipv6Cidr::new(link_local, PREFIX_LEN)))
+                .unwrap();
+        });
+
+        let socket = raw::Socket::new(
+            IpVersion::Ipv6,
+            IpProtocol::Icmpv6,
+            raw::PacketBuffer::new(vec![raw::PacketMetadata::EMPTY; 4], vec![0; 4096]),
+            raw::PacketBuffer::new(vec![raw::PacketMetadata::EMPTY; 1], vec![0; 256]),
+        );
+        Self {
+            handle: sockets.add(socket),
+            mac,
+            link_local,
+            global: None,
+            router: None,
+            solicitations_left: MAX_RTR_SOLICITATIONS,
+            // Rather than waiting for the next periodic advertisement
+            next_solicitation: Some(now),
+        }
+    }
+
+    pub fn poll(&mut self, now: Instant, iface: &mut Interface, sockets: &mut SocketSet<'_>) {
+        let socket = sockets.get_mut::<raw::Socket>(self.handle);
+        while let Ok(packet) = socket.recv() {
+            if let Some(advert) = RouterAdvert::parse(packet) {
+                self.router_advert(now, iface, &advert);
+            }
+        }
+
+        if self.global.is_some_and(|(_, expires)| expires <= now) {
+            self.set_global(iface, None);
+        }
+        if self.router.is_some_and(|(_, expires)| expires <= now) {
+            self.set_router(iface, None);
+        }
+
+        if self.next_solicitation.is_some_and(|at| at <= now) {
+            self.solicit_router(socket);
+            self.solicitations_left -= 1;
+            self.next_solicitation =
+                (self.solicitations_left > 0).then(|| now + RTR_SOLICITATION_INTERVAL);
+        }
+    }
+
+    /// When `poll` next needs to be called, on top of what the sockets need
+    pub fn poll_at(&self) -> Option<Instant> {
+        [
+            self.global.map(|(_, expires)| expires),
+            self.router.map(|(_, expires)| expires),
+            self.next_solicitation,
+        ]
+        .into_iter()
+        .flatten()
+        .min()
+    }
+
+    fn router_advert(&mut self, now: Instant, iface: &mut Interface, advert: &RouterAdvert) {
+        debug!("Router advertisement from {}", advert.router);
+        self.next_solicitation = None;
+
+        if advert.router_lifetime > Duration::ZERO {
+            self.set_router(iface, Some((advert.router, now + advert.router_lifetime)));
+        } else if self
+            .router
+            .is_some_and(|(router, _)| router == advert.router)
+        {
+            self.set_router(iface, None);
+        }
+
+        let Some(prefix) = &advert.prefix_info else {
+            return;
+        };
+        if !prefix.flags.contains(NdiscPrefixInfoFlags::ADDRCONF)
+            || prefix.prefix_len != PREFIX_LEN
+            || prefix.prefix.is_link_local()
+        {
+            return;
+        }
+        let cidr = Ipv6Cidr::new(address(&prefix.prefix, &self.mac), PREFIX_LEN);
+        if prefix.valid_lifetime > Duration::ZERO {
+            self.set_global(iface, Some((cidr, now + prefix.valid_lifetime)));
+        } else if self.global.is_some_and(|(global, _)| global == cidr) {
+            self.set_global(iface, None);
+        }
+    }
+
+    fn set_global(&mut self, iface: &mut Interface, global: Option<(Ipv6Cidr, Instant)>) {
+        let old = self.global.map(|(cidr, _)| cidr);
+        let new = global.map(|(cidr, _)| cidr);
+        if old != new {
+            match new {
+                Some(cidr) => info!("IPv6 address: {cidr}"),
+                None => warn!("IPv6 address {} expired", old.unwrap()),
+            }
+            iface.update_ip_addrs(|addrs| {
+                if let Some(old) = old {
+                    addrs.retain(|addr| *addr != IpCidr::Ipv6(old));
+                }
+                if let Some(new) = new {
+                    addrs.push(IpCidr::Ipv6(new)).unwrap();
+                }
+            });
+        }
+        self.global = global;
+    }
+
+    fn set_router(&mut self, iface: &mut Interface, router: Option<(Ipv6Address, Instant)>) {
+        match router {
+            Some((router, _)) => {
+                iface.routes_mut().add_default_ipv6_route(router).unwrap();
+            }
+            None => {
+                iface.routes_mut().remove_default_ipv6_route();
+            }
+        }
+        self.router = router;
+    }
+
+    fn solicit_router(&self, socket: &mut raw::Socket<'_>) {
+        let icmp_repr = Icmpv6Repr::Ndisc(NdiscRepr::RouterSolicit {
+            lladdr: Some(RawHardwareAddress::from_bytes(self.mac.as_bytes())),
+        });
+        let ipv6_repr = Ipv6Repr {
+            src_addr: self.link_local,
+            dst_addr: Ipv6Address::LINK_LOCAL_ALL_ROUTERS,
+            next_header: IpProtocol::Icmpv6,
+            payload_len: icmp_repr.buffer_len(),
+            hop_limit: HOP_LIMIT,
+        };
+        let buf = match socket.send(ipv6_repr.buffer_len() + icmp_repr.buffer_len()) {
+            Ok(buf) => buf,
+            Err(err) => {
+                warn!("Failed to send router solicitation: {err:?}");
+                return;
+            }
+        };
+        let mut packet = Ipv6Packet::new_unchecked(buf);
+        ipv6_repr.emit(&mut packet);
+        icmp_repr.emit(
+            &ipv6_repr.src_addr.into(),
+            &ipv6_repr.dst_addr.into(),
+            &mut Icmpv6Packet::new_unchecked(packet.payload_mut()),
+            &ChecksumCapabilities::default(),
+        );
+    }
+}
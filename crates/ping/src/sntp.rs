@@ -0,0 +1,251 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+use crate::config;
+use crate::dns::{Lookup, Resolver};
+use crate::syslog;
+use alloc::string::String;
+use alloc::vec;
+use log::{debug, info, warn};
+use ping_core::sntp::{parse_reply, request, LOCAL_PORT, NTP_PORT, PACKET_LEN};
+use smoltcp::{
+    iface::{SocketHandle, SocketSet},
+    socket::udp,
+    time::Instant,
+    wire::{IpAddress, IpEndpoint},
+};
+use timer_interface::{Timer, Utc};
+
+// SNTP client (RFC 4330), with the packets themselves in `ping_core::sntp`. Each answer gives
+// the offset of UTC from the time since boot, and successive offsets give the drift between the
+// two clocks. The timer PD is handed the latest offset and drift, so other components can ask it
+// for UTC, and so is `syslog::LOGGER`, which stamps every record and can't wait on a call.
+
+// Each new drift measurement moves the estimate this fraction of the way
+const DRIFT_WEIGHT: i64 = 4;
+
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    now_us: i64,
+    // UTC minus time since boot
+    offset_us: i64,
+}
+
+/// UTC as the time since boot, an offset and a drift, from the latest answer
+#[derive(Debug, Clone, Copy)]
+pub struct Clock {
+    sample: Sample,
+    drift_ppb: i64,
+}
+
+impl Clock {
+    /// UTC when the time since boot is `now_us`, in microseconds since the Unix epoch
+    pub fn utc_us(&self, now_us: i64) -> Option<u64> {
+        let elapsed = now_us - self.sample.now_us;
+        let utc = now_us + self.sample.offset_us + elapsed * self.drift_ppb / 1_000_000_000;
+        u64::try_from(utc).ok()
+    }
+}
+
+enum State {
+    Idle {
+        next_at: Instant,
+    },
+    Resolving,
+    Waiting {
+        server: IpAddress,
+        // When the request was queued, until the stack has sent it, which can wait on the
+        // server's or router's link-layer address
+        sent_at: Instant,
+        sent: bool,
+        // Echoed back by the server as the originate timestamp
+        cookie: u64,
+    },
+}
+
+pub struct Sntp {
+    handle: SocketHandle,
+    timer: Timer,
+    // A name or an address
+    server: String,
+    state: State,
+    last: Option<Sample>,
+    drift_ppb: i64,
+    // Until then there is only one sample
+    drift_measured: bool,
+}
+
+impl Sntp {
+    pub fn new(sockets: &mut SocketSet<'_>, timer: Timer, server: String, now: Instant) -> Self {
+        let buffer = |packets| {
+            udp::PacketBuffer::new(
+                vec![udp::PacketMetadata::EMPTY; packets],
+                vec![0; packets * PACKET_LEN],
+            )
+        };
+        // Room for only the one request, so `can_send` says when it has gone out
+        let mut socket = udp::Socket::new(buffer(2), buffer(1));
+        socket.bind(LOCAL_PORT).unwrap();
+        info!("SNTP server: {server}");
+        Self {
+            handle: sockets.add(socket),
+            timer,
+            server,
+            state: State::Idle { next_at: now },
+            last: None,
+            drift_ppb: 0,
+            drift_measured: false,
+        }
+    }
+
+    pub fn poll(&mut self, now: Instant, sockets: &mut SocketSet<'_>, resolver: &mut Resolver) {
+        self.dispatched(now, sockets);
+        match self.state {
+            State::Idle { next_at } if next_at <= now => self.state = State::Resolving,
+            State::Waiting { sent_at, .. } if sent_at + config::sntp::TIMEOUT <= now => {
+                warn!("No answer from SNTP server");
+                self.retry(now);
+            }
+            _ => {}
+        }
+
+        if let State::Resolving = self.state {
+            match resolver.resolve(now, sockets, &self.server) {
+                Lookup::Pending => {}
+                Lookup::Failed => self.retry(now),
+                Lookup::Resolved(addrs) => self.send(now, sockets, addrs[0]),
+            }
+        }
+
+        let socket = sockets.get_mut::<udp::Socket>(self.handle);
+        while let Ok((packet, meta)) = socket.recv() {
+            let State::Waiting {
+                server,
+                sent_at,
+                sent: true,
+                cookie,
+            } = self.state
+            else {
+                continue;
+            };
+            if meta.endpoint.addr != server {
+                continue;
+            }
+            match parse_reply(packet, cookie) {
+                Some((receive_us, transmit_us)) => {
+                    self.update(sent_at, now, receive_us, transmit_us);
+                    self.state = State::Idle {
+                        next_at: now + config::sntp::INTERVAL,
+                    };
+                }
+                None => debug!("Ignoring SNTP packet from {server}"),
+            }
+        }
+    }
+
+    /// Call after the stack has been polled, so the request's send time is when it went out
+    pub fn dispatched(&mut self, now: Instant, sockets: &SocketSet<'_>) {
+        if let State::Waiting { sent_at, sent, .. } = &mut self.state {
+            if !*sent && sockets.get::<udp::Socket>(self.handle).can_send() {
+                *sent_at = now;
+                *sent = true;
+            }
+        }
+    }
+
+    /// When `poll` next needs to be called, on top of what the sockets need
+    pub fn poll_at(&self) -> Option<Instant> {
+        match self.state {
+            State::Idle { next_at } => Some(next_at),
+            // Answers come in as packets
+            State::Resolving => None,
+            State::Waiting { sent_at, .. } => Some(sent_at + config::sntp::TIMEOUT),
+        }
+    }
+
+    /// UTC at `now`, in microseconds since the Unix epoch, once the server has answered
+    pub fn utc_us(&self, now: Instant) -> Option<u64> {
+        self.clock()?.utc_us(now.total_micros())
+    }
+
+    pub fn clock(&self) -> Option<Clock> {
+        Some(Clock {
+            sample: self.last?,
+            drift_ppb: self.drift_ppb,
+        })
+    }
+
+    /// In parts per billion, positive when UTC gains on the time since boot
+    pub fn drift_ppb(&self) -> i64 {
+        self.drift_ppb
+    }
+
+    fn retry(&mut self, now: Instant) {
+        self.state = State::Idle {
+            next_at: now + config::sntp::RETRY_INTERVAL,
+        };
+    }
+
+    fn send(&mut self, now: Instant, sockets: &mut SocketSet<'_>, server: IpAddress) {
+        let socket = sockets.get_mut::<udp::Socket>(self.handle);
+        // Only needs to be unique to this request
+        let cookie = now.total_micros() as u64;
+        match socket.send_slice(&request(cookie), IpEndpoint::new(server, NTP_PORT)) {
+            Ok(()) => {
+                self.state = State::Waiting {
+                    server,
+                    sent_at: now,
+                    sent: false,
+                    cookie,
+                }
+            }
+            Err(err) => {
+                warn!("Failed to send SNTP request: {err:?}");
+                self.retry(now);
+            }
+        }
+    }
+
+    // The offset is measured at the midpoint of the exchange, on the assumption that the
+    // network delay is the same both ways
+    fn update(&mut self, sent_at: Instant, now: Instant, receive_us: u64, transmit_us: u64) {
+        let (t1, t4) = (sent_at.total_micros(), now.total_micros());
+        let (t2, t3) = (receive_us as i64, transmit_us as i64);
+        let sample = Sample {
+            now_us: t4,
+            offset_us: ((t2 - t1) + (t3 - t4)) / 2,
+        };
+
+        let utc_us = (sample.now_us + sample.offset_us) as u64;
+        match self.last {
+            Some(last) if sample.now_us > last.now_us => {
+                let measured = ((sample.offset_us - last.offset_us) as i128 * 1_000_000_000
+                    / (sample.now_us - last.now_us) as i128) as i64;
+                self.drift_ppb = match self.drift_measured {
+                    true => self.drift_ppb + (measured - self.drift_ppb) / DRIFT_WEIGHT,
+                    false => measured,
+                };
+                self.drift_measured = true;
+                debug!(
+                    "SNTP offset moved {}us, drift {}ppb",
+                    sample.offset_us - last.offset_us,
+                    self.drift_ppb
+                );
+            }
+            Some(_) => {}
+            None => info!("UTC time: {}", Utc(utc_us)),
+        }
+        self.last = Some(sample);
+
+        syslog::LOGGER.set_clock(self.clock());
+        if let Err(err) = self
+            .timer
+            .set_utc(sample.now_us as u64, utc_us, self.drift_ppb)
+        {
+            warn!("Failed to pass the time on to the timer: {err:?}");
+        }
+    }
+}
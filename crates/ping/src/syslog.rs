@@ -0,0 +1,337 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+use crate::config;
+use crate::dns::{Lookup, Resolver};
+use crate::sntp::Clock;
+use alloc::collections::VecDeque;
+use alloc::string::String;
+use alloc::vec;
+use core::fmt::Write;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use eth_driver_interface::LogRecord;
+use log::{info, Level, Log, Metadata, Record};
+use smoltcp::{
+    iface::{Interface, SocketHandle, SocketSet},
+    socket::udp,
+    time::{Duration, Instant},
+    wire::{IpAddress, IpCidr, IpEndpoint},
+};
+use spin::Mutex;
+use timer_interface::{counter_us, Utc};
+
+// Remote syslog (RFC 5424 over UDP, RFC 5426). Every record still goes to the serial console,
+// and is also queued here with its UTC time, once SNTP has set the clock. The time is worked out
+// from the generic timer's counter rather than asked of the timer PD: a call would overwrite the
+// message registers of whatever `ping` was handling when it logged. The queue is
+// sent to the collector while there is an address to send from, at no more than `RATE`
+// messages a second. When the network is down for long enough for it to fill up, the oldest
+// records are dropped and the collector is told how many.
+//
+// The driver's records are passed on too, see `forward`. They have already been to the serial
+// console, and have the time `ping` took them from the driver.
+
+const SYSLOG_PORT: u16 = 514;
+const LOCAL_PORT: u16 = 50_514;
+// The longest message RFC 5426 guarantees to get through over IPv4
+const MAX_MESSAGE: usize = 480;
+// MSGID is limited to 32 characters
+const MAX_MSGID: usize = 32;
+const TX_PACKETS: usize = 8;
+const APP_NAME: &str = "ping";
+
+pub static LOGGER: Logger = Logger {
+    serial: &config::log::LOGGER,
+    clock: Mutex::new(None),
+    enabled: AtomicBool::new(true),
+    queue: Mutex::new(VecDeque::new()),
+    dropped: AtomicUsize::new(0),
+};
+
+struct Entry {
+    severity: u8,
+    app_name: &'static str,
+    utc_us: Option<u64>,
+    target: String,
+    message: String,
+}
+
+pub struct Logger {
+    serial: &'static sel4_logging::Logger,
+    clock: Mutex<Option<Clock>>,
+    // Until it is known whether there is a collector, records from boot are kept
+    enabled: AtomicBool,
+    queue: Mutex<VecDeque<Entry>>,
+    dropped: AtomicUsize,
+}
+
+impl Logger {
+    /// Queues a record from the driver
+    pub fn forward(&self, app_name: &'static str, record: &LogRecord) {
+        self.dropped
+            .fetch_add(record.dropped as usize, Ordering::Relaxed);
+        let level = match record.level {
+            1 => Level::Error,
+            2 => Level::Warn,
+            3 => Level::Info,
+            4 => Level::Debug,
+            _ => Level::Trace,
+        };
+        self.push(Entry {
+            severity: severity(level),
+            app_name,
+            utc_us: self.utc_us(),
+            target: String::from(record.target()),
+            message: String::from(record.message()),
+        });
+    }
+
+    /// Sets the clock records are stamped with, from SNTP's latest answer
+    pub fn set_clock(&self, clock: Option<Clock>) {
+        *self.clock.lock() = clock;
+    }
+
+    fn utc_us(&self) -> Option<u64> {
+        self.clock.lock().as_ref()?.utc_us(counter_us() as i64)
+    }
+
+    fn push(&self, entry: Entry) {
+        if !self.enabled.load(Ordering::Relaxed) {
+            return;
+        }
+        // Nothing logs while the queue is locked, but a record that comes in while it is can
+        // only be dropped
+        let Some(mut queue) = self.queue.try_lock() else {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            return;
+        };
+        if queue.len() == config::syslog::QUEUE_LEN {
+            queue.pop_front();
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+        queue.push_back(entry);
+    }
+
+    fn disable(&self) {
+        self.enabled.store(false, Ordering::Relaxed);
+        self.queue.lock().clear();
+        self.dropped.store(0, Ordering::Relaxed);
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.serial.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        self.serial.log(record);
+        if !self.enabled.load(Ordering::Relaxed) || !self.enabled(record.metadata()) {
+            return;
+        }
+        self.push(Entry {
+            severity: severity(record.level()),
+            app_name: APP_NAME,
+            utc_us: self.utc_us(),
+            target: String::from(record.target()),
+            message: alloc::format!("{}", record.args()),
+        });
+    }
+
+    fn flush(&self) {
+        self.serial.flush();
+    }
+}
+
+fn severity(level: Level) -> u8 {
+    match level {
+        Level::Error => 3,
+        Level::Warn => 4,
+        Level::Info => 6,
+        Level::Debug | Level::Trace => 7,
+    }
+}
+
+enum State {
+    Idle { next_at: Instant },
+    Resolving,
+    Ready(IpAddress),
+}
+
+/// Sends what `LOGGER` queues to the collector
+pub struct Syslog {
+    handle: SocketHandle,
+    // A name or an address
+    collector: Option<String>,
+    state: State,
+    // Messages that can be sent straight away
+    tokens: u32,
+    refilled_at: Instant,
+    sent: u64,
+}
+
+impl Syslog {
+    pub fn new(sockets: &mut SocketSet<'_>, collector: Option<String>, now: Instant) -> Self {
+        let rx_buffer = udp::PacketBuffer::new(vec![], vec![]);
+        let tx_buffer = udp::PacketBuffer::new(
+            vec![udp::PacketMetadata::EMPTY; TX_PACKETS],
+            vec![0; TX_PACKETS * MAX_MESSAGE],
+        );
+        let mut socket = udp::Socket::new(rx_buffer, tx_buffer);
+        socket.bind(LOCAL_PORT).unwrap();
+        match &collector {
+            Some(collector) => info!("Syslog collector: {collector}"),
+            None => LOGGER.disable(),
+        }
+        Self {
+            handle: sockets.add(socket),
+            collector,
+            state: State::Idle { next_at: now },
+            tokens: config::syslog::BURST,
+            refilled_at: now,
+            sent: 0,
+        }
+    }
+
+    pub fn poll(
+        &mut self,
+        now: Instant,
+        iface: &mut Interface,
+        sockets: &mut SocketSet<'_>,
+        resolver: &mut Resolver,
+    ) {
+        self.refill(now);
+        let Some(collector) = &self.collector else {
+            return;
+        };
+        if let State::Idle { next_at } = self.state {
+            if next_at <= now {
+                self.state = State::Resolving;
+            }
+        }
+        if let State::Resolving = self.state {
+            self.state = match resolver.resolve(now, sockets, collector) {
+                Lookup::Pending => State::Resolving,
+                Lookup::Failed => State::Idle {
+                    next_at: now + config::syslog::RETRY_INTERVAL,
+                },
+                Lookup::Resolved(addrs) => State::Ready(addrs[0]),
+            };
+        }
+        let State::Ready(collector) = self.state else {
+            return;
+        };
+        // Keep the queue until there is an address to send from
+        let Some(hostname) = source_address(iface, &collector) else {
+            return;
+        };
+
+        let socket = sockets.get_mut::<udp::Socket>(self.handle);
+        let endpoint = IpEndpoint::new(collector, SYSLOG_PORT);
+        while self.tokens > 0 && socket.can_send() {
+            let dropped = LOGGER.dropped.swap(0, Ordering::Relaxed);
+            let message = if dropped > 0 {
+                let entry = Entry {
+                    severity: severity(Level::Warn),
+                    app_name: APP_NAME,
+                    utc_us: LOGGER.utc_us(),
+                    target: String::from(module_path!()),
+                    message: alloc::format!("{dropped} log messages dropped"),
+                };
+                format(&entry, hostname)
+            } else {
+                match LOGGER.queue.lock().pop_front() {
+                    Some(entry) => format(&entry, hostname),
+                    None => break,
+                }
+            };
+            if socket.send_slice(message.as_bytes(), endpoint).is_err() {
+                // The buffer is nearly full, count the message rather than holding on to it
+                LOGGER.dropped.fetch_add(dropped.max(1), Ordering::Relaxed);
+                break;
+            }
+            self.tokens -= 1;
+            self.sent += 1;
+        }
+    }
+
+    /// When `poll` next needs to be called, on top of what the sockets need
+    pub fn poll_at(&self) -> Option<Instant> {
+        self.collector.as_ref()?;
+        match self.state {
+            State::Idle { next_at } => Some(next_at),
+            // Answers come in as packets
+            State::Resolving => None,
+            // Also when the socket's buffer was full, to try again without spinning
+            State::Ready(_) => {
+                let waiting =
+                    !LOGGER.queue.lock().is_empty() || LOGGER.dropped.load(Ordering::Relaxed) > 0;
+                waiting.then(|| self.refilled_at + token_interval())
+            }
+        }
+    }
+
+    /// Messages sent to the collector
+    pub fn sent(&self) -> u64 {
+        self.sent
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let interval = token_interval();
+        let new = (now - self.refilled_at).total_micros() / interval.total_micros();
+        if new > 0 {
+            self.tokens = (self.tokens as u64 + new).min(config::syslog::BURST as u64) as u32;
+            self.refilled_at += interval * new as u32;
+        }
+    }
+}
+
+fn token_interval() -> Duration {
+    Duration::from_micros(1_000_000 / config::syslog::RATE as u64)
+}
+
+// Where smoltcp will send from, which also serves as the HOSTNAME
+fn source_address(iface: &Interface, collector: &IpAddress) -> Option<IpAddress> {
+    iface
+        .ip_addrs()
+        .iter()
+        .find_map(|cidr| match (cidr, collector) {
+            (IpCidr::Ipv4(cidr), IpAddress::Ipv4(_)) => Some(cidr.address().into()),
+            (IpCidr::Ipv6(cidr), IpAddress::Ipv6(collector))
+                if cidr.address().is_link_local() == collector.is_link_local() =>
+            {
+                Some(cidr.address().into())
+            }
+            _ => None,
+        })
+}
+
+// PRI VERSION TIMESTAMP HOSTNAME APP-NAME PROCID MSGID STRUCTURED-DATA MSG
+fn format(entry: &Entry, hostname: IpAddress) -> String {
+    let mut out = String::new();
+    write!(out, "<{}>1 ", config::syslog::FACILITY * 8 + entry.severity).unwrap();
+    match entry.utc_us {
+        Some(utc_us) => write!(out, "{}", Utc(utc_us)),
+        None => write!(out, "-"),
+    }
+    .unwrap();
+    write!(out, " {hostname} {} - ", entry.app_name).unwrap();
+    let msgid: String = entry
+        .target
+        .chars()
+        .filter(|c| c.is_ascii_graphic())
+        .take(MAX_MSGID)
+        .collect();
+    out.push_str(if msgid.is_empty() { "-" } else { &msgid });
+    out.push_str(" - ");
+    for c in entry.message.chars() {
+        if out.len() + c.len_utf8() > MAX_MESSAGE {
+            break;
+        }
+        out.push(c);
+    }
+    out
+}
@@ -0,0 +1,123 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+use crate::api;
+use crate::config;
+use crate::dns::{Lookup, Resolver};
+use crate::pinger::{Millis, Pinger, Summary};
+use alloc::string::String;
+use log::{info, warn};
+use smoltcp::{
+    iface::{Interface, SocketSet},
+    time::Instant,
+};
+
+// Checks the way out of the local network by pinging `config::uplink::TARGET`, or the default
+// router, every `INTERVAL`, logging the outcome. The last one is served in `/status`.
+
+// The identifier the echo requests carry, the shell's runs start above it
+const IDENT: u16 = 0xb;
+
+pub struct Uplink {
+    pinger: Option<Pinger>,
+    next_at: Instant,
+    // Waiting for `TARGET` to resolve
+    resolving: bool,
+    last: Option<Summary>,
+}
+
+impl Uplink {
+    pub fn new(now: Instant) -> Self {
+        Self {
+            pinger: None,
+            // Give DHCP and router discovery a chance first
+            next_at: now + config::uplink::INTERVAL,
+            resolving: false,
+            last: None,
+        }
+    }
+
+    pub fn poll(
+        &mut self,
+        now: Instant,
+        iface: &mut Interface,
+        sockets: &mut SocketSet<'_>,
+        resolver: &mut Resolver,
+    ) {
+        // Nothing is shown of the run itself
+        let mut out = String::new();
+        if self.pinger.is_none() && self.next_at <= now {
+            self.resolving = false;
+            match target(now, iface, sockets, resolver) {
+                Lookup::Pending => self.resolving = true,
+                Lookup::Resolved(addrs) => {
+                    self.next_at = now + config::uplink::INTERVAL;
+                    self.pinger = Some(Pinger::new(
+                        sockets,
+                        addrs[0],
+                        config::uplink::COUNT,
+                        IDENT,
+                        now,
+                        &mut out,
+                    ))
+                }
+                Lookup::Failed => {
+                    self.next_at = now + config::uplink::INTERVAL;
+                    match config::uplink::TARGET {
+                        Some(target) => warn!("Uplink check: can't resolve {target}"),
+                        None => warn!("Uplink check: no default route"),
+                    }
+                }
+            }
+        }
+        let Some(pinger) = &mut self.pinger else {
+            return;
+        };
+        if !pinger.poll(now, iface, sockets, &mut out) {
+            return;
+        }
+        let summary = self.pinger.take().unwrap().finish(sockets, &mut out);
+        match summary.rtt {
+            Some(rtt) => info!(
+                "Uplink check of {}: {summary}, rtt avg {} ms",
+                summary.target,
+                Millis(rtt.avg_us)
+            ),
+            None => warn!("Uplink check of {}: {summary}", summary.target),
+        }
+        self.last = Some(summary);
+    }
+
+    /// When `poll` next needs to be called, on top of what the sockets need
+    pub fn poll_at(&self) -> Option<Instant> {
+        match &self.pinger {
+            Some(pinger) => Some(pinger.poll_at()),
+            // The answer comes in as packets
+            None if self.resolving => None,
+            None => Some(self.next_at),
+        }
+    }
+
+    /// The last completed check
+    pub fn last(&self) -> Option<&Summary> {
+        self.last.as_ref()
+    }
+}
+
+fn target(
+    now: Instant,
+    iface: &mut Interface,
+    sockets: &mut SocketSet<'_>,
+    resolver: &mut Resolver,
+) -> Lookup {
+    match config::uplink::TARGET {
+        Some(target) => resolver.resolve(now, sockets, target),
+        None => match api::default_router(iface) {
+            Some(router) => Lookup::Resolved([router].into()),
+            None => Lookup::Failed,
+        },
+    }
+}
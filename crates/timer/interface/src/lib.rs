@@ -0,0 +1,133 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+#![no_std]
+
+use core::fmt;
+use sel4_microkit::{with_msg_regs, with_msg_regs_mut, Channel, MessageInfo};
+
+// Protected calls from a client to the timer PD on the client's channel. The message label
+// selects the operation, replies are labelled `OK` or `ERROR`. Times are in microseconds.
+//
+// Expired timeouts are signalled by a notification on the same channel.
+
+/// Reply with the time since boot in message register 0. Never goes backwards.
+pub const TIME: u64 = 0;
+/// Notify the client once message register 0 microseconds have passed. Replaces the timeout
+/// the client already had set, if any.
+pub const SET_TIMEOUT: u64 = 1;
+/// Forget the client's timeout, if any
+pub const CANCEL_TIMEOUT: u64 = 2;
+/// Reply with the UTC time in message register 0, in microseconds since the Unix epoch.
+/// Replies `ERROR` until the time source has called `SET_UTC`.
+pub const UTC: u64 = 3;
+/// Only from the time source. Message register 0 is a time since boot, 1 the UTC time at that
+/// moment, and 2 how fast UTC gains on the time since boot, in parts per billion (signed).
+pub const SET_UTC: u64 = 4;
+
+pub const OK: u64 = 0;
+pub const ERROR: u64 = 1;
+
+/// The timer PD replied with `ERROR`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimerError;
+
+/// Calls to the timer PD over `channel`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timer {
+    channel: Channel,
+}
+
+impl Timer {
+    pub const fn new(channel: Channel) -> Self {
+        Self { channel }
+    }
+
+    pub fn channel(&self) -> Channel {
+        self.channel
+    }
+
+    fn call(&self, label: u64, args: &[u64]) -> Result<(), TimerError> {
+        with_msg_regs_mut(|mrs| mrs[..args.len()].copy_from_slice(args));
+        let reply = self.channel.pp_call(MessageInfo::new(label, args.len()));
+        match reply.label() {
+            OK => Ok(()),
+            _ => Err(TimerError),
+        }
+    }
+
+    pub fn now_us(&self) -> Result<u64, TimerError> {
+        self.call(TIME, &[])?;
+        Ok(with_msg_regs(|mrs| mrs[0]))
+    }
+
+    pub fn set_timeout(&self, timeout_us: u64) -> Result<(), TimerError> {
+        self.call(SET_TIMEOUT, &[timeout_us])
+    }
+
+    pub fn cancel_timeout(&self) -> Result<(), TimerError> {
+        self.call(CANCEL_TIMEOUT, &[])
+    }
+
+    pub fn utc_us(&self) -> Result<u64, TimerError> {
+        self.call(UTC, &[])?;
+        Ok(with_msg_regs(|mrs| mrs[0]))
+    }
+
+    pub fn set_utc(&self, now_us: u64, utc_us: u64, drift_ppb: i64) -> Result<(), TimerError> {
+        self.call(SET_UTC, &[now_us, utc_us, drift_ppb as u64])
+    }
+}
+
+/// The time since boot that `TIME` replies with, read straight from the generic timer's counter,
+/// which seL4 lets user level read. For timestamps taken too often for a call to the timer PD.
+#[cfg(target_arch = "aarch64")]
+pub fn counter_us() -> u64 {
+    use core::arch::asm;
+
+    let count: u64;
+    let freq: u64;
+    unsafe {
+        asm!("mrs {}, cntvct_el0", out(reg) count, options(nomem, nostack, preserves_flags));
+        asm!("mrs {}, cntfrq_el0", out(reg) freq, options(nomem, nostack, preserves_flags));
+    }
+    (count as u128 * 1_000_000 / freq as u128) as u64
+}
+
+#[cfg(not(target_arch = "aarch64"))]
+pub fn counter_us() -> u64 {
+    0
+}
+
+/// Shows microseconds since the Unix epoch as an RFC 3339 UTC date and time
+pub struct Utc(pub u64);
+
+impl fmt::Display for Utc {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let secs = self.0 / 1_000_000;
+        let (days, secs_of_day) = ((secs / 86_400) as i64, secs % 86_400);
+
+        // Howard Hinnant's civil_from_days
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let doe = z.rem_euclid(146_097);
+        let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = yoe + era * 400 + (month <= 2) as i64;
+
+        write!(
+            f,
+            "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}.{:06}Z",
+            secs_of_day / 3600,
+            secs_of_day / 60 % 60,
+            secs_of_day % 60,
+            self.0 % 1_000_000
+        )
+    }
+}
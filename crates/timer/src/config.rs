@@ -0,0 +1,38 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+pub mod channels {
+    use sel4_microkit::Channel;
+
+    pub const TTC_IRQ: Channel = Channel::new(0);
+    /// Each client has its own timeout
+    /// `ping` and `daytime`
+    pub const CLIENTS: [Channel; 2] = [Channel::new(1), Channel::new(2)];
+    /// The client allowed to set the UTC time, `ping` with its SNTP client
+    pub const UTC_SOURCE: Channel = Channel::new(1);
+}
+
+pub mod ttc {
+    /// TTC0's LPD_LSBUS reference clock on the ZCU102
+    pub const CLOCK_HZ: u64 = 100_000_000;
+}
+
+pub mod log {
+    use sel4_logging::{LevelFilter, Logger, LoggerBuilder};
+    use sel4_microkit::debug_print;
+
+    const LOG_LEVEL: LevelFilter = {
+        // LevelFilter::Trace
+        // LevelFilter::Debug
+        LevelFilter::Info
+        // LevelFilter::Warn
+    };
+
+    pub static LOGGER: Logger = LoggerBuilder::const_default()
+        .level_filter(LOG_LEVEL)
+        .write(|s| debug_print!("{}", s))
+        .build();
+}
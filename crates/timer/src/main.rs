@@ -0,0 +1,159 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+#![no_std]
+#![no_main]
+
+use log::{error, info, warn};
+use sel4_microkit::{
+    memory_region_symbol, protection_domain, with_msg_regs, with_msg_regs_mut, Channel, Handler,
+    Infallible, MessageInfo,
+};
+use timer_interface as timer;
+
+mod config;
+mod ttc;
+
+use config::channels::{CLIENTS, TTC_IRQ, UTC_SOURCE};
+use ttc::Ttc;
+
+// Time since boot comes from the generic timer's counter, which can't go backwards and doesn't
+// wrap for centuries. The kernel keeps the generic timer's interrupts to itself, so timeouts
+// are driven by a TTC counter instead.
+//
+// UTC is worked out from the last reference the time source gave, carried forward at the rate
+// it measured.
+
+#[protection_domain]
+fn init() -> HandlerImpl {
+    config::log::LOGGER.set().unwrap();
+    let ttc = Ttc::new(memory_region_symbol!(ttc_register_block: *mut ()).as_ptr());
+    if let Err(err) = TTC_IRQ.irq_ack() {
+        error!("Failed to ack TTC IRQ: {err:?}");
+    }
+    info!("Initialized Timer");
+    HandlerImpl {
+        ttc,
+        deadlines: [None; CLIENTS.len()],
+        utc: None,
+    }
+}
+
+// Longer timeouts are split over several TTC intervals
+fn ticks(us: u64) -> u32 {
+    let ticks = us.saturating_mul(config::ttc::CLOCK_HZ / 1_000_000);
+    ticks.try_into().unwrap_or(u32::MAX)
+}
+
+#[derive(Debug, Clone, Copy)]
+struct UtcReference {
+    now_us: u64,
+    utc_us: u64,
+    drift_ppb: i64,
+}
+
+impl UtcReference {
+    fn utc_us(&self, now_us: u64) -> u64 {
+        let elapsed = now_us.saturating_sub(self.now_us) as i128;
+        let utc = self.utc_us as i128 + elapsed + elapsed * self.drift_ppb as i128 / 1_000_000_000;
+        utc as u64
+    }
+}
+
+struct HandlerImpl {
+    ttc: Ttc,
+    // Absolute, in microseconds since boot
+    deadlines: [Option<u64>; CLIENTS.len()],
+    utc: Option<UtcReference>,
+}
+
+impl HandlerImpl {
+    // Notify clients whose timeouts have expired and arm the TTC for the earliest of the rest
+    fn update(&mut self) {
+        let now = timer::counter_us();
+        for (client, deadline) in CLIENTS.iter().zip(self.deadlines.iter_mut()) {
+            if deadline.is_some_and(|deadline| deadline <= now) {
+                *deadline = None;
+                client.notify();
+            }
+        }
+        match self.deadlines.iter().flatten().min() {
+            Some(deadline) => self.ttc.start(ticks(deadline - now)),
+            None => self.ttc.stop(),
+        }
+    }
+
+    fn client(channel: Channel) -> Option<usize> {
+        CLIENTS.iter().position(|client| *client == channel)
+    }
+}
+
+impl Handler for HandlerImpl {
+    type Error = Infallible;
+
+    fn notified(&mut self, channel: Channel) -> Result<(), Self::Error> {
+        if channel == TTC_IRQ {
+            self.ttc.ack();
+            self.update();
+            if let Err(err) = TTC_IRQ.irq_ack() {
+                error!("Failed to ack TTC IRQ: {err:?}");
+            }
+        }
+        Ok(())
+    }
+
+    fn protected(
+        &mut self,
+        channel: Channel,
+        msg_info: MessageInfo,
+    ) -> Result<MessageInfo, Self::Error> {
+        let Some(client) = Self::client(channel) else {
+            warn!("Call from unknown channel: {}", channel.index());
+            return Ok(MessageInfo::new(timer::ERROR, 0));
+        };
+        let reply = match msg_info.label() {
+            timer::TIME => {
+                with_msg_regs_mut(|mrs| mrs[0] = timer::counter_us());
+                MessageInfo::new(timer::OK, 1)
+            }
+            timer::SET_TIMEOUT if msg_info.count() > 0 => {
+                let timeout = with_msg_regs(|mrs| mrs[0]);
+                self.deadlines[client] = Some(timer::counter_us().saturating_add(timeout));
+                self.update();
+                MessageInfo::new(timer::OK, 0)
+            }
+            timer::CANCEL_TIMEOUT => {
+                self.deadlines[client] = None;
+                self.update();
+                MessageInfo::new(timer::OK, 0)
+            }
+            timer::UTC => match self.utc {
+                Some(utc) => {
+                    with_msg_regs_mut(|mrs| mrs[0] = utc.utc_us(timer::counter_us()));
+                    MessageInfo::new(timer::OK, 1)
+                }
+                None => MessageInfo::new(timer::ERROR, 0),
+            },
+            timer::SET_UTC if channel == UTC_SOURCE && msg_info.count() >= 3 => {
+                let (now_us, utc_us, drift_ppb) = with_msg_regs(|mrs| (mrs[0], mrs[1], mrs[2]));
+                if self.utc.is_none() {
+                    info!("UTC time set");
+                }
+                self.utc = Some(UtcReference {
+                    now_us,
+                    utc_us,
+                    drift_ppb: drift_ppb as i64,
+                });
+                MessageInfo::new(timer::OK, 0)
+            }
+            label => {
+                warn!("Unknown timer request: {label}");
+                MessageInfo::new(timer::ERROR, 0)
+            }
+        };
+        Ok(reply)
+    }
+}
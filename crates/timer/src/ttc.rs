@@ -0,0 +1,109 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+use core::ops::Deref;
+use tock_registers::interfaces::{Readable, Writeable};
+use tock_registers::register_bitfields;
+use tock_registers::register_structs;
+use tock_registers::registers::{ReadOnly, ReadWrite};
+
+// ZynqMP triple timer counter. Only the first of the three counters is used, as a one-shot
+// timer: it counts up to the interval value and interrupts.
+
+register_bitfields![u32,
+    pub ClockControl [
+        PRESCALE_ENABLE OFFSET(0) NUMBITS(1) [],
+        PRESCALE_VALUE OFFSET(1) NUMBITS(4) [],
+        EXTERNAL_CLOCK OFFSET(5) NUMBITS(1) [],
+    ],
+    pub CounterControl [
+        DISABLE OFFSET(0) NUMBITS(1) [],
+        INTERVAL_MODE OFFSET(1) NUMBITS(1) [],
+        DECREMENT OFFSET(2) NUMBITS(1) [],
+        MATCH_MODE OFFSET(3) NUMBITS(1) [],
+        RESET OFFSET(4) NUMBITS(1) [],
+        // Waveform output is enabled when clear
+        WAVE_DISABLE OFFSET(5) NUMBITS(1) [],
+    ],
+    pub Interrupt [
+        INTERVAL OFFSET(0) NUMBITS(1) [],
+        MATCH_1 OFFSET(1) NUMBITS(1) [],
+        MATCH_2 OFFSET(2) NUMBITS(1) [],
+        MATCH_3 OFFSET(3) NUMBITS(1) [],
+        OVERFLOW OFFSET(4) NUMBITS(1) [],
+        EVENT_OVERFLOW OFFSET(5) NUMBITS(1) [],
+    ]
+];
+
+const NUM_COUNTERS: usize = 3;
+
+register_structs! {
+    pub TtcRegisters {
+        (0x00 => pub clock_control: [ReadWrite<u32, ClockControl::Register>; NUM_COUNTERS]),
+        (0x0C => pub counter_control: [ReadWrite<u32, CounterControl::Register>; NUM_COUNTERS]),
+        (0x18 => pub counter_value: [ReadOnly<u32>; NUM_COUNTERS]),
+        (0x24 => pub interval_counter: [ReadWrite<u32>; NUM_COUNTERS]),
+        (0x30 => _reserved0),
+        // Cleared on read
+        (0x54 => pub interrupt_status: [ReadOnly<u32, Interrupt::Register>; NUM_COUNTERS]),
+        (0x60 => pub interrupt_enable: [ReadWrite<u32, Interrupt::Register>; NUM_COUNTERS]),
+        (0x6C => @END),
+    }
+}
+
+// Counter 0 interrupts on IRQ 68
+const COUNTER: usize = 0;
+
+// The interval must be non-zero
+const MIN_TICKS: u32 = 2;
+
+pub struct Ttc {
+    ptr: *const TtcRegisters,
+}
+
+impl Ttc {
+    pub fn new(ptr: *mut ()) -> Self {
+        let ttc = Self { ptr: ptr.cast() };
+        ttc.stop();
+        // Count the reference clock undivided
+        ttc.clock_control[COUNTER].set(0);
+        ttc.interrupt_enable[COUNTER].write(Interrupt::INTERVAL::SET);
+        ttc.ack();
+        ttc
+    }
+
+    /// Interrupt once after `ticks` reference clock cycles
+    pub fn start(&self, ticks: u32) {
+        self.stop();
+        self.interval_counter[COUNTER].set(ticks.max(MIN_TICKS));
+        self.ack();
+        self.counter_control[COUNTER].write(
+            CounterControl::INTERVAL_MODE::SET
+                + CounterControl::RESET::SET
+                + CounterControl::WAVE_DISABLE::SET,
+        );
+    }
+
+    pub fn stop(&self) {
+        self.counter_control[COUNTER]
+            .write(CounterControl::DISABLE::SET + CounterControl::WAVE_DISABLE::SET);
+    }
+
+    /// Clear the counter's interrupt, returns whether the interval had elapsed
+    pub fn ack(&self) -> bool {
+        self.interrupt_status[COUNTER]
+            .extract()
+            .is_set(Interrupt::INTERVAL)
+    }
+}
+
+impl Deref for Ttc {
+    type Target = TtcRegisters;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.ptr }
+    }
+}
@@ -0,0 +1,69 @@
+#!/usr/bin/env python3
+#
+# Copyright 2024, DornerWorks
+#
+# SPDX-License-Identifier: BSD-2-Clause
+#
+
+"""Appends the crc32 line to a network configuration for the .net_config section of ping's
+image, and pads it with NULs to the size of the section in ping.elf. Given another section, as
+for net_filter's .filter_rules, does the same for that.
+
+    support/net-config.py ping.elf board.cfg > net.cfg
+    support/net-config.py net-filter.elf rules.cfg .filter_rules > rules.cfg.packed
+
+The Makefile runs this for NET_CONFIG and FILTER_RULES. See crates/ping/core/src/settings.rs
+and crates/net-filter/core/src/table.rs for the formats. An existing crc32 line is replaced.
+"""
+
+import struct
+import sys
+import zlib
+
+DEFAULT_SECTION = ".net_config"
+
+
+def section_size(path, name):
+    """The size of the section called name in the 64-bit ELF file at path"""
+    with open(path, "rb") as f:
+        elf = f.read()
+    if elf[:4] != b"\x7fELF" or elf[4] != 2:
+        sys.exit(f"{path} isn't a 64-bit ELF file")
+    endian = "<" if elf[5] == 1 else ">"
+    (shoff,) = struct.unpack_from(endian + "Q", elf, 0x28)
+    shentsize, shnum, shstrndx = struct.unpack_from(endian + "HHH", elf, 0x3A)
+
+    def header(index):
+        # sh_name, sh_offset and sh_size
+        at = shoff + index * shentsize
+        (name_at,) = struct.unpack_from(endian + "I", elf, at)
+        offset, size = struct.unpack_from(endian + "QQ", elf, at + 0x18)
+        return name_at, offset, size
+
+    _, strtab, _ = header(shstrndx)
+    for index in range(shnum):
+        name_at, _, size = header(index)
+        start = strtab + name_at
+        if elf[start : elf.index(b"\0", start)] == name:
+            return size
+    sys.exit(f"{path} has no {name.decode()} section")
+
+
+def main():
+    if len(sys.argv) not in (3, 4):
+        sys.exit(__doc__)
+    section = sys.argv[3] if len(sys.argv) == 4 else DEFAULT_SECTION
+    size = section_size(sys.argv[1], section.encode())
+    with open(sys.argv[2], encoding="utf-8") as f:
+        lines = [line.rstrip("\r\n") for line in f]
+    while lines and (not lines[-1].strip() or lines[-1].startswith("crc32=")):
+        lines.pop()
+    body = "".join(line + "\n" for line in lines).encode()
+    out = body + b"crc32=%08x\n" % zlib.crc32(body)
+    if len(out) >= size:
+        sys.exit(f"Configuration is {len(out)} bytes, the section holds {size - 1}")
+    sys.stdout.buffer.write(out.ljust(size, b"\0"))
+
+
+if __name__ == "__main__":
+    main()